@@ -1,5 +1,8 @@
-use crate::models::cv::{CV, CVAnalysisStatus};
-use crate::storage::memory::CVStorage;
+use crate::models::cv::CVAnalysisStatus;
+use crate::services::cv_document;
+use crate::services::profile_completion::ProfileCompletion;
+use crate::models::experience::{WorkHistory, YearMonth};
+use crate::storage::memory::{CVStorage, CVVersionStorage, EducationStorage, WorkExperienceStorage};
 use ic_cdk::api::time;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -7,6 +10,10 @@ use std::collections::HashMap;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisFeedback {
     total_score: f32,
+    /// Best university grade from the education profile, normalized to 0–1.
+    normalized_gpa: Option<f32>,
+    /// Years of experience from the work history, overlaps counted once.
+    years_of_experience: f32,
     section_scores: HashMap<String, SectionScore>,
     priority_improvements: Vec<String>,
     overall_feedback: String,
@@ -33,17 +40,28 @@ impl CVAnalyzer {
             return Err(format!("Failed to update CV status: {}", e));
         }
 
-        let analysis_result = Self::perform_analysis(&cv.content);
+        let normalized_gpa = EducationStorage::get_by_user(&cv.user_id)
+            .and_then(|record| record.best_normalized_grade());
+        let work_history = WorkHistory::new(
+            WorkExperienceStorage::get_by_user(&cv.user_id),
+            YearMonth::from_timestamp(time()),
+        );
+        let analysis_result =
+            Self::perform_analysis(&cv_document::cv_text(&cv), normalized_gpa, &work_history);
 
         cv.ai_analysis_status = CVAnalysisStatus::Completed;
-        cv.ai_feedback = Some(serde_json::to_string(&analysis_result).map_err(|e| e.to_string())?);
+        let feedback = serde_json::to_string(&analysis_result).map_err(|e| e.to_string())?;
+        cv.ai_feedback = Some(feedback.clone());
 
-        CVStorage::update_cv(cv).map_err(|e| format!("Failed to store analysis results: {}", e))?;
+        CVStorage::update_cv(cv.clone()).map_err(|e| format!("Failed to store analysis results: {}", e))?;
+        CVVersionStorage::attach_analysis(&cv.id, cv.version, feedback)
+            .map_err(|e| format!("Failed to store analysis results: {}", e))?;
+        ProfileCompletion::refresh(&cv.user_id);
 
         Ok(())
     }
 
-    fn perform_analysis(content: &str) -> AnalysisFeedback {
+    fn perform_analysis(content: &str, normalized_gpa: Option<f32>, work_history: &WorkHistory) -> AnalysisFeedback {
         let mut section_scores = HashMap::new();
         
         section_scores.insert(
@@ -52,11 +70,11 @@ impl CVAnalyzer {
         );
         section_scores.insert(
             "experience".to_string(),
-            Self::analyze_experience_section(content)
+            Self::analyze_experience_section(content, work_history)
         );
         section_scores.insert(
             "education".to_string(),
-            Self::analyze_education_section(content)
+            Self::analyze_education_section(content, normalized_gpa)
         );
         section_scores.insert(
             "skills".to_string(),
@@ -70,6 +88,8 @@ impl CVAnalyzer {
 
         AnalysisFeedback {
             total_score,
+            normalized_gpa,
+            years_of_experience: work_history.total_years,
             section_scores,
             priority_improvements,
             overall_feedback,
@@ -109,7 +129,7 @@ impl CVAnalyzer {
         }
     }
 
-    fn analyze_experience_section(content: &str) -> SectionScore {
+    fn analyze_experience_section(content: &str, work_history: &WorkHistory) -> SectionScore {
         let mut score = 0.0;
         let mut suggestions = Vec::new();
 
@@ -130,6 +150,12 @@ impl CVAnalyzer {
             suggestions.push("Add dates to your work experience".to_string());
         }
 
+        if work_history.experiences.is_empty() {
+            suggestions.push("Add your roles to your work history".to_string());
+        } else if !work_history.overlaps.is_empty() {
+            suggestions.push("Check the dates of overlapping roles in your work history".to_string());
+        }
+
         SectionScore {
             score: score.min(10.0),
             feedback: if score >= 7.0 {
@@ -141,7 +167,7 @@ impl CVAnalyzer {
         }
     }
 
-    fn analyze_education_section(content: &str) -> SectionScore {
+    fn analyze_education_section(content: &str, normalized_gpa: Option<f32>) -> SectionScore {
         let mut score = 0.0;
         let mut suggestions = Vec::new();
 
@@ -156,6 +182,19 @@ impl CVAnalyzer {
             suggestions.push("Add more details about your education".to_string());
         }
 
+        let lower = content.to_lowercase();
+        let mentions_grade = ["gpa", "ipk", "grade", "honours", "cum laude"]
+            .iter()
+            .any(|word| lower.contains(word));
+        match normalized_gpa {
+            Some(gpa) if gpa >= 0.75 && !mentions_grade => {
+                suggestions.push("Your grades are strong; mention your GPA on the CV".to_string());
+            }
+            Some(gpa) if gpa >= 0.75 => score += 1.0,
+            None => suggestions.push("Add your grades to your education profile".to_string()),
+            _ => {}
+        }
+
         SectionScore {
             score: score.min(10.0),
             feedback: if score >= 7.0 {
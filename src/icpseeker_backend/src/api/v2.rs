@@ -0,0 +1,985 @@
+//! Version 2 of the public API. Every endpoint returns `Result<T, ApiError>`
+//! and is exported with a `v2_` prefix; the unprefixed v1 endpoints in the
+//! crate root are thin adapters over these and are deprecated.
+
+use candid::{candid_method, Principal};
+use ic_cdk::api;
+
+use crate::auth::access::{
+    self, anyone, can_browse_candidates, can_manage_ai_config, can_moderate_content, can_wipe_data, is_admin,
+    is_controller, is_signed_in, Admin, AdminPermission, Admins, BusinessMember, BusinessMembers, Role,
+};
+use crate::auth::{AuthService, DuplicateProfile, IdentityStore, LinkCode, LinkedPrincipal, Session};
+use crate::encryption::Encryption;
+use crate::models::bank::BankInformation;
+use crate::models::blob::{BlobInfo, BlobUsage, ExtractedText, UploadProgress, UploadSession};
+use crate::models::chat::{ChatMessage, ChatSession};
+use crate::models::cv::{CV, CVDiff, CVVersion, CVVersionInfo};
+use crate::models::cv_document::{CvDocument, CvEntry};
+use crate::models::education::{
+    EducationDetails, EducationRecord, HighSchoolEducation, NormalizedGrade, UniversityEducation,
+};
+use crate::models::experience::{WorkExperience, WorkHistory, YearMonth};
+use crate::models::payout::{BankAccount, PayoutMethod, PayoutMethodRecord};
+use crate::models::pagination::{Page, PageRequest};
+use crate::models::UserProfile;
+use crate::services::chat::ChatService;
+use crate::services::cv_document::CvDocumentService;
+use crate::services::cv_history::CVHistoryService;
+use crate::services::email_verification::{EmailVerificationService, EmailVerificationStatus};
+use crate::services::key_rotation::{EncryptionStatus, KeyRotationService, KeyRotationStatus};
+use crate::services::mail::{MailConfig, MailService};
+use crate::services::profile_completion::{ProfileChecklist, ProfileCompletion};
+use crate::storage::blob::BlobStorage;
+use crate::storage::memory::{
+    BankStorage, ChatSessionStorage, EducationStorage, IndexRebuildReport, PayoutStorage, UserStorage,
+    WorkExperienceStorage,
+};
+use crate::storage::{CVStorage, CVVersionStorage};
+use crate::types::errors::ApiError;
+use crate::validation::bank::{self, BankSuggestion};
+use crate::validation::ledger;
+use crate::validation::phone::{self, PhoneCountry};
+use crate::validation::Validate;
+use crate::{
+    caller_user_id, BankInfoPayload, BeginUploadPayload, CVAnalyzer, CreateCVPayload,
+    CreateUserPayload, EducationPayload, PayoutMethodInput, PayoutMethodPayload, UpdateCVPayload, UpdateUserPayload, UploadChunkPayload,
+    WorkExperiencePayload, OPENAI_API_KEY,
+};
+
+fn require_signed_in() -> Result<Principal, ApiError> {
+    let principal = ic_cdk::caller();
+    if principal == Principal::anonymous() {
+        return Err(ApiError::Unauthorized("Anonymous principals cannot do this".to_string()));
+    }
+    Ok(principal)
+}
+
+/// The caller's user id, once their profile exists.
+fn require_user() -> Result<String, ApiError> {
+    let user_id = caller_user_id();
+    if !UserStorage::exists(&user_id) {
+        return Err(ApiError::NotFound("User not found".to_string()));
+    }
+    Ok(user_id)
+}
+
+/// Stores the profile's phone number in E.164 form for its country.
+fn canonicalize_phone(user: &mut UserProfile) -> Result<(), ApiError> {
+    user.phone_number = phone::to_e164(&user.phone_number, &user.country)
+        .map_err(|(code, message)| ApiError::invalid("phone_number", code, message))?;
+    Ok(())
+}
+
+fn invalid_cursor(message: String) -> ApiError {
+    ApiError::invalid("cursor", "invalid", message)
+}
+
+fn analyze_in_background(cv: &CV) {
+    let cv_id = cv.id.clone();
+    ic_cdk::spawn(async move {
+        let _ = CVAnalyzer::analyze_cv(cv_id).await;
+    });
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_create_user(payload: CreateUserPayload) -> Result<UserProfile, ApiError> {
+    payload.validate()?;
+    let caller = ic_cdk::caller();
+    let mut user = UserProfile::new(
+        AuthService::resolve_user_id(&caller),
+        payload.name,
+        payload.email,
+        payload.phone_number,
+        payload.city,
+        payload.country,
+    );
+    canonicalize_phone(&mut user)?;
+
+    UserStorage::save_with_validation(user.clone())?;
+    AuthService::associate_user_principal(caller, user.id.clone());
+    ProfileCompletion::refresh(&user.id);
+    // Read it back for the computed completion.
+    Ok(UserStorage::get(&user.id).unwrap_or(user))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_user() -> Result<UserProfile, ApiError> {
+    UserStorage::get(&caller_user_id()).ok_or_else(|| ApiError::NotFound("User not found".to_string()))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_user_by_id(user_id: String) -> Result<UserProfile, ApiError> {
+    access::require_owner_or(&user_id, &[Role::BusinessMember, Role::Admin])?;
+    UserStorage::get(&user_id).ok_or_else(|| ApiError::NotFound("User not found".to_string()))
+}
+
+/// Ids of users whose best university grade, normalized to 0–1 across
+/// grading scales, is at least `min_normalized`.
+#[ic_cdk::query(guard = "can_browse_candidates")]
+#[candid_method(query)]
+pub fn v2_find_candidates_by_grade(min_normalized: f32) -> Result<Vec<String>, ApiError> {
+    if !(0.0..=1.0).contains(&min_normalized) {
+        return Err(ApiError::invalid("min_normalized", "out_of_range", "Must be between 0 and 1"));
+    }
+    Ok(EducationStorage::user_ids_with_min_grade(min_normalized))
+}
+
+/// Updates the fields the payload carries. A caller without a profile gets
+/// one made from the payload, which then has to carry every field.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_user(payload: UpdateUserPayload) -> Result<UserProfile, ApiError> {
+    payload.validate()?;
+    let principal = ic_cdk::caller();
+    let user_id = AuthService::resolve_user_id(&principal);
+
+    let Some(mut user) = UserStorage::get(&user_id) else {
+        return v2_create_user(CreateUserPayload {
+            name: payload.name.unwrap_or_default(),
+            email: payload.email.unwrap_or_default(),
+            phone_number: payload.phone_number.unwrap_or_default(),
+            city: payload.city.unwrap_or_default(),
+            country: payload.country.unwrap_or_default(),
+        });
+    };
+
+    if let Some(name) = payload.name {
+        user.name = name;
+    }
+    if let Some(email) = payload.email {
+        if email != user.email {
+            user.email_verified_at = None;
+        }
+        user.email = email;
+    }
+    if let Some(phone) = payload.phone_number {
+        user.phone_number = phone;
+    }
+    if let Some(city) = payload.city {
+        user.city = city;
+    }
+    if let Some(country) = payload.country {
+        user.country = country;
+    }
+    // The phone number is checked against the country, which either field
+    // of the payload may have changed.
+    user.validate()?;
+    canonicalize_phone(&mut user)?;
+
+    UserStorage::update_with_validation(user.clone())?;
+    ProfileCompletion::refresh(&user_id);
+    Ok(UserStorage::get(&user_id).unwrap_or(user))
+}
+
+/// Countries whose phone numbers are checked in full, with their calling
+/// codes. Numbers from elsewhere need an explicit `+` country code.
+#[ic_cdk::query(guard = "anyone")]
+#[candid_method(query)]
+pub fn v2_list_phone_countries() -> Result<Vec<PhoneCountry>, ApiError> {
+    Ok(phone::phone_countries())
+}
+
+/// Mails a verification code to the caller's email address.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_request_email_verification() -> Result<EmailVerificationStatus, ApiError> {
+    require_signed_in()?;
+    let user_id = require_user()?;
+    EmailVerificationService::request(&user_id).await
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_confirm_email(code: String) -> Result<UserProfile, ApiError> {
+    require_signed_in()?;
+    EmailVerificationService::confirm(&caller_user_id(), &code)
+}
+
+/// What the caller still has to fill in, with the weight each item carries
+/// in `profile_completion`.
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_profile_checklist() -> Result<ProfileChecklist, ApiError> {
+    ProfileCompletion::checklist(&caller_user_id()).map_err(ApiError::NotFound)
+}
+
+/// Sets the high school entry and replaces the university entries, for
+/// whichever of the two the payload carries.
+fn apply_education_payload(record: &mut EducationRecord, payload: EducationPayload) -> Result<(), ApiError> {
+    payload.validate()?;
+
+    if let Some(hs_payload) = payload.high_school {
+        let high_school = HighSchoolEducation::new(
+            hs_payload.school_name,
+            hs_payload.track,
+            hs_payload.city,
+            hs_payload.country,
+            hs_payload.start_year,
+            hs_payload.end_year,
+            hs_payload.status,
+        );
+        record.set_high_school(high_school).map_err(education_error)?;
+    }
+
+    if let Some(uni_payloads) = payload.university {
+        let universities = uni_payloads
+            .into_iter()
+            .map(|uni_payload| {
+                UniversityEducation::new(
+                    uni_payload.university_name,
+                    uni_payload.level,
+                    uni_payload.major,
+                    uni_payload.city,
+                    uni_payload.country,
+                    uni_payload.start_year,
+                    uni_payload.end_year,
+                    uni_payload.gpa,
+                    uni_payload.gpa_scale,
+                    uni_payload.status,
+                )
+            })
+            .collect();
+        record.set_universities(universities).map_err(education_error)?;
+    }
+    Ok(())
+}
+
+/// Errors left over from `EducationRecord` once the input has passed
+/// validation: entry limits and entry ids.
+fn education_error(message: String) -> ApiError {
+    ApiError::invalid("entries", "invalid", message)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_add_education(payload: EducationPayload) -> Result<EducationRecord, ApiError> {
+    let user_id = caller_user_id();
+    let mut record = EducationRecord::new(format!("EDU_{}", user_id), user_id);
+    apply_education_payload(&mut record, payload)?;
+    EducationStorage::save_with_validation(record.clone())?;
+    ProfileCompletion::refresh(&record.user_id);
+    Ok(record)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_education() -> Result<EducationRecord, ApiError> {
+    EducationStorage::get_by_user(&caller_user_id())
+        .ok_or_else(|| ApiError::NotFound("Education record not found".to_string()))
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_education(payload: EducationPayload) -> Result<EducationRecord, ApiError> {
+    let mut record = v2_get_education()?;
+    apply_education_payload(&mut record, payload)?;
+    EducationStorage::update_with_validation(record.clone())?;
+    ProfileCompletion::refresh(&record.user_id);
+    Ok(record)
+}
+
+/// The caller's university grades with each normalized to 0–1.
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_normalized_grades() -> Result<Vec<NormalizedGrade>, ApiError> {
+    Ok(EducationStorage::get_by_user(&caller_user_id())
+        .map(|record| record.normalized_grades())
+        .unwrap_or_default())
+}
+
+/// Applies `change` to the caller's education record, creating the record
+/// on first use, and stores the result.
+fn modify_education(
+    change: impl FnOnce(&mut EducationRecord) -> Result<(), String>,
+) -> Result<EducationRecord, ApiError> {
+    let user_id = caller_user_id();
+    let mut record = EducationStorage::get_by_user(&user_id)
+        .unwrap_or_else(|| EducationRecord::new(format!("EDU_{}", user_id), user_id));
+    change(&mut record).map_err(education_error)?;
+    EducationStorage::save_with_validation(record.clone())?;
+    ProfileCompletion::refresh(&record.user_id);
+    Ok(record)
+}
+
+fn require_education_entry(entry_id: u32) -> Result<(), ApiError> {
+    let exists = EducationStorage::get_by_user(&caller_user_id())
+        .is_some_and(|record| record.entries.iter().any(|entry| entry.id == entry_id));
+    if !exists {
+        return Err(ApiError::NotFound(format!("Education entry {} not found", entry_id)));
+    }
+    Ok(())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_add_education_entry(details: EducationDetails) -> Result<EducationRecord, ApiError> {
+    details.validate()?;
+    modify_education(|record| record.add_entry(details).map(|_| ()))
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_education_entry(entry_id: u32, details: EducationDetails) -> Result<EducationRecord, ApiError> {
+    details.validate()?;
+    require_education_entry(entry_id)?;
+    modify_education(|record| record.update_entry(entry_id, details))
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_remove_education_entry(entry_id: u32) -> Result<EducationRecord, ApiError> {
+    require_education_entry(entry_id)?;
+    modify_education(|record| record.remove_entry(entry_id))
+}
+
+/// Reorders the caller's education entries; `entry_ids` must list each once.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_reorder_education_entries(entry_ids: Vec<u32>) -> Result<EducationRecord, ApiError> {
+    modify_education(|record| record.reorder_entries(&entry_ids))
+}
+
+fn caller_work_history() -> WorkHistory {
+    WorkHistory::new(
+        WorkExperienceStorage::get_by_user(&caller_user_id()),
+        YearMonth::from_timestamp(api::time()),
+    )
+}
+
+fn validate_experience(experience: &mut WorkExperience, now: u64) -> Result<(), ApiError> {
+    experience
+        .validate(YearMonth::from_timestamp(now))
+        .map_err(|e| ApiError::invalid("", "invalid", e))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_work_history() -> Result<WorkHistory, ApiError> {
+    Ok(caller_work_history())
+}
+
+/// Adds a role and returns the caller's updated history, in which any
+/// overlap with existing roles is flagged.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_add_work_experience(payload: WorkExperiencePayload) -> Result<WorkHistory, ApiError> {
+    let user_id = require_user()?;
+    let now = api::time();
+    let mut experience = WorkExperience {
+        id: 0,
+        user_id,
+        company: payload.company,
+        title: payload.title,
+        employment_type: payload.employment_type,
+        location: payload.location,
+        start: payload.start,
+        end: payload.end,
+        is_current: payload.is_current,
+        description: payload.description,
+        created_at: now,
+        updated_at: now,
+    };
+    validate_experience(&mut experience, now)?;
+    let experience = WorkExperienceStorage::add(experience)?;
+    ProfileCompletion::refresh(&experience.user_id);
+    Ok(caller_work_history())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_work_experience(id: u32, payload: WorkExperiencePayload) -> Result<WorkHistory, ApiError> {
+    let mut experience = WorkExperienceStorage::get(&caller_user_id(), id)?;
+    let now = api::time();
+    experience.company = payload.company;
+    experience.title = payload.title;
+    experience.employment_type = payload.employment_type;
+    experience.location = payload.location;
+    experience.start = payload.start;
+    experience.end = payload.end;
+    experience.is_current = payload.is_current;
+    experience.description = payload.description;
+    experience.updated_at = now;
+    validate_experience(&mut experience, now)?;
+    WorkExperienceStorage::update(experience)?;
+    Ok(caller_work_history())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_delete_work_experience(id: u32) -> Result<WorkHistory, ApiError> {
+    let user_id = caller_user_id();
+    WorkExperienceStorage::delete(&user_id, id)?;
+    ProfileCompletion::refresh(&user_id);
+    Ok(caller_work_history())
+}
+
+fn bank_account(payload: BankInfoPayload) -> BankAccount {
+    BankAccount {
+        account_holder_name: payload.account_holder_name,
+        bank_name: payload.bank_name,
+        swift_code: bank::normalize(&payload.swift_code),
+        account_number: bank::normalize(&payload.account_number),
+        bank_country: payload.bank_country,
+        bank_branch: payload.bank_branch,
+    }
+}
+
+/// The method a validated payload describes.
+fn payout_method(input: PayoutMethodInput) -> Result<PayoutMethod, ApiError> {
+    let invalid = |field: &'static str| move |(code, message): (&str, String)| ApiError::invalid(field, code, message);
+    Ok(match input {
+        PayoutMethodInput::BankAccount(payload) => PayoutMethod::BankAccount(bank_account(payload)),
+        PayoutMethodInput::Icrc1Account(text) => {
+            let (owner, subaccount) = ledger::parse_icrc1_account(&text).map_err(invalid("method.account"))?;
+            PayoutMethod::Icrc1Account { owner, subaccount }
+        }
+        PayoutMethodInput::IcpAccountIdentifier(text) => PayoutMethod::IcpAccountIdentifier(
+            ledger::parse_account_identifier(&text).map_err(invalid("method.account_identifier"))?,
+        ),
+    })
+}
+
+/// The bank account the single-account bank endpoints work on: the primary
+/// payout method if it is a bank account, else the oldest bank account.
+fn bank_payout_method(user_id: &str) -> Result<PayoutMethodRecord, ApiError> {
+    let methods = PayoutStorage::get_by_user(user_id)?;
+    let is_bank = |m: &&PayoutMethodRecord| matches!(m.method, PayoutMethod::BankAccount(_));
+    methods
+        .iter()
+        .filter(is_bank)
+        .find(|m| m.is_primary)
+        .or_else(|| methods.iter().find(is_bank))
+        .cloned()
+        .ok_or_else(|| ApiError::NotFound("Bank information not found".to_string()))
+}
+
+/// Sets up encryption if this is the first write since install or upgrade.
+/// Awaited after the caller's checks, before anything is stored.
+async fn encryption_ready() -> Result<(), ApiError> {
+    Encryption::ensure_ready().await.map_err(ApiError::Internal)
+}
+
+fn bank_information(record: &PayoutMethodRecord) -> Result<BankInformation, ApiError> {
+    record
+        .to_bank_information()
+        .ok_or_else(|| ApiError::Internal(format!("Payout method {} is not a bank account", record.id)))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_list_payout_methods() -> Result<Vec<PayoutMethodRecord>, ApiError> {
+    Ok(PayoutStorage::get_by_user(&caller_user_id())?)
+}
+
+/// Saves another payout method. The first one a user adds is primary.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_add_payout_method(payload: PayoutMethodPayload) -> Result<Vec<PayoutMethodRecord>, ApiError> {
+    payload.validate()?;
+    let user_id = require_user()?;
+    let now = api::time();
+    let record = PayoutMethodRecord {
+        id: 0,
+        user_id: user_id.clone(),
+        label: payload.label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty()),
+        method: payout_method(payload.method)?,
+        is_primary: false,
+        created_at: now,
+        updated_at: now,
+    };
+
+    encryption_ready().await?;
+    PayoutStorage::add(record)?;
+    ProfileCompletion::refresh(&user_id);
+    Ok(PayoutStorage::get_by_user(&user_id)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_set_primary_payout_method(id: u32) -> Result<Vec<PayoutMethodRecord>, ApiError> {
+    let user_id = require_user()?;
+    PayoutStorage::set_primary(&user_id, id)?;
+    Ok(PayoutStorage::get_by_user(&user_id)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_remove_payout_method(id: u32) -> Result<Vec<PayoutMethodRecord>, ApiError> {
+    let user_id = require_user()?;
+    PayoutStorage::remove(&user_id, id)?;
+    ProfileCompletion::refresh(&user_id);
+    Ok(PayoutStorage::get_by_user(&user_id)?)
+}
+
+/// Adds a bank account as a payout method.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_add_bank_info(payload: BankInfoPayload) -> Result<BankInformation, ApiError> {
+    payload.validate()?;
+    let user_id = require_user()?;
+    let now = api::time();
+    let record = PayoutMethodRecord {
+        id: 0,
+        user_id,
+        label: None,
+        method: PayoutMethod::BankAccount(bank_account(payload)),
+        is_primary: false,
+        created_at: now,
+        updated_at: now,
+    };
+
+    encryption_ready().await?;
+    let record = PayoutStorage::add(record)?;
+    ProfileCompletion::refresh(&record.user_id);
+    bank_information(&record)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_bank_info() -> Result<BankInformation, ApiError> {
+    bank_information(&bank_payout_method(&caller_user_id())?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_update_bank_info(payload: BankInfoPayload) -> Result<BankInformation, ApiError> {
+    payload.validate()?;
+    let user_id = require_user()?;
+    encryption_ready().await?;
+
+    // Read after the await, so a concurrent change is not overwritten.
+    let mut record = bank_payout_method(&user_id)?;
+    record.method = PayoutMethod::BankAccount(bank_account(payload));
+    record.updated_at = api::time();
+    PayoutStorage::update(record.clone())?;
+    bank_information(&record)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_bank_info_by_user_id(user_id: String) -> Result<BankInformation, ApiError> {
+    access::require_owner_or_permission(&user_id, AdminPermission::ViewPayoutDetails)?;
+    bank_information(&bank_payout_method(&user_id)?)
+}
+
+/// Adds a new master key version and re-encrypts stored data under it in
+/// the background. Progress is in `v2_encryption_status`.
+#[ic_cdk::update(guard = "is_controller")]
+#[candid_method(update)]
+pub async fn v2_start_key_rotation() -> Result<KeyRotationStatus, ApiError> {
+    KeyRotationService::start().await
+}
+
+#[ic_cdk::query(guard = "is_controller")]
+#[candid_method(query)]
+pub fn v2_encryption_status() -> Result<EncryptionStatus, ApiError> {
+    Ok(KeyRotationService::status())
+}
+
+/// The bank a BIC belongs to, for filling in `bank_name`.
+#[ic_cdk::query(guard = "anyone")]
+#[candid_method(query)]
+pub fn v2_lookup_bank(swift_code: String) -> Result<BankSuggestion, ApiError> {
+    let bic = bank::normalize(&swift_code);
+    bank::check_bic(&bic, "").map_err(|(code, message)| ApiError::invalid("swift_code", code, message))?;
+    bank::lookup_bic(&bic)
+        .map(BankSuggestion::from)
+        .ok_or_else(|| ApiError::NotFound("No bank with this BIC in the directory".to_string()))
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_upload_cv(payload: CreateCVPayload) -> Result<CV, ApiError> {
+    payload.validate()?;
+    let user_id = require_user()?;
+
+    let cv = CV::new(CVStorage::next_id(), user_id, payload.title, payload.content);
+    let version = CVVersion::of(&cv, None);
+    CVVersionStorage::check(&version)?;
+    CVStorage::store_cv(cv.clone())?;
+    CVVersionStorage::record(version)?;
+    ProfileCompletion::refresh(&cv.user_id);
+    analyze_in_background(&cv);
+    Ok(cv)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_cv(id: String) -> Result<CV, ApiError> {
+    CVHistoryService::owned_cv(&id, &caller_user_id())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_my_cvs() -> Result<Vec<CV>, ApiError> {
+    let user_id = require_user()?;
+    Ok(CVStorage::get_user_cvs(&user_id)?)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_my_cvs_page(request: PageRequest) -> Result<Page<CV>, ApiError> {
+    let user_id = require_user()?;
+    Ok(CVStorage::get_user_cvs_page(&user_id, &request)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_cv(payload: UpdateCVPayload) -> Result<CV, ApiError> {
+    payload.validate()?;
+    let user_id = require_user()?;
+
+    let mut cv = CVHistoryService::owned_cv(&payload.id, &user_id)?;
+    cv.title = payload.title;
+    cv.content = payload.content;
+
+    let cv = CVHistoryService::commit(cv, None)?;
+    analyze_in_background(&cv);
+    Ok(cv)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_begin_upload(payload: BeginUploadPayload) -> Result<UploadSession, ApiError> {
+    let user_id = require_user()?;
+    Ok(BlobStorage::begin_upload(
+        &user_id,
+        payload.file_name,
+        payload.content_type,
+        payload.size,
+        &payload.sha256,
+    )?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_upload_chunk(payload: UploadChunkPayload) -> Result<UploadProgress, ApiError> {
+    Ok(BlobStorage::upload_chunk(&caller_user_id(), payload.upload_id, payload.index, payload.data)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_commit_upload(upload_id: u64) -> Result<BlobInfo, ApiError> {
+    Ok(BlobStorage::commit_upload(&caller_user_id(), upload_id)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_abort_upload(upload_id: u64) -> Result<(), ApiError> {
+    Ok(BlobStorage::abort_upload(&caller_user_id(), upload_id)?)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_list_my_files() -> Result<Vec<BlobInfo>, ApiError> {
+    Ok(BlobStorage::get_user_blobs(&caller_user_id()))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_file_usage() -> Result<BlobUsage, ApiError> {
+    Ok(BlobStorage::usage(&caller_user_id()))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_file_chunk(blob_id: u64, index: u32) -> Result<Vec<u8>, ApiError> {
+    Ok(BlobStorage::get_chunk(&caller_user_id(), blob_id, index)?)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_file_text(blob_id: u64) -> Result<ExtractedText, ApiError> {
+    Ok(BlobStorage::get_user_text(&caller_user_id(), blob_id)?)
+}
+
+/// Files attached to one of the caller's CVs cannot be deleted; detach them
+/// first.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_delete_file(blob_id: u64) -> Result<(), ApiError> {
+    let user_id = caller_user_id();
+
+    let attached = CVStorage::get_user_cvs(&user_id)
+        .unwrap_or_default()
+        .iter()
+        .any(|cv| cv.blob_id == Some(blob_id));
+    if attached {
+        return Err(ApiError::invalid("blob_id", "attached", "File is attached to a CV"));
+    }
+
+    Ok(BlobStorage::delete_blob(&user_id, blob_id)?)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_attach_file_to_cv(cv_id: String, blob_id: Option<u64>) -> Result<CV, ApiError> {
+    let cv = CVHistoryService::attach_blob(&cv_id, &caller_user_id(), blob_id)?;
+    analyze_in_background(&cv);
+    Ok(cv)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_list_cv_versions(cv_id: String, request: PageRequest) -> Result<Page<CVVersionInfo>, ApiError> {
+    CVHistoryService::list_versions(&cv_id, &caller_user_id(), &request)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_cv_version(cv_id: String, version: u32) -> Result<CVVersion, ApiError> {
+    CVHistoryService::get_version(&cv_id, &caller_user_id(), version)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_diff_cv_versions(cv_id: String, from_version: u32, to_version: u32) -> Result<CVDiff, ApiError> {
+    CVHistoryService::diff(&cv_id, &caller_user_id(), from_version, to_version)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_revert_cv(cv_id: String, version: u32) -> Result<CV, ApiError> {
+    let cv = CVHistoryService::revert(&cv_id, &caller_user_id(), version)?;
+    analyze_in_background(&cv);
+    Ok(cv)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_cv_document(cv_id: String) -> Result<CvDocument, ApiError> {
+    CvDocumentService::get(&cv_id, &caller_user_id())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_set_cv_summary(cv_id: String, summary: String) -> Result<CvDocument, ApiError> {
+    CvDocumentService::set_summary(&cv_id, &caller_user_id(), summary)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_add_cv_entry(cv_id: String, entry: CvEntry) -> Result<CvDocument, ApiError> {
+    CvDocumentService::add_entry(&cv_id, &caller_user_id(), entry)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_update_cv_entry(cv_id: String, entry_id: u32, entry: CvEntry) -> Result<CvDocument, ApiError> {
+    CvDocumentService::update_entry(&cv_id, &caller_user_id(), entry_id, entry)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_remove_cv_entry(cv_id: String, entry_id: u32) -> Result<CvDocument, ApiError> {
+    CvDocumentService::remove_entry(&cv_id, &caller_user_id(), entry_id)
+}
+
+/// Replaces the CV's document with one parsed from `text`, or from the
+/// CV's file or content when no text is given.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_import_cv_document(cv_id: String, text: Option<String>) -> Result<CvDocument, ApiError> {
+    CvDocumentService::import(&cv_id, &caller_user_id(), text)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_start_cv_chat(cv_id: String) -> Result<ChatMessage, ApiError> {
+    ChatService::start_chat(&caller_user_id(), &cv_id).await
+}
+
+/// Stores the caller's message and returns the assistant's reply.
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_send_chat_message(session_id: String, content: String) -> Result<ChatMessage, ApiError> {
+    ChatService::send_message(&session_id, &caller_user_id(), content).await
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_chat_history(session_id: String) -> Result<Vec<ChatMessage>, ApiError> {
+    ChatService::get_chat_history(&session_id, &caller_user_id())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_chat_history_page(session_id: String, request: PageRequest) -> Result<Page<ChatMessage>, ApiError> {
+    ChatService::get_chat_history_page(&session_id, &caller_user_id(), &request)
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_get_my_chat_sessions_page(request: PageRequest) -> Result<Page<ChatSession>, ApiError> {
+    ChatSessionStorage::get_user_sessions_page(&caller_user_id(), &request).map_err(invalid_cursor)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_login() -> Result<Session, ApiError> {
+    let principal = require_signed_in()?;
+    let session = AuthService::create_session(principal);
+
+    if AuthService::get_user_id(&principal).is_none() {
+        AuthService::associate_user_principal(principal, principal.to_string());
+    }
+
+    Ok(session)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn v2_create_link_code() -> Result<LinkCode, ApiError> {
+    let principal = require_signed_in()?;
+    let user_id = require_user()?;
+    IdentityStore::create_link_code(&user_id, principal).await.map_err(ApiError::Upstream)
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_link_principal(code: String) -> Result<Vec<LinkedPrincipal>, ApiError> {
+    let principal = require_signed_in()?;
+
+    if UserStorage::exists(&AuthService::resolve_user_id(&principal)) {
+        return Err(ApiError::invalid(
+            "",
+            "has_profile",
+            "This principal already has a profile; ask an admin to merge the two",
+        ));
+    }
+
+    let user_id = IdentityStore::redeem_link_code(&code).map_err(|e| ApiError::invalid("code", "invalid", e))?;
+    AuthService::associate_user_principal(principal, user_id.clone());
+
+    Ok(IdentityStore::principals_of(&user_id))
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn v2_list_my_principals() -> Result<Vec<LinkedPrincipal>, ApiError> {
+    Ok(IdentityStore::principals_of(&caller_user_id()))
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn v2_unlink_principal(principal: Principal) -> Result<Vec<LinkedPrincipal>, ApiError> {
+    let user_id = caller_user_id();
+
+    if IdentityStore::get_user_id(&principal).as_deref() != Some(user_id.as_str()) {
+        return Err(ApiError::NotFound("Principal is not linked to your account".to_string()));
+    }
+    // The owning principal resolves to the user id even without a link.
+    if principal.to_string() == user_id {
+        return Err(ApiError::invalid(
+            "principal",
+            "owner",
+            "Cannot unlink the principal that owns the account",
+        ));
+    }
+
+    IdentityStore::unlink(&principal);
+    Ok(IdentityStore::principals_of(&user_id))
+}
+
+#[ic_cdk::update(guard = "can_manage_ai_config")]
+#[candid_method(update)]
+pub fn v2_set_openai_key(key: String) -> Result<(), ApiError> {
+    OPENAI_API_KEY.with(|k| *k.borrow_mut() = key);
+    Ok(())
+}
+
+/// Points outgoing mail at a transactional-mail endpoint, or at a local
+/// mock server during development.
+#[ic_cdk::update(guard = "can_manage_ai_config")]
+#[candid_method(update)]
+pub fn v2_set_mail_config(config: MailConfig) -> Result<(), ApiError> {
+    MailService::configure(config)
+}
+
+#[ic_cdk::update(guard = "can_wipe_data")]
+#[candid_method(update)]
+pub fn v2_clear_all_storage() -> Result<(), ApiError> {
+    UserStorage::clear();
+    BankStorage::clear();
+    PayoutStorage::clear();
+    CVStorage::clear();
+    Ok(())
+}
+
+#[ic_cdk::update(guard = "can_wipe_data")]
+#[candid_method(update)]
+pub fn v2_clear_cv_storage() -> Result<(), ApiError> {
+    CVStorage::clear();
+    Ok(())
+}
+
+#[ic_cdk::update(guard = "is_admin")]
+#[candid_method(update)]
+pub fn v2_rebuild_indexes() -> Result<IndexRebuildReport, ApiError> {
+    Ok(crate::storage::rebuild_user_indexes())
+}
+
+#[ic_cdk::query(guard = "can_moderate_content")]
+#[candid_method(query)]
+pub fn v2_find_duplicate_profiles() -> Result<Vec<DuplicateProfile>, ApiError> {
+    Ok(IdentityStore::find_duplicate_profiles())
+}
+
+#[ic_cdk::update(guard = "can_moderate_content")]
+#[candid_method(update)]
+pub async fn v2_merge_duplicate_profiles(canonical_id: String, duplicate_id: String) -> Result<UserProfile, ApiError> {
+    encryption_ready().await?;
+    IdentityStore::merge_profiles(&canonical_id, &duplicate_id).map_err(|e| ApiError::invalid("", "invalid", e))
+}
+
+#[ic_cdk::query(guard = "anyone")]
+#[candid_method(query)]
+pub fn v2_get_my_roles() -> Result<Vec<Role>, ApiError> {
+    Ok(access::roles_of(&ic_cdk::caller()))
+}
+
+/// Lets a user read candidates' profiles on behalf of a hiring company.
+#[ic_cdk::update(guard = "can_moderate_content")]
+#[candid_method(update)]
+pub fn v2_grant_business_member(user_id: String) -> Result<BusinessMember, ApiError> {
+    if !UserStorage::exists(&user_id) {
+        return Err(ApiError::NotFound("User not found".to_string()));
+    }
+    Ok(BusinessMembers::grant(&user_id, ic_cdk::caller()))
+}
+
+#[ic_cdk::update(guard = "can_moderate_content")]
+#[candid_method(update)]
+pub fn v2_revoke_business_member(user_id: String) -> Result<(), ApiError> {
+    BusinessMembers::revoke(&user_id)
+}
+
+#[ic_cdk::query(guard = "can_moderate_content")]
+#[candid_method(query)]
+pub fn v2_list_business_members() -> Result<Vec<BusinessMember>, ApiError> {
+    Ok(BusinessMembers::list())
+}
+
+/// Puts `principal` on the admin list with `permissions`, replacing those of
+/// an admin already on it. Controllers need no entry.
+#[ic_cdk::update(guard = "is_controller")]
+#[candid_method(update)]
+pub fn v2_add_admin(principal: Principal, permissions: Vec<AdminPermission>) -> Result<Admin, ApiError> {
+    Admins::add(principal, permissions, ic_cdk::caller())
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+#[candid_method(update)]
+pub fn v2_remove_admin(principal: Principal) -> Result<(), ApiError> {
+    Admins::remove(&principal)
+}
+
+#[ic_cdk::query(guard = "is_admin")]
+#[candid_method(query)]
+pub fn v2_list_admins() -> Result<Vec<Admin>, ApiError> {
+    Ok(Admins::list())
+}
@@ -0,0 +1,310 @@
+//! Who may call what.
+//!
+//! Every endpoint names a guard in its `#[update]`/`#[query]` attribute:
+//! `anyone` for the few public ones, otherwise the role it needs. Endpoints
+//! that take another user's id also check ownership with
+//! `require_owner_or`. Roles are only held as listed in `roles_of`, so
+//! anything not granted there is denied.
+//!
+//! Admins are controllers of the canister plus the principals on the stable
+//! admin list. Listed admins only get the `AdminPermission`s they were given;
+//! controllers hold every permission and are the only ones who manage the
+//! list.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::time;
+use ic_stable_structures::storable::Blob;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use super::AuthService;
+use crate::models::types::{decode_record, encode_record, record_schema_version, string_to_storage_key};
+use crate::models::StorageKey;
+use crate::storage::registry::{self, Memory};
+use crate::types::errors::ApiError;
+
+type PrincipalKey = Blob<29>;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// Any signed-in principal. Manages their own profile, CVs and payouts.
+    Seeker,
+    /// Works for a hiring company; may read candidates' profiles.
+    BusinessMember,
+    /// A controller or a principal on the admin list. Runs maintenance;
+    /// anything more needs an `AdminPermission`.
+    Admin,
+    /// A controller of the canister. Controllers are admins too.
+    Controller,
+}
+
+/// What a listed admin may do beyond the maintenance every admin can.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdminPermission {
+    /// Set the OpenAI key and the mail provider.
+    ManageAiConfig,
+    /// Clear whole stores.
+    WipeData,
+    /// Find and merge duplicate profiles, grant business membership.
+    ModerateContent,
+    /// Read other users' decrypted payout details.
+    ViewPayoutDetails,
+}
+
+/// A principal a controller has made an admin. Keyed by principal rather
+/// than user id, so linking another principal to the account does not make
+/// it an admin too.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Admin {
+    pub principal: Principal,
+    pub permissions: Vec<AdminPermission>,
+    pub added_by: Principal,
+    pub added_at: u64,
+}
+
+impl Admin {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for Admin {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported admin schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for Admin {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// A user an admin has made a business member. Keyed by user id, so every
+/// principal linked to the user holds the role.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BusinessMember {
+    pub user_id: String,
+    pub granted_by: Principal,
+    pub granted_at: u64,
+}
+
+impl BusinessMember {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for BusinessMember {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported business member schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for BusinessMember {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+thread_local! {
+    static BUSINESS_MEMBERS: RefCell<StableBTreeMap<StorageKey, BusinessMember, Memory>> = RefCell::new(
+        registry::init_map(registry::BUSINESS_MEMBERS)
+    );
+
+    static ADMINS: RefCell<StableBTreeMap<PrincipalKey, Admin, Memory>> = RefCell::new(
+        registry::init_map(registry::ADMINS)
+    );
+}
+
+fn principal_key(principal: &Principal) -> PrincipalKey {
+    PrincipalKey::try_from(principal.as_slice()).expect("Principal longer than 29 bytes")
+}
+
+/// Every role `principal` holds. The anonymous principal holds none.
+pub fn roles_of(principal: &Principal) -> Vec<Role> {
+    if *principal == Principal::anonymous() {
+        return Vec::new();
+    }
+
+    let mut roles = vec![Role::Seeker];
+    let user_key = string_to_storage_key(&AuthService::resolve_user_id(principal));
+    if BUSINESS_MEMBERS.with(|members| members.borrow().contains_key(&user_key)) {
+        roles.push(Role::BusinessMember);
+    }
+    let controller = ic_cdk::api::is_controller(principal);
+    if controller || Admins::get(principal).is_some() {
+        roles.push(Role::Admin);
+    }
+    if controller {
+        roles.push(Role::Controller);
+    }
+    roles
+}
+
+/// Whether `principal` may do what `permission` covers: controllers always,
+/// listed admins if they were given it.
+pub fn has_permission(principal: &Principal, permission: AdminPermission) -> bool {
+    if *principal == Principal::anonymous() {
+        return false;
+    }
+    ic_cdk::api::is_controller(principal)
+        || Admins::get(principal).is_some_and(|admin| admin.permissions.contains(&permission))
+}
+
+pub fn caller_has(role: Role) -> bool {
+    roles_of(&ic_cdk::caller()).contains(&role)
+}
+
+fn require(role: Role) -> Result<(), String> {
+    if caller_has(role) {
+        Ok(())
+    } else {
+        Err(format!("This requires the {:?} role", role))
+    }
+}
+
+/// Guard of public endpoints, so that they state it.
+pub fn anyone() -> Result<(), String> {
+    Ok(())
+}
+
+/// Guard of endpoints acting on the caller's own records.
+pub fn is_signed_in() -> Result<(), String> {
+    require(Role::Seeker)
+}
+
+pub fn is_admin() -> Result<(), String> {
+    require(Role::Admin)
+}
+
+pub fn is_controller() -> Result<(), String> {
+    require(Role::Controller)
+}
+
+/// Guard of candidate search, for hiring companies and admins.
+pub fn can_browse_candidates() -> Result<(), String> {
+    let roles = roles_of(&ic_cdk::caller());
+    if roles.contains(&Role::BusinessMember) || roles.contains(&Role::Admin) {
+        Ok(())
+    } else {
+        Err("This requires the BusinessMember or Admin role".to_string())
+    }
+}
+
+fn require_permission(permission: AdminPermission) -> Result<(), String> {
+    if has_permission(&ic_cdk::caller(), permission) {
+        Ok(())
+    } else {
+        Err(format!("This requires the {:?} admin permission", permission))
+    }
+}
+
+pub fn can_manage_ai_config() -> Result<(), String> {
+    require_permission(AdminPermission::ManageAiConfig)
+}
+
+pub fn can_wipe_data() -> Result<(), String> {
+    require_permission(AdminPermission::WipeData)
+}
+
+pub fn can_moderate_content() -> Result<(), String> {
+    require_permission(AdminPermission::ModerateContent)
+}
+
+fn is_owner(caller: &Principal, user_id: &str) -> bool {
+    *caller != Principal::anonymous() && AuthService::resolve_user_id(caller) == user_id
+}
+
+/// Lets the caller at `user_id`'s records if they are that user or hold one
+/// of `roles`.
+pub fn require_owner_or(user_id: &str, roles: &[Role]) -> Result<(), ApiError> {
+    let caller = ic_cdk::caller();
+    let held = roles_of(&caller);
+    if is_owner(&caller, user_id) || roles.iter().any(|role| held.contains(role)) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("You cannot access this user's records".to_string()))
+    }
+}
+
+/// Lets the caller at `user_id`'s records if they are that user or an admin
+/// with `permission`.
+pub fn require_owner_or_permission(user_id: &str, permission: AdminPermission) -> Result<(), ApiError> {
+    let caller = ic_cdk::caller();
+    if is_owner(&caller, user_id) || has_permission(&caller, permission) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("You cannot access this user's records".to_string()))
+    }
+}
+
+pub struct BusinessMembers;
+
+impl BusinessMembers {
+    pub fn grant(user_id: &str, granted_by: Principal) -> BusinessMember {
+        let member = BusinessMember { user_id: user_id.to_string(), granted_by, granted_at: time() };
+        BUSINESS_MEMBERS.with(|members| {
+            members.borrow_mut().insert(string_to_storage_key(user_id), member.clone())
+        });
+        member
+    }
+
+    pub fn revoke(user_id: &str) -> Result<(), ApiError> {
+        BUSINESS_MEMBERS
+            .with(|members| members.borrow_mut().remove(&string_to_storage_key(user_id)))
+            .map(|_| ())
+            .ok_or_else(|| ApiError::NotFound("User is not a business member".to_string()))
+    }
+
+    pub fn list() -> Vec<BusinessMember> {
+        BUSINESS_MEMBERS.with(|members| members.borrow().iter().map(|(_, member)| member).collect())
+    }
+}
+
+pub struct Admins;
+
+impl Admins {
+    /// Adds `principal` to the admin list, or replaces the permissions of an
+    /// admin already on it.
+    pub fn add(principal: Principal, permissions: Vec<AdminPermission>, added_by: Principal) -> Result<Admin, ApiError> {
+        if principal == Principal::anonymous() {
+            return Err(ApiError::invalid("principal", "anonymous", "The anonymous principal cannot be an admin"));
+        }
+        let mut unique: Vec<AdminPermission> = Vec::new();
+        for permission in permissions {
+            if !unique.contains(&permission) {
+                unique.push(permission);
+            }
+        }
+
+        let admin = Admin { principal, permissions: unique, added_by, added_at: time() };
+        ADMINS.with(|admins| admins.borrow_mut().insert(principal_key(&principal), admin.clone()));
+        Ok(admin)
+    }
+
+    pub fn remove(principal: &Principal) -> Result<(), ApiError> {
+        ADMINS
+            .with(|admins| admins.borrow_mut().remove(&principal_key(principal)))
+            .map(|_| ())
+            .ok_or_else(|| ApiError::NotFound("Principal is not on the admin list".to_string()))
+    }
+
+    pub fn get(principal: &Principal) -> Option<Admin> {
+        ADMINS.with(|admins| admins.borrow().get(&principal_key(principal)))
+    }
+
+    pub fn list() -> Vec<Admin> {
+        ADMINS.with(|admins| admins.borrow().iter().map(|(_, admin)| admin).collect())
+    }
+}
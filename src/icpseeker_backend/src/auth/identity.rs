@@ -0,0 +1,348 @@
+//! Stable mapping between principals and canonical user ids.
+//!
+//! A user's canonical id is the text of the principal that created the
+//! profile. Further principals (a second Internet Identity anchor, a wallet)
+//! are linked to it with a one-time code and resolve to the same id.
+
+use candid::{CandidType, Principal};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::time;
+use ic_stable_structures::storable::Blob;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::models::types::{decode_record, encode_record, record_schema_version, string_to_storage_key};
+use crate::models::{StorageKey, UserProfile};
+use crate::services::profile_completion::ProfileCompletion;
+use crate::storage::index::SecondaryIndex;
+use crate::storage::blob::BlobStorage;
+use crate::storage::{
+    CVStorage, ChatSessionStorage, EducationStorage, PayoutStorage, UserStorage, WorkExperienceStorage,
+};
+use crate::storage::registry::{self, Memory};
+
+/// Raw principal bytes; principals are at most 29 bytes long.
+type PrincipalKey = Blob<29>;
+
+const LINK_CODE_TTL_NANOS: u64 = 10 * 60 * 1_000_000_000;
+const LINK_CODE_LENGTH: usize = 10;
+/// 32 symbols without look-alikes (no 0/O, 1/I), so `byte % 32` is unbiased.
+const LINK_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableIdentityLink {
+    pub user_id: String,
+    pub linked_at: u64,
+}
+
+impl StableIdentityLink {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for StableIdentityLink {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported identity link schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StableIdentityLink {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableLinkCode {
+    pub user_id: String,
+    pub created_by: Principal,
+    pub expires_at: u64,
+}
+
+impl StableLinkCode {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for StableLinkCode {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported link code schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StableLinkCode {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LinkedPrincipal {
+    pub principal: Principal,
+    pub linked_at: u64,
+}
+
+/// Two profiles that belong to the same person: one keyed by principal text
+/// (`create_user`), one by a generated `user_{time}` id (old `login`).
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DuplicateProfile {
+    pub canonical_id: String,
+    pub duplicate_id: String,
+    pub principals: Vec<Principal>,
+    pub reason: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LinkCode {
+    pub code: String,
+    pub expires_at: u64,
+}
+
+thread_local! {
+    static PRINCIPAL_LINKS: RefCell<StableBTreeMap<PrincipalKey, StableIdentityLink, Memory>> = RefCell::new(
+        registry::init_map(registry::PRINCIPAL_LINKS)
+    );
+
+    static USER_PRINCIPALS: RefCell<SecondaryIndex<PrincipalKey>> = RefCell::new(
+        SecondaryIndex::init(registry::USER_PRINCIPALS)
+    );
+
+    static LINK_CODES: RefCell<StableBTreeMap<StorageKey, StableLinkCode, Memory>> = RefCell::new(
+        registry::init_map(registry::LINK_CODES)
+    );
+}
+
+fn principal_key(principal: &Principal) -> PrincipalKey {
+    PrincipalKey::try_from(principal.as_slice()).expect("Principal longer than 29 bytes")
+}
+
+pub struct IdentityStore;
+
+impl IdentityStore {
+    pub fn get_user_id(principal: &Principal) -> Option<String> {
+        PRINCIPAL_LINKS.with(|links| links.borrow().get(&principal_key(principal)).map(|link| link.user_id))
+    }
+
+    /// Points `principal` at `user_id`, moving it away from any user it was
+    /// linked to before.
+    pub fn link(principal: Principal, user_id: &str) {
+        let key = principal_key(&principal);
+        let previous = PRINCIPAL_LINKS.with(|links| {
+            links.borrow_mut().insert(
+                key,
+                StableIdentityLink {
+                    user_id: user_id.to_string(),
+                    linked_at: time(),
+                },
+            )
+        });
+
+        USER_PRINCIPALS.with(|index| {
+            index.borrow_mut().on_write(previous.as_ref().map(|p| p.user_id.as_str()), user_id, key)
+        });
+    }
+
+    pub fn unlink(principal: &Principal) -> Option<String> {
+        let key = principal_key(principal);
+        let removed = PRINCIPAL_LINKS.with(|links| links.borrow_mut().remove(&key))?;
+        USER_PRINCIPALS.with(|index| index.borrow_mut().remove(&removed.user_id, &key));
+        Some(removed.user_id)
+    }
+
+    pub fn principals_of(user_id: &str) -> Vec<LinkedPrincipal> {
+        let keys = USER_PRINCIPALS.with(|index| index.borrow().keys(user_id));
+
+        PRINCIPAL_LINKS.with(|links| {
+            let links = links.borrow();
+            keys.into_iter()
+                .filter_map(|key| {
+                    let link = links.get(&key)?;
+                    Some(LinkedPrincipal {
+                        principal: Principal::from_slice(key.as_slice()),
+                        linked_at: link.linked_at,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Every `(principal, user_id)` link, in principal order.
+    pub fn links() -> Vec<(Principal, String)> {
+        PRINCIPAL_LINKS.with(|links| {
+            links
+                .borrow()
+                .iter()
+                .map(|(key, link)| (Principal::from_slice(key.as_slice()), link.user_id))
+                .collect()
+        })
+    }
+
+    /// Issues a single-use code that links another principal to `user_id`.
+    /// Any earlier code of the same user, and every expired code, is dropped.
+    pub async fn create_link_code(user_id: &str, created_by: Principal) -> Result<LinkCode, String> {
+        let random_bytes = match raw_rand().await {
+            Ok((bytes,)) => bytes,
+            Err(e) => return Err(format!("Failed to generate link code: {:?}", e)),
+        };
+        if random_bytes.len() < LINK_CODE_LENGTH {
+            return Err("Failed to generate link code: not enough randomness".to_string());
+        }
+
+        let code: String = random_bytes[..LINK_CODE_LENGTH]
+            .iter()
+            .map(|b| LINK_CODE_ALPHABET[(*b % 32) as usize] as char)
+            .collect();
+        let now = time();
+        let expires_at = now + LINK_CODE_TTL_NANOS;
+
+        LINK_CODES.with(|codes| {
+            let mut codes = codes.borrow_mut();
+            let stale: Vec<StorageKey> = codes
+                .iter()
+                .filter(|(_, c)| c.user_id == user_id || c.expires_at <= now)
+                .map(|(key, _)| key)
+                .collect();
+            for key in stale {
+                codes.remove(&key);
+            }
+            codes.insert(
+                string_to_storage_key(&code),
+                StableLinkCode {
+                    user_id: user_id.to_string(),
+                    created_by,
+                    expires_at,
+                },
+            );
+        });
+
+        Ok(LinkCode { code, expires_at })
+    }
+
+    /// Consumes `code` and returns the user id it was issued for.
+    pub fn redeem_link_code(code: &str) -> Result<String, String> {
+        let key = string_to_storage_key(code.trim().to_uppercase().as_str());
+        let link_code = LINK_CODES
+            .with(|codes| codes.borrow_mut().remove(&key))
+            .ok_or_else(|| "Invalid link code".to_string())?;
+
+        if link_code.expires_at <= time() {
+            return Err("Link code has expired".to_string());
+        }
+        Ok(link_code.user_id)
+    }
+}
+
+fn is_generated_user_id(user_id: &str) -> bool {
+    user_id.starts_with("user_")
+}
+
+impl IdentityStore {
+    /// Pairs profiles created under the two id schemes. A generated profile
+    /// is matched to a principal profile when one of its principals owns
+    /// that profile, or failing that, when both share an email address.
+    pub fn find_duplicate_profiles() -> Vec<DuplicateProfile> {
+        let mut duplicates: Vec<DuplicateProfile> = Vec::new();
+
+        for (principal, user_id) in Self::links() {
+            let canonical_id = principal.to_string();
+            if !is_generated_user_id(&user_id)
+                || duplicates.iter().any(|d| d.duplicate_id == user_id)
+                || !UserStorage::exists(&user_id)
+                || !UserStorage::exists(&canonical_id)
+            {
+                continue;
+            }
+            duplicates.push(DuplicateProfile {
+                canonical_id,
+                principals: Self::principals_of(&user_id).into_iter().map(|p| p.principal).collect(),
+                duplicate_id: user_id,
+                reason: "Principal is linked to a generated profile but owns its own".to_string(),
+            });
+        }
+
+        let profiles = UserStorage::get_all();
+        for generated in profiles.iter().filter(|p| is_generated_user_id(&p.id)) {
+            let email = generated.email.trim().to_lowercase();
+            if email.is_empty() || duplicates.iter().any(|d| d.duplicate_id == generated.id) {
+                continue;
+            }
+            if let Some(canonical) = profiles
+                .iter()
+                .find(|p| !is_generated_user_id(&p.id) && p.email.trim().to_lowercase() == email)
+            {
+                duplicates.push(DuplicateProfile {
+                    canonical_id: canonical.id.clone(),
+                    duplicate_id: generated.id.clone(),
+                    principals: Self::principals_of(&generated.id).into_iter().map(|p| p.principal).collect(),
+                    reason: "Profiles share an email address".to_string(),
+                });
+            }
+        }
+
+        duplicates
+    }
+
+    /// Folds `duplicate_id` into `canonical_id`: empty fields of the
+    /// canonical profile are filled from the duplicate, the duplicate's
+    /// records and principals move to the canonical profile, and the
+    /// duplicate is deleted. Payout methods are re-sealed, so the key of
+    /// the canonical user has to be derivable.
+    pub fn merge_profiles(canonical_id: &str, duplicate_id: &str) -> Result<UserProfile, String> {
+        if string_to_storage_key(canonical_id) == string_to_storage_key(duplicate_id) {
+            return Err("Cannot merge a profile into itself".to_string());
+        }
+        let mut canonical = UserStorage::get(canonical_id)
+            .ok_or_else(|| format!("Profile {} not found", canonical_id))?;
+        let duplicate = UserStorage::get(duplicate_id)
+            .ok_or_else(|| format!("Profile {} not found", duplicate_id))?;
+
+        for (field, fallback) in [
+            (&mut canonical.name, duplicate.name),
+            (&mut canonical.email, duplicate.email),
+            (&mut canonical.phone_number, duplicate.phone_number),
+            (&mut canonical.city, duplicate.city),
+            (&mut canonical.country, duplicate.country),
+        ] {
+            if field.trim().is_empty() {
+                *field = fallback;
+            }
+        }
+        canonical.status = canonical.status.max(duplicate.status);
+        canonical.profile_completion = canonical.profile_completion.max(duplicate.profile_completion);
+
+        UserStorage::update_with_validation(canonical.clone())
+            .map_err(|e| format!("Failed to update profile: {:?}", e))?;
+
+        // Moving payout methods is the only step left that can fail; the
+        // trap rolls back the profile update above with it.
+        if let Err(e) = PayoutStorage::transfer(duplicate_id, canonical_id) {
+            ic_cdk::trap(&format!("Failed to move payout methods: {:?}", e));
+        }
+        CVStorage::transfer(duplicate_id, canonical_id);
+        BlobStorage::transfer(duplicate_id, canonical_id);
+        EducationStorage::transfer(duplicate_id, canonical_id);
+        WorkExperienceStorage::transfer(duplicate_id, canonical_id);
+        ChatSessionStorage::transfer(duplicate_id, canonical_id);
+        ProfileCompletion::refresh(canonical_id);
+
+        for linked in Self::principals_of(duplicate_id) {
+            Self::link(linked.principal, canonical_id);
+        }
+        UserStorage::delete(duplicate_id)
+            .map_err(|e| format!("Failed to delete duplicate profile: {:?}", e))?;
+
+        Ok(canonical)
+    }
+}
@@ -3,6 +3,11 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use ic_cdk::api::time;
 
+pub mod access;
+pub mod identity;
+
+pub use identity::{DuplicateProfile, IdentityStore, LinkCode, LinkedPrincipal};
+
 #[derive(Debug, CandidType, Deserialize, Clone)]
 pub struct Session {
     pub principal_id: Principal,
@@ -15,10 +20,17 @@ pub struct AuthenticatedUser {
     pub user_id: String,
 }
 
+/// Heap state of `AuthService`, carried across upgrades.
+#[derive(Debug, CandidType, Deserialize, Clone, Default)]
+pub struct AuthSnapshot {
+    pub sessions: Vec<(Principal, Session)>,
+    /// Links kept on the heap before `IdentityStore` existed. Only read, to
+    /// import them on the first upgrade; new snapshots leave it empty.
+    pub principal_to_user: Vec<(Principal, String)>,
+}
+
 thread_local! {
     static SESSIONS: RefCell<HashMap<Principal, Session>> = RefCell::new(HashMap::new());
-    static PRINCIPAL_TO_USER_MAP: RefCell<HashMap<Principal, String>> = RefCell::new(HashMap::new());
-    static USER_PRINCIPALS: RefCell<HashMap<Principal, String>> = RefCell::new(HashMap::new());
 }
 
 const SESSION_DURATION_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000; 
@@ -55,16 +67,37 @@ impl AuthService {
     }
 
     pub fn associate_user_principal(principal: Principal, user_id: String) {
-        PRINCIPAL_TO_USER_MAP.with(|map| {
-            let mut map = map.borrow_mut();
-            map.insert(principal, user_id);
-        });
+        IdentityStore::link(principal, &user_id);
     }
 
     pub fn get_user_id(principal: &Principal) -> Option<String> {
-        PRINCIPAL_TO_USER_MAP.with(|map| {
-            map.borrow().get(principal).cloned()
-        })
+        IdentityStore::get_user_id(principal)
+    }
+
+    /// The canonical user id acting for `principal`: the user it is linked
+    /// to, or its own text for a principal that was never linked.
+    pub fn resolve_user_id(principal: &Principal) -> String {
+        Self::get_user_id(principal).unwrap_or_else(|| principal.to_string())
+    }
+
+    pub fn export_state() -> AuthSnapshot {
+        AuthSnapshot {
+            sessions: SESSIONS.with(|sessions| {
+                sessions.borrow().iter().map(|(p, s)| (*p, s.clone())).collect()
+            }),
+            principal_to_user: Vec::new(),
+        }
+    }
+
+    pub fn restore_state(snapshot: AuthSnapshot) {
+        SESSIONS.with(|map| {
+            *map.borrow_mut() = snapshot.sessions.into_iter().collect();
+        });
+        for (principal, user_id) in snapshot.principal_to_user {
+            if IdentityStore::get_user_id(&principal).is_none() {
+                IdentityStore::link(principal, &user_id);
+            }
+        }
     }
 
     pub fn is_authenticated(principal: &Principal) -> bool {
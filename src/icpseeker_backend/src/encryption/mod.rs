@@ -1,128 +1,263 @@
+//! Authenticated encryption of personal data at rest.
+//!
+//! A 256-bit master key is drawn from `raw_rand` once and kept in stable
+//! memory, so it survives upgrades. It never encrypts anything itself: each
+//! user's data key is derived from it with HKDF-SHA256 and the user id, and
+//! fields are sealed with ChaCha20-Poly1305 under that key. Nonces are 96
+//! random bits from a ChaCha20 generator seeded by `raw_rand`; the field name
+//! is bound as associated data, so a ciphertext cannot be moved to another
+//! field or another user's record.
+//!
+//! `raw_rand` can only be awaited from an update call, so the key and the
+//! nonce generator are set up by `ensure_ready`, which writers await first.
+//!
+//! Master keys are versioned. `rotate` adds a new version that all new
+//! ciphertexts use; each ciphertext records the version it was sealed under,
+//! so older ones stay readable until the key rotation job re-encrypts them
+//! and `retire_keys_before` drops the old keys.
+
+use candid::CandidType;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
 use ic_cdk::api::management_canister::main::raw_rand;
-use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{StableCell, Storable};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::Sha256;
+use std::borrow::Cow;
 use std::cell::RefCell;
 
+use crate::models::types::{decode_record, encode_record, record_schema_version};
+use crate::storage::registry::{self, Memory};
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+/// HKDF `info` prefix of user data keys; the user id follows it.
+const DATA_KEY_INFO: &[u8] = b"icpseeker/user-data-key/v1/";
+
+/// Version of the first master key, and of ciphertexts sealed before key
+/// versions were recorded.
+pub const FIRST_KEY_VERSION: u32 = 1;
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct EncryptedData {
+    /// Ciphertext followed by the 16-byte Poly1305 tag.
     pub data: Vec<u8>,
     pub nonce: Vec<u8>,
+    /// The master key version; `None` on data sealed before versions were
+    /// recorded, which used `FIRST_KEY_VERSION`.
+    pub key_version: Option<u32>,
 }
 
-thread_local! {
-    static ENCRYPTION_KEYS: RefCell<HashMap<String, Vec<u8>>> = RefCell::new(HashMap::new());
+impl EncryptedData {
+    pub fn key_version(&self) -> u32 {
+        self.key_version.unwrap_or(FIRST_KEY_VERSION)
+    }
 }
 
-pub struct Encryption;
-
-impl Encryption {
-    pub async fn generate_key(user_id: &str) -> Result<(), String> {
-        let random_bytes = match raw_rand().await {
-            Ok((bytes, _)) => bytes,
-            Err(e) => return Err(format!("Failed to generate random bytes: {:?}", e)),
-        };
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct MasterKey {
+    version: u32,
+    key: Vec<u8>,
+    created_at: u64,
+}
 
-        ENCRYPTION_KEYS.with(|keys| {
-            keys.borrow_mut().insert(user_id.to_string(), random_bytes);
-        });
+/// Every master key still needed for decryption. The last one is current.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct KeyRing {
+    keys: Vec<MasterKey>,
+}
 
-        Ok(())
-    }
+/// The single key stored before versions; an empty key meant none yet.
+#[derive(CandidType, Deserialize)]
+struct UnversionedMasterKey {
+    key: Vec<u8>,
+    created_at: u64,
+}
 
-    pub fn encrypt(data: &str, user_id: &str) -> Result<EncryptedData, String> {
-        ENCRYPTION_KEYS.with(|keys| {
-            let keys = keys.borrow();
-            let key = keys.get(user_id)
-                .ok_or_else(|| "Encryption key not found".to_string())?;
+impl KeyRing {
+    const SCHEMA_VERSION: u8 = 2;
+    const UNVERSIONED_SCHEMA_VERSION: u8 = 1;
 
-            let nonce = ic_cdk::api::time().to_be_bytes().to_vec();
+    fn current(&self) -> Option<&MasterKey> {
+        self.keys.last()
+    }
 
-            let mut encrypted = Vec::new();
-            for (i, byte) in data.as_bytes().iter().enumerate() {
-                let key_byte = key[i % key.len()];
-                let nonce_byte = nonce[i % nonce.len()];
-                encrypted.push(byte ^ key_byte ^ nonce_byte);
-            }
+    fn get(&self, version: u32) -> Option<&MasterKey> {
+        self.keys.iter().find(|key| key.version == version)
+    }
+}
 
-            Ok(EncryptedData {
-                data: encrypted,
-                nonce,
-            })
-        })
+impl Storable for KeyRing {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
     }
 
-    pub fn decrypt(encrypted: &EncryptedData, user_id: &str) -> Result<String, String> {
-        ENCRYPTION_KEYS.with(|keys| {
-            let keys = keys.borrow();
-            let key = keys.get(user_id)
-                .ok_or_else(|| "Encryption key not found".to_string())?;
-
-            let mut decrypted = Vec::new();
-            for (i, byte) in encrypted.data.iter().enumerate() {
-                let key_byte = key[i % key.len()];
-                let nonce_byte = encrypted.nonce[i % encrypted.nonce.len()];
-                decrypted.push(byte ^ key_byte ^ nonce_byte);
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(Self::UNVERSIONED_SCHEMA_VERSION) => {
+                let old: UnversionedMasterKey = decode_record(&bytes);
+                let keys = if old.key.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![MasterKey { version: FIRST_KEY_VERSION, key: old.key, created_at: old.created_at }]
+                };
+                Self { keys }
             }
-
-            String::from_utf8(decrypted)
-                .map_err(|e| format!("Failed to decode decrypted data: {:?}", e))
-        })
+            version => panic!("Unsupported key ring schema version {:?}", version),
+        }
     }
 }
 
+/// A master key version as shown to controllers; the key itself is not.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
-pub struct EncryptedBankInformation {
-    pub id: String,
-    pub user_id: String,
-    pub account_holder_name: String,
-    pub bank_name: String,
-    pub encrypted_swift_code: EncryptedData,
-    pub encrypted_account_number: EncryptedData,
-    pub bank_country: String,
-    pub bank_branch: Option<String>,
+pub struct KeyVersionInfo {
+    pub version: u32,
     pub created_at: u64,
-    pub updated_at: u64,
+    pub current: bool,
+}
+
+thread_local! {
+    static KEY_RING: RefCell<StableCell<KeyRing, Memory>> = RefCell::new(
+        registry::init_cell(registry::MASTER_KEY, KeyRing::default())
+    );
+
+    /// Heap only: reseeded after every upgrade.
+    static NONCE_RNG: RefCell<Option<ChaCha20Rng>> = const { RefCell::new(None) };
 }
 
-impl EncryptedBankInformation {
-    pub async fn new(
-        id: String,
-        user_id: String,
-        account_holder_name: String,
-        bank_name: String,
-        swift_code: String,
-        account_number: String,
-        bank_country: String,
-        bank_branch: Option<String>,
-    ) -> Result<Self, String> {
-        if let Err(e) = Encryption::generate_key(&user_id).await {
-            return Err(format!("Failed to generate encryption key: {}", e));
+pub struct Encryption;
+
+impl Encryption {
+    /// Generates the master key if there is none and seeds the nonce
+    /// generator if it is not seeded. Decryption only needs the master key.
+    pub async fn ensure_ready() -> Result<(), String> {
+        if Self::current_version().is_none() {
+            let key = random_bytes().await?;
+            // A concurrent call may have stored one while this one waited.
+            if Self::current_version().is_none() {
+                Self::add_key(FIRST_KEY_VERSION, key)?;
+            }
         }
 
-        let encrypted_swift_code = Encryption::encrypt(&swift_code, &user_id)?;
-        let encrypted_account_number = Encryption::encrypt(&account_number, &user_id)?;
-
-        let timestamp = ic_cdk::api::time();
-        Ok(Self {
-            id,
-            user_id,
-            account_holder_name,
-            bank_name,
-            encrypted_swift_code,
-            encrypted_account_number,
-            bank_country,
-            bank_branch,
-            created_at: timestamp,
-            updated_at: timestamp,
+        if NONCE_RNG.with(|rng| rng.borrow().is_none()) {
+            let seed = random_bytes().await?;
+            NONCE_RNG.with(|rng| {
+                rng.borrow_mut().get_or_insert_with(|| ChaCha20Rng::from_seed(seed));
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds a new master key version and makes it current. Returns the new
+    /// version.
+    pub async fn rotate() -> Result<u32, String> {
+        Self::ensure_ready().await?;
+        let key = random_bytes().await?;
+        let version = Self::current_version().map_or(FIRST_KEY_VERSION, |v| v + 1);
+        Self::add_key(version, key)?;
+        Ok(version)
+    }
+
+    /// The version new ciphertexts are sealed under, once there is a key.
+    pub fn current_version() -> Option<u32> {
+        KEY_RING.with(|ring| ring.borrow().get().current().map(|key| key.version))
+    }
+
+    pub fn key_versions() -> Vec<KeyVersionInfo> {
+        KEY_RING.with(|ring| {
+            let ring = ring.borrow();
+            let current = ring.get().current().map(|key| key.version);
+            ring.get()
+                .keys
+                .iter()
+                .map(|key| KeyVersionInfo {
+                    version: key.version,
+                    created_at: key.created_at,
+                    current: Some(key.version) == current,
+                })
+                .collect()
+        })
+    }
+
+    /// Drops every master key older than `version`, once nothing is sealed
+    /// under them any more.
+    pub fn retire_keys_before(version: u32) -> Result<(), String> {
+        KEY_RING.with(|ring| {
+            let mut ring = ring.borrow_mut();
+            let mut keys = ring.get().clone();
+            keys.keys.retain(|key| key.version >= version);
+            ring.set(keys).map(|_| ()).map_err(|e| format!("Failed to store the key ring: {:?}", e))
         })
     }
 
-    pub fn get_swift_code(&self) -> Result<String, String> {
-        Encryption::decrypt(&self.encrypted_swift_code, &self.user_id)
+    /// Encrypts `plaintext` as the `field` of one of `user_id`'s records,
+    /// under the current master key.
+    pub fn encrypt(user_id: &str, field: &str, plaintext: &str) -> Result<EncryptedData, String> {
+        let key_version = Self::current_version().ok_or_else(|| "No master key has been generated yet".to_string())?;
+        let cipher = Self::cipher(user_id, key_version)?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        NONCE_RNG.with(|rng| {
+            rng.borrow_mut()
+                .as_mut()
+                .map(|rng| rng.fill_bytes(&mut nonce))
+                .ok_or_else(|| "Encryption is not ready yet; try again".to_string())
+        })?;
+
+        let data = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext.as_bytes(), aad: field.as_bytes() })
+            .map_err(|_| "Encryption failed".to_string())?;
+        Ok(EncryptedData { data, nonce: nonce.to_vec(), key_version: Some(key_version) })
     }
 
-    pub fn get_account_number(&self) -> Result<String, String> {
-        Encryption::decrypt(&self.encrypted_account_number, &self.user_id)
+    /// Decrypts a `field` of one of `user_id`'s records. Fails if the data
+    /// was altered or belongs to another user or field.
+    pub fn decrypt(user_id: &str, field: &str, encrypted: &EncryptedData) -> Result<String, String> {
+        if encrypted.nonce.len() != NONCE_LENGTH {
+            return Err(format!("Invalid nonce length {}", encrypted.nonce.len()));
+        }
+        let plaintext = Self::cipher(user_id, encrypted.key_version())?
+            .decrypt(
+                Nonce::from_slice(&encrypted.nonce),
+                Payload { msg: &encrypted.data, aad: field.as_bytes() },
+            )
+            .map_err(|_| format!("Failed to decrypt {}: the data is corrupted or under another key", field))?;
+        String::from_utf8(plaintext).map_err(|e| format!("Failed to decode decrypted data: {:?}", e))
+    }
+
+    fn add_key(version: u32, key: [u8; KEY_LENGTH]) -> Result<(), String> {
+        KEY_RING.with(|ring| {
+            let mut ring = ring.borrow_mut();
+            let mut keys = ring.get().clone();
+            keys.keys.push(MasterKey { version, key: key.to_vec(), created_at: ic_cdk::api::time() });
+            ring.set(keys).map(|_| ()).map_err(|e| format!("Failed to store the key ring: {:?}", e))
+        })
     }
-}
\ No newline at end of file
+
+    fn cipher(user_id: &str, version: u32) -> Result<ChaCha20Poly1305, String> {
+        let master = KEY_RING
+            .with(|ring| ring.borrow().get().get(version).map(|key| key.key.clone()))
+            .ok_or_else(|| format!("Master key version {} is not available", version))?;
+        let mut info = DATA_KEY_INFO.to_vec();
+        info.extend_from_slice(user_id.as_bytes());
+
+        let mut data_key = [0u8; KEY_LENGTH];
+        Hkdf::<Sha256>::new(None, &master)
+            .expand(&info, &mut data_key)
+            .map_err(|e| format!("Failed to derive the data key: {}", e))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&data_key)))
+    }
+}
+
+async fn random_bytes() -> Result<[u8; KEY_LENGTH], String> {
+    let (bytes,) = raw_rand()
+        .await
+        .map_err(|e| format!("Failed to generate random bytes: {:?}", e))?;
+    bytes
+        .get(..KEY_LENGTH)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "raw_rand returned too few bytes".to_string())
+}
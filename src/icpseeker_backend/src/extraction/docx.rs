@@ -0,0 +1,120 @@
+//! Text extraction from DOCX files: the runs of `word/document.xml`, one
+//! line per paragraph. Paragraphs styled as headings or as the title are
+//! reported as outline headings.
+
+use super::zip;
+use super::RawDocument;
+
+const MAX_DOCUMENT_XML: usize = 16 * 1024 * 1024;
+
+pub fn extract(data: &[u8]) -> Result<RawDocument, String> {
+    let xml = zip::read_entry(data, "word/document.xml", MAX_DOCUMENT_XML)?;
+    Ok(parse_document_xml(&String::from_utf8_lossy(&xml)))
+}
+
+fn tag_name(tag: &str) -> &str {
+    let tag = tag.trim_start_matches('/');
+    let end = tag
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or(tag.len());
+    &tag[..end]
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = tag[start..].chars().next()?;
+    let value = &tag[start + 1..];
+    value.find(quote).map(|end| &value[..end])
+}
+
+fn parse_document_xml(xml: &str) -> RawDocument {
+    let mut text = String::new();
+    let mut headings = Vec::new();
+    let mut paragraph = String::new();
+    let mut is_heading = false;
+    let mut in_text = false;
+    let mut rest = xml;
+
+    while let Some(open) = rest.find('<') {
+        if in_text {
+            paragraph.push_str(&decode_entities(&rest[..open]));
+        }
+        let Some(close) = rest[open..].find('>') else {
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+
+        let closing = tag.starts_with('/');
+        let self_closing = tag.ends_with('/');
+        match (tag_name(tag), closing) {
+            ("w:t", false) => in_text = !self_closing,
+            ("w:t", true) => in_text = false,
+            ("w:tab", false) => paragraph.push('\t'),
+            ("w:br" | "w:cr", false) => paragraph.push('\n'),
+            ("w:p", false) => {
+                paragraph.clear();
+                is_heading = false;
+                if self_closing {
+                    text.push('\n');
+                }
+            }
+            ("w:pStyle", false) => {
+                let style = attribute(tag, "w:val").unwrap_or_default().to_lowercase();
+                is_heading = style.starts_with("heading") || style == "title";
+            }
+            ("w:p", true) => {
+                if is_heading && !paragraph.trim().is_empty() {
+                    headings.push(paragraph.trim().to_string());
+                }
+                text.push_str(&paragraph);
+                text.push('\n');
+                paragraph.clear();
+            }
+            _ => {}
+        }
+    }
+
+    RawDocument { text, headings }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|&semi| semi <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
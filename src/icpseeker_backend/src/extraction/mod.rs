@@ -0,0 +1,173 @@
+//! Plain-text extraction from uploaded CV files.
+//!
+//! Everything here is pure Rust without system libraries so that it runs
+//! inside the canister (wasm32). Extracted text is normalized (whitespace,
+//! typographic punctuation, ligatures) and capped so it fits next to the blob
+//! in stable memory, and an outline of section headings is derived from it.
+
+mod docx;
+mod pdf;
+mod zip;
+
+use ic_cdk::api::time;
+
+use crate::models::blob::{DocumentFormat, ExtractedText, OutlineEntry};
+
+pub const MAX_TEXT_BYTES: usize = 48 * 1024;
+const MAX_OUTLINE_ENTRIES: usize = 64;
+const MAX_HEADING_CHARS: usize = 60;
+
+/// Common section headings of English and Indonesian CVs, lowercase.
+const SECTION_HEADINGS: &[&str] = &[
+    "summary", "professional summary", "profile", "professional profile", "about me", "objective",
+    "career objective", "experience", "work experience", "professional experience",
+    "employment history", "work history", "education", "skills", "technical skills", "core skills",
+    "languages", "certifications", "certificates", "licenses & certifications", "projects",
+    "awards", "achievements", "honors & awards", "publications", "volunteer experience",
+    "volunteering", "organizations", "organizational experience", "courses", "training",
+    "interests", "hobbies", "references", "contact", "contact information", "personal details",
+    "ringkasan", "profil", "tentang saya", "pengalaman", "pengalaman kerja", "riwayat pekerjaan",
+    "pendidikan", "riwayat pendidikan", "keahlian", "keterampilan", "bahasa", "sertifikasi",
+    "proyek", "penghargaan", "prestasi", "pengalaman organisasi", "organisasi", "pelatihan",
+    "minat", "referensi", "kontak", "data diri", "data pribadi",
+];
+
+/// Text and style-derived headings as they come out of a format reader.
+pub(crate) struct RawDocument {
+    pub text: String,
+    pub headings: Vec<String>,
+}
+
+pub fn detect_format(content_type: &str, data: &[u8]) -> DocumentFormat {
+    let content_type = content_type.to_ascii_lowercase();
+    if data.starts_with(b"%PDF-") || content_type == "application/pdf" {
+        DocumentFormat::Pdf
+    } else if data.starts_with(b"PK\x03\x04")
+        && (content_type.contains("wordprocessingml") || content_type.is_empty() || content_type == "application/zip")
+    {
+        DocumentFormat::Docx
+    } else if content_type.starts_with("text/") || std::str::from_utf8(data).is_ok() {
+        DocumentFormat::PlainText
+    } else {
+        DocumentFormat::Unsupported
+    }
+}
+
+/// Extracts normalized text and an outline from a file. Never fails: a file
+/// that cannot be read yields empty text and the reason in `error`.
+pub fn extract(content_type: &str, data: &[u8]) -> ExtractedText {
+    let format = detect_format(content_type, data);
+    let raw = match format {
+        DocumentFormat::Pdf => pdf::extract(data),
+        DocumentFormat::Docx => docx::extract(data),
+        DocumentFormat::PlainText => Ok(RawDocument {
+            text: String::from_utf8_lossy(data).into_owned(),
+            headings: Vec::new(),
+        }),
+        DocumentFormat::Unsupported => Err("Unsupported file type".to_string()),
+    };
+
+    let (raw, error) = match raw {
+        Ok(raw) => (raw, None),
+        Err(e) => (RawDocument { text: String::new(), headings: Vec::new() }, Some(e)),
+    };
+    let (text, truncated) = truncate(normalize(&raw.text), MAX_TEXT_BYTES);
+    let error = error.or_else(|| (text.is_empty()).then(|| "No text found in file".to_string()));
+
+    ExtractedText {
+        format,
+        outline: outline(&text, &raw.headings),
+        text,
+        truncated,
+        error,
+        extracted_at: time(),
+    }
+}
+
+fn normalize_char(c: char, out: &mut String) {
+    match c {
+        '\n' | '\r' | '\u{2028}' | '\u{2029}' | '\u{000B}' | '\u{000C}' => out.push('\n'),
+        '\u{00AD}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{FFFD}' => {}
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => out.push('\''),
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => out.push('"'),
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => out.push('-'),
+        '\u{2026}' => out.push_str("..."),
+        '\u{FB00}' => out.push_str("ff"),
+        '\u{FB01}' => out.push_str("fi"),
+        '\u{FB02}' => out.push_str("fl"),
+        '\u{FB03}' => out.push_str("ffi"),
+        '\u{FB04}' => out.push_str("ffl"),
+        c if c.is_whitespace() => out.push(' '),
+        c if c.is_control() || ('\u{E000}'..='\u{F8FF}').contains(&c) => {}
+        c => out.push(c),
+    }
+}
+
+/// Unifies line endings, whitespace and typographic punctuation, trims
+/// every line and collapses runs of spaces and of blank lines.
+pub(crate) fn normalize(raw: &str) -> String {
+    let mut mapped = String::with_capacity(raw.len());
+    for c in raw.replace("\r\n", "\n").chars() {
+        normalize_char(c, &mut mapped);
+    }
+
+    let mut out = String::with_capacity(mapped.len());
+    let mut blank_run = 0;
+    for line in mapped.lines() {
+        let line = line.split(' ').filter(|word| !word.is_empty()).collect::<Vec<_>>().join(" ");
+        if line.is_empty() {
+            blank_run += 1;
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank_run > 0 { "\n\n" } else { "\n" });
+        }
+        out.push_str(&line);
+        blank_run = 0;
+    }
+    out
+}
+
+/// Cuts `text` to at most `max` bytes, at a line end when there is one.
+fn truncate(text: String, max: usize) -> (String, bool) {
+    if text.len() <= max {
+        return (text, false);
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').unwrap_or(end);
+    (text[..end].trim_end().to_string(), true)
+}
+
+fn is_section_heading(line: &str) -> bool {
+    let chars = line.chars().count();
+    if !(2..=MAX_HEADING_CHARS).contains(&chars) || line.ends_with(['.', ',', ';']) {
+        return false;
+    }
+    let key = line.trim_end_matches(':').trim().to_lowercase();
+    if SECTION_HEADINGS.contains(&key.as_str()) {
+        return true;
+    }
+
+    // Short all-caps lines ("WORK EXPERIENCE") are headings in most layouts.
+    let letters: Vec<char> = line.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() >= 3
+        && letters.iter().all(|c| c.is_uppercase())
+        && line.split_whitespace().count() <= 4
+        && !line.contains('@')
+}
+
+fn outline(text: &str, headings: &[String]) -> Vec<OutlineEntry> {
+    let headings: Vec<String> = headings.iter().map(|h| normalize(h)).collect();
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| headings.iter().any(|h| h == line) || is_section_heading(line))
+        .take(MAX_OUTLINE_ENTRIES)
+        .map(|(line_number, line)| OutlineEntry {
+            title: line.trim_end_matches(':').chars().take(MAX_HEADING_CHARS).collect(),
+            line: line_number as u32,
+        })
+        .collect()
+}
@@ -0,0 +1,1096 @@
+//! Text extraction from PDF files.
+//!
+//! Only what CVs need. Objects are found by scanning for `N G obj`, so a
+//! broken cross-reference table does not matter; object streams are
+//! expanded; pages are read in page-tree order; and the text-showing
+//! operators are decoded through each font's `ToUnicode` map when it has one.
+//! Line breaks are inferred from vertical text movement.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use miniz_oxide::inflate::{decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit};
+
+use super::RawDocument;
+
+const MAX_DECODED_STREAM: usize = 8 * 1024 * 1024;
+const MAX_DEPTH: usize = 32;
+const MAX_PAGES: usize = 200;
+/// `TJ` adjustments (thousandths of an em) more negative than this are
+/// treated as word gaps.
+const TJ_SPACE_THRESHOLD: f64 = -200.0;
+
+#[derive(Clone, Debug)]
+enum Object {
+    Null,
+    Number(f64),
+    Name(Vec<u8>),
+    Str(Vec<u8>),
+    Array(Vec<Object>),
+    Dict(Dict),
+    Ref(u32),
+    Stream(Dict, Vec<u8>),
+    Operator(Vec<u8>),
+}
+
+static NULL: Object = Object::Null;
+
+#[derive(Clone, Debug, Default)]
+struct Dict(Vec<(Vec<u8>, Object)>);
+
+impl Dict {
+    fn get(&self, key: &[u8]) -> Option<&Object> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn name(&self, key: &[u8]) -> Option<&[u8]> {
+        self.get(key).and_then(Object::as_name)
+    }
+}
+
+impl Object {
+    fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Object::Dict(dict) | Object::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+
+    fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Object::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Object]> {
+        match self {
+            Object::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, 0 | b'\t' | b'\n' | 0x0C | b'\r' | b' ')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(b, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn find(data: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|at| at + from)
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Tokenizer and object parser, used both for the file body and for content
+/// streams, where bare keywords come back as `Operator`s.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while let Some(b) = self.peek() {
+                    if b == b'\n' || b == b'\r' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn word(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) || is_delimiter(b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    fn parse(&mut self, depth: usize) -> Option<Object> {
+        self.skip_whitespace();
+        let b = self.peek()?;
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        Some(match b {
+            b'/' => {
+                self.pos += 1;
+                Object::Name(decode_name(self.word()))
+            }
+            b'(' => {
+                self.pos += 1;
+                Object::Str(self.literal_string())
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Object::Dict(self.dict(depth))
+            }
+            b'<' => {
+                self.pos += 1;
+                Object::Str(self.hex_string())
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        None => break,
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        _ => match self.parse(depth + 1) {
+                            Some(item) => items.push(item),
+                            None => break,
+                        },
+                    }
+                }
+                Object::Array(items)
+            }
+            b'0'..=b'9' | b'+' | b'-' | b'.' => self.number(),
+            b')' | b'>' | b']' | b'{' | b'}' => {
+                self.pos += 1;
+                Object::Operator(vec![b])
+            }
+            _ => match self.word() {
+                // No boolean ever matters for text extraction.
+                b"true" | b"false" | b"null" => Object::Null,
+                word => Object::Operator(word.to_vec()),
+            },
+        })
+    }
+
+    fn dict(&mut self, depth: usize) -> Dict {
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.data[self.pos.min(self.data.len())..].starts_with(b">>") {
+                self.pos += 2;
+                break;
+            }
+            let Some(key) = self.parse(depth + 1) else {
+                break;
+            };
+            let Object::Name(key) = key else {
+                continue;
+            };
+            let Some(value) = self.parse(depth + 1) else {
+                break;
+            };
+            entries.push((key, value));
+        }
+        Dict(entries)
+    }
+
+    /// A number, or an `N G R` reference when one follows.
+    fn number(&mut self) -> Object {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9' | b'+' | b'-' | b'.')) {
+            self.pos += 1;
+        }
+        let token = &self.data[start..self.pos];
+        let value = std::str::from_utf8(token).ok().and_then(|t| t.parse::<f64>().ok()).unwrap_or(0.0);
+
+        if token.iter().all(u8::is_ascii_digit) {
+            let after_number = self.pos;
+            self.skip_whitespace();
+            let generation_start = self.pos;
+            while matches!(self.peek(), Some(b'0'..=b'9')) {
+                self.pos += 1;
+            }
+            if self.pos > generation_start {
+                self.skip_whitespace();
+                let is_ref = self.peek() == Some(b'R')
+                    && self.data.get(self.pos + 1).is_none_or(|b| is_whitespace(*b) || is_delimiter(*b));
+                if is_ref {
+                    self.pos += 1;
+                    return Object::Ref(value as u32);
+                }
+            }
+            self.pos = after_number;
+        }
+        Object::Number(value)
+    }
+
+    fn literal_string(&mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        break;
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => out.push(b'\n'),
+                        b'r' => out.push(b'\r'),
+                        b't' => out.push(b'\t'),
+                        b'b' => out.push(8),
+                        b'f' => out.push(12),
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(d @ b'0'..=b'7') => {
+                                        value = value * 8 + (d - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            out.push(value as u8);
+                        }
+                        other => out.push(other),
+                    }
+                }
+                b'(' => {
+                    depth += 1;
+                    out.push(b);
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    out.push(b);
+                }
+                _ => out.push(b),
+            }
+        }
+        out
+    }
+
+    fn hex_string(&mut self) -> Vec<u8> {
+        let mut digits = Vec::new();
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            if b == b'>' {
+                break;
+            }
+            if let Some(d) = hex_value(b) {
+                digits.push(d);
+            }
+        }
+        if digits.len() % 2 == 1 {
+            digits.push(0);
+        }
+        digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect()
+    }
+
+    /// Skips inline image data (`BI ... ID <binary> EI`), which would
+    /// otherwise be tokenized as text.
+    fn skip_inline_image(&mut self) {
+        let Some(id) = find(self.data, b"ID", self.pos) else {
+            self.pos = self.data.len();
+            return;
+        };
+        let mut at = id + 2;
+        while let Some(ei) = find(self.data, b"EI", at) {
+            let before = ei.checked_sub(1).map(|i| self.data[i]);
+            let after = self.data.get(ei + 2).copied();
+            if before.is_some_and(is_whitespace) && after.is_none_or(|b| is_whitespace(b) || is_delimiter(b)) {
+                self.pos = ei + 2;
+                return;
+            }
+            at = ei + 2;
+        }
+        self.pos = self.data.len();
+    }
+}
+
+fn decode_name(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        match (raw[i], raw.get(i + 1).and_then(|b| hex_value(*b)), raw.get(i + 2).and_then(|b| hex_value(*b))) {
+            (b'#', Some(hi), Some(lo)) => {
+                out.push(hi << 4 | lo);
+                i += 3;
+            }
+            (b, _, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The object number of an `N G obj` header whose `obj` keyword is at `at`.
+fn object_number_before(data: &[u8], at: usize) -> Option<u32> {
+    let mut i = at;
+    let skip_whitespace = |i: &mut usize| {
+        let start = *i;
+        while *i > 0 && is_whitespace(data[*i - 1]) {
+            *i -= 1;
+        }
+        *i < start
+    };
+    let skip_digits = |i: &mut usize| {
+        let end = *i;
+        while *i > 0 && data[*i - 1].is_ascii_digit() {
+            *i -= 1;
+        }
+        (*i < end).then_some(*i..end)
+    };
+
+    if !skip_whitespace(&mut i) {
+        return None;
+    }
+    skip_digits(&mut i)?;
+    if !skip_whitespace(&mut i) {
+        return None;
+    }
+    let number = skip_digits(&mut i)?;
+    if i > 0 && !is_whitespace(data[i - 1]) && !is_delimiter(data[i - 1]) {
+        return None;
+    }
+    std::str::from_utf8(&data[number]).ok()?.parse().ok()
+}
+
+/// Raw bytes of the stream following `dict`, if the lexer stands at a
+/// `stream` keyword. Falls back to searching for `endstream` when `/Length`
+/// is indirect or wrong; it comes from the upload, so it may be negative,
+/// fractional or past the end of the file.
+fn stream_data(data: &[u8], lexer: &mut Lexer, dict: &Dict) -> Option<Vec<u8>> {
+    lexer.skip_whitespace();
+    if !data[lexer.pos.min(data.len())..].starts_with(b"stream") {
+        return None;
+    }
+    let mut start = lexer.pos + 6;
+    if data.get(start) == Some(&b'\r') {
+        start += 1;
+    }
+    if data.get(start) == Some(&b'\n') {
+        start += 1;
+    }
+
+    let declared_end = dict
+        .get(b"Length")
+        .and_then(Object::as_number)
+        .filter(|length| *length >= 0.0 && length.fract() == 0.0)
+        .and_then(|length| usize::try_from(length as u64).ok())
+        .and_then(|length| start.checked_add(length))
+        .filter(|&end| {
+            if end > data.len() {
+                return false;
+            }
+            let mut after = Lexer::new(data, end);
+            after.skip_whitespace();
+            data[after.pos.min(data.len())..].starts_with(b"endstream")
+        });
+
+    let end = match declared_end {
+        Some(end) => end,
+        None => {
+            let mut end = find(data, b"endstream", start)?;
+            if end > start && data[end - 1] == b'\n' {
+                end -= 1;
+            }
+            if end > start && data[end - 1] == b'\r' {
+                end -= 1;
+            }
+            end
+        }
+    };
+    lexer.pos = find(data, b"endstream", end).map_or(data.len(), |at| at + 9);
+    Some(data[start..end].to_vec())
+}
+
+fn scan_objects(data: &[u8]) -> HashMap<u32, Object> {
+    let mut objects = HashMap::new();
+    let mut from = 0;
+    while let Some(at) = find(data, b"obj", from) {
+        from = at + 3;
+        if !data.get(from).is_none_or(|b| is_whitespace(*b) || is_delimiter(*b)) {
+            continue;
+        }
+        let Some(number) = object_number_before(data, at) else {
+            continue;
+        };
+        let mut lexer = Lexer::new(data, from);
+        let Some(object) = lexer.parse(0) else {
+            continue;
+        };
+        let object = match object {
+            Object::Dict(dict) => match stream_data(data, &mut lexer, &dict) {
+                Some(bytes) => Object::Stream(dict, bytes),
+                None => Object::Dict(dict),
+            },
+            other => other,
+        };
+        from = lexer.pos.max(from);
+        // Later definitions (incremental updates) replace earlier ones.
+        objects.insert(number, object);
+    }
+    objects
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    match decompress_to_vec_zlib_with_limit(data, MAX_DECODED_STREAM) {
+        Ok(out) => Ok(out),
+        // Truncated streams are common; keep what could be decoded. The
+        // output buffer comes back untrimmed, zero-filled past that point.
+        Err(e) if e.output.iter().any(|b| *b != 0) => {
+            let mut output = e.output;
+            let decoded = output.iter().rposition(|b| *b != 0).map_or(0, |last| last + 1);
+            output.truncate(decoded);
+            Ok(output)
+        }
+        Err(_) => decompress_to_vec_with_limit(data, MAX_DECODED_STREAM)
+            .map_err(|e| format!("Failed to inflate stream: {:?}", e.status)),
+    }
+}
+
+fn ascii_hex(data: &[u8]) -> Vec<u8> {
+    Lexer::new(data, 0).hex_string()
+}
+
+fn ascii85(data: &[u8]) -> Vec<u8> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let mut out = Vec::with_capacity(data.len() * 4 / 5);
+    let mut value: u32 = 0;
+    let mut count = 0;
+    for &b in data {
+        match b {
+            b'~' => break,
+            b'z' if count == 0 => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                value = value.wrapping_mul(85).wrapping_add((b - b'!') as u32);
+                count += 1;
+                if count == 5 {
+                    out.extend_from_slice(&value.to_be_bytes());
+                    value = 0;
+                    count = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+    if count > 1 {
+        for _ in count..5 {
+            value = value.wrapping_mul(85).wrapping_add(84);
+        }
+        out.extend_from_slice(&value.to_be_bytes()[..count - 1]);
+    }
+    out
+}
+
+fn decode_stream(dict: &Dict, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let filters: Vec<&[u8]> = match dict.get(b"Filter") {
+        Some(Object::Name(name)) => vec![name.as_slice()],
+        Some(Object::Array(items)) => items.iter().filter_map(Object::as_name).collect(),
+        _ => Vec::new(),
+    };
+
+    let mut data = raw.to_vec();
+    for filter in filters {
+        data = match filter {
+            b"FlateDecode" | b"Fl" => inflate(&data)?,
+            b"ASCIIHexDecode" | b"AHx" => ascii_hex(&data),
+            b"ASCII85Decode" | b"A85" => ascii85(&data),
+            other => return Err(format!("Unsupported stream filter {}", String::from_utf8_lossy(other))),
+        };
+    }
+    Ok(data)
+}
+
+fn expand_object_streams(objects: &mut HashMap<u32, Object>) {
+    let streams: Vec<(Dict, Vec<u8>)> = objects
+        .values()
+        .filter_map(|object| match object {
+            Object::Stream(dict, raw) if dict.name(b"Type") == Some(b"ObjStm") => Some((dict.clone(), raw.clone())),
+            _ => None,
+        })
+        .collect();
+
+    for (dict, raw) in streams {
+        let Ok(data) = decode_stream(&dict, &raw) else {
+            continue;
+        };
+        let count = dict.get(b"N").and_then(Object::as_number).unwrap_or(0.0) as usize;
+        let first = dict.get(b"First").and_then(Object::as_number).unwrap_or(0.0) as usize;
+
+        let mut header = Lexer::new(&data, 0);
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            match (header.parse(0), header.parse(0)) {
+                (Some(Object::Number(number)), Some(Object::Number(offset))) => {
+                    entries.push((number as u32, first + offset as usize))
+                }
+                _ => break,
+            }
+        }
+        for (number, offset) in entries {
+            if objects.contains_key(&number) {
+                continue;
+            }
+            if let Some(object) = Lexer::new(&data, offset).parse(0) {
+                objects.insert(number, object);
+            }
+        }
+    }
+}
+
+/// How a font's string bytes map to text.
+struct Font {
+    to_unicode: HashMap<u32, String>,
+    code_len: usize,
+    composite: bool,
+}
+
+impl Font {
+    fn decode(&self, bytes: &[u8]) -> String {
+        if self.to_unicode.is_empty() {
+            // Composite fonts without a ToUnicode map show glyph ids, which
+            // cannot be turned back into text.
+            return if self.composite { String::new() } else { bytes.iter().map(|b| win_ansi(*b)).collect() };
+        }
+        let mut text = String::new();
+        for code in bytes.chunks(self.code_len) {
+            match self.to_unicode.get(&code_value(code)) {
+                Some(mapped) => text.push_str(mapped),
+                None if self.code_len == 1 => text.push(win_ansi(code[0])),
+                None => {}
+            }
+        }
+        text
+    }
+}
+
+fn code_value(bytes: &[u8]) -> u32 {
+    bytes.iter().take(4).fold(0, |value, b| value << 8 | *b as u32)
+}
+
+fn utf16_be(bytes: &[u8]) -> String {
+    if bytes.len() % 2 == 1 {
+        return bytes.iter().map(|b| *b as char).collect();
+    }
+    let units = bytes.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units).filter_map(Result::ok).filter(|c| *c != '\0').collect()
+}
+
+/// WinAnsiEncoding, which matches Latin-1 outside 0x80–0x9F.
+fn win_ansi(b: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', ' ', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', ' ', 'Ž', ' ',
+        ' ', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', ' ', 'ž', 'Ÿ',
+    ];
+    match b {
+        0x80..=0x9F => HIGH[(b - 0x80) as usize],
+        _ => b as char,
+    }
+}
+
+/// Reads the `bfchar`/`bfrange` mappings of a ToUnicode CMap.
+fn parse_cmap(data: &[u8], default_code_len: usize) -> (HashMap<u32, String>, usize) {
+    let mut map = HashMap::new();
+    let mut code_len = None;
+    let mut operands: Vec<Object> = Vec::new();
+    let mut lexer = Lexer::new(data, 0);
+
+    while let Some(object) = lexer.parse(0) {
+        let Object::Operator(op) = object else {
+            operands.push(object);
+            continue;
+        };
+        match op.as_slice() {
+            b"endcodespacerange" => {
+                if let Some(Object::Str(low)) = operands.first() {
+                    code_len = Some(low.len().clamp(1, 4));
+                }
+            }
+            b"endbfchar" => {
+                for pair in operands.chunks(2) {
+                    if let [Object::Str(code), Object::Str(text)] = pair {
+                        map.insert(code_value(code), utf16_be(text));
+                    }
+                }
+            }
+            b"endbfrange" => {
+                for range in operands.chunks(3) {
+                    let [Object::Str(low), Object::Str(high), target] = range else {
+                        continue;
+                    };
+                    let (low, high) = (code_value(low), code_value(high));
+                    if high < low || high - low > 0xFFFF {
+                        continue;
+                    }
+                    match target {
+                        Object::Str(start) => {
+                            for (offset, code) in (low..=high).enumerate() {
+                                map.insert(code, utf16_be(&offset_utf16(start, offset as u16)));
+                            }
+                        }
+                        Object::Array(targets) => {
+                            for (code, text) in (low..=high).zip(targets) {
+                                if let Object::Str(text) = text {
+                                    map.insert(code, utf16_be(text));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        operands.clear();
+    }
+
+    (map, code_len.unwrap_or(default_code_len))
+}
+
+/// `start` with `offset` added to its last UTF-16 unit, as `bfrange`
+/// targets are incremented.
+fn offset_utf16(start: &[u8], offset: u16) -> Vec<u8> {
+    let mut bytes = start.to_vec();
+    let n = bytes.len();
+    if n >= 2 {
+        let last = u16::from_be_bytes([bytes[n - 2], bytes[n - 1]]).wrapping_add(offset);
+        bytes[n - 2..].copy_from_slice(&last.to_be_bytes());
+    } else if n == 1 {
+        bytes[0] = bytes[0].wrapping_add(offset as u8);
+    }
+    bytes
+}
+
+struct Document {
+    objects: HashMap<u32, Object>,
+    fonts: HashMap<u32, Rc<Font>>,
+}
+
+impl Document {
+    fn resolve<'a>(&'a self, object: &'a Object) -> &'a Object {
+        let mut current = object;
+        for _ in 0..MAX_DEPTH {
+            match current {
+                Object::Ref(id) => match self.objects.get(id) {
+                    Some(object) => current = object,
+                    None => return &NULL,
+                },
+                _ => return current,
+            }
+        }
+        &NULL
+    }
+
+    fn get<'a>(&'a self, dict: &'a Dict, key: &[u8]) -> Option<&'a Object> {
+        dict.get(key).map(|object| self.resolve(object))
+    }
+
+    /// Page dictionaries in reading order, each with its (possibly
+    /// inherited) resources.
+    fn pages(&self) -> Vec<(&Dict, Option<&Dict>)> {
+        let root = self
+            .objects
+            .iter()
+            .filter(|(_, object)| object.as_dict().and_then(|d| d.name(b"Type")) == Some(b"Catalog"))
+            .max_by_key(|(id, _)| **id)
+            .and_then(|(_, catalog)| self.get(catalog.as_dict()?, b"Pages"))
+            .and_then(Object::as_dict);
+
+        let mut pages = Vec::new();
+        match root {
+            Some(root) => self.walk_pages(root, None, &mut pages, 0),
+            None => {
+                let mut ids: Vec<&u32> = self
+                    .objects
+                    .iter()
+                    .filter(|(_, object)| object.as_dict().and_then(|d| d.name(b"Type")) == Some(b"Page"))
+                    .map(|(id, _)| id)
+                    .collect();
+                ids.sort();
+                for id in ids.into_iter().take(MAX_PAGES) {
+                    if let Some(page) = self.objects[id].as_dict() {
+                        pages.push((page, self.get(page, b"Resources").and_then(Object::as_dict)));
+                    }
+                }
+            }
+        }
+        pages
+    }
+
+    fn walk_pages<'a>(
+        &'a self,
+        node: &'a Dict,
+        inherited: Option<&'a Dict>,
+        pages: &mut Vec<(&'a Dict, Option<&'a Dict>)>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH || pages.len() >= MAX_PAGES {
+            return;
+        }
+        let resources = self.get(node, b"Resources").and_then(Object::as_dict).or(inherited);
+        match self.get(node, b"Kids").and_then(Object::as_array) {
+            Some(kids) => {
+                for kid in kids {
+                    if let Some(kid) = self.resolve(kid).as_dict() {
+                        self.walk_pages(kid, resources, pages, depth + 1);
+                    }
+                }
+            }
+            None => pages.push((node, resources)),
+        }
+    }
+
+    fn load_font(&self, font: &Dict) -> Font {
+        let composite = font.name(b"Subtype") == Some(b"Type0");
+        let default_code_len = if composite { 2 } else { 1 };
+        let (to_unicode, code_len) = match self.get(font, b"ToUnicode") {
+            Some(Object::Stream(dict, raw)) => match decode_stream(dict, raw) {
+                Ok(data) => parse_cmap(&data, default_code_len),
+                Err(_) => (HashMap::new(), default_code_len),
+            },
+            _ => (HashMap::new(), default_code_len),
+        };
+        Font { to_unicode, code_len, composite }
+    }
+
+    fn font(&mut self, font_resources: Option<&Dict>, name: &[u8]) -> Option<Rc<Font>> {
+        let reference = font_resources?.get(name)?.clone();
+        let id = match reference {
+            Object::Ref(id) => id,
+            direct => return direct.as_dict().map(|font| Rc::new(self.load_font(font))),
+        };
+        if let Some(font) = self.fonts.get(&id) {
+            return Some(font.clone());
+        }
+        let font = Rc::new(self.load_font(self.objects.get(&id)?.as_dict()?));
+        self.fonts.insert(id, font.clone());
+        Some(font)
+    }
+
+    fn page_content(&self, page: &Dict) -> Vec<u8> {
+        let streams: Vec<&Object> = match self.get(page, b"Contents") {
+            Some(Object::Array(items)) => items.iter().map(|item| self.resolve(item)).collect(),
+            Some(object) => vec![object],
+            None => Vec::new(),
+        };
+        let mut content = Vec::new();
+        for stream in streams {
+            if let Object::Stream(dict, raw) = stream {
+                if let Ok(data) = decode_stream(dict, raw) {
+                    content.extend_from_slice(&data);
+                    content.push(b'\n');
+                }
+            }
+        }
+        content
+    }
+
+    fn page_text(&mut self, page: &Dict, resources: Option<&Dict>) -> String {
+        let content = self.page_content(page);
+        let font_resources = resources
+            .and_then(|resources| self.get(resources, b"Font"))
+            .and_then(Object::as_dict)
+            .cloned();
+
+        let mut writer = TextWriter::default();
+        let mut font: Option<Rc<Font>> = None;
+        let mut operands: Vec<Object> = Vec::new();
+        let mut lexer = Lexer::new(&content, 0);
+
+        while let Some(object) = lexer.parse(0) {
+            let Object::Operator(op) = object else {
+                operands.push(object);
+                continue;
+            };
+            let number = |i: usize| operands.get(i).and_then(Object::as_number).unwrap_or(0.0);
+            match op.as_slice() {
+                b"BI" => lexer.skip_inline_image(),
+                b"BT" => writer.move_to(0.0),
+                b"Tf" => {
+                    font = match operands.first() {
+                        Some(Object::Name(name)) => self.font(font_resources.as_ref(), name),
+                        _ => None,
+                    }
+                }
+                b"Td" | b"TD" => writer.move_by(number(1)),
+                b"Tm" => writer.move_to(number(5)),
+                b"T*" => writer.next_line(),
+                b"Tj" => {
+                    if let Some(Object::Str(bytes)) = operands.last() {
+                        writer.show(&decode_text(font.as_deref(), bytes));
+                    }
+                }
+                b"'" | b"\"" => {
+                    writer.next_line();
+                    if let Some(Object::Str(bytes)) = operands.last() {
+                        writer.show(&decode_text(font.as_deref(), bytes));
+                    }
+                }
+                b"TJ" => {
+                    for item in operands.last().and_then(Object::as_array).unwrap_or_default() {
+                        match item {
+                            Object::Str(bytes) => writer.show(&decode_text(font.as_deref(), bytes)),
+                            Object::Number(n) if *n < TJ_SPACE_THRESHOLD => writer.space(),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+            operands.clear();
+        }
+
+        writer.text
+    }
+}
+
+fn decode_text(font: Option<&Font>, bytes: &[u8]) -> String {
+    match font {
+        Some(font) => font.decode(bytes),
+        None => bytes.iter().map(|b| win_ansi(*b)).collect(),
+    }
+}
+
+/// Accumulates shown text, starting a new line when the text position moves
+/// vertically and separating horizontally positioned runs with a space.
+#[derive(Default)]
+struct TextWriter {
+    text: String,
+    y: f64,
+    shown_y: Option<f64>,
+    moved: bool,
+    line_break: bool,
+}
+
+impl TextWriter {
+    fn move_to(&mut self, y: f64) {
+        self.y = y;
+        self.moved = true;
+    }
+
+    fn move_by(&mut self, dy: f64) {
+        self.y += dy;
+        self.moved = true;
+    }
+
+    fn next_line(&mut self) {
+        self.line_break = true;
+    }
+
+    fn space(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+            self.text.push(' ');
+        }
+    }
+
+    fn show(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let new_line = self.line_break || self.shown_y.is_some_and(|y| (y - self.y).abs() > 0.5);
+        if new_line && !self.text.is_empty() {
+            if !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+        } else if self.moved {
+            self.space();
+        }
+        self.text.push_str(text);
+        self.shown_y = Some(self.y);
+        self.moved = false;
+        self.line_break = false;
+    }
+}
+
+pub fn extract(data: &[u8]) -> Result<RawDocument, String> {
+    if find(&data[..data.len().min(1024)], b"%PDF-", 0).is_none() {
+        return Err("Not a PDF file".to_string());
+    }
+
+    // The key sits in the trailer, which is not an object.
+    if find(data, b"/Encrypt", 0).is_some() {
+        return Err("Encrypted PDFs are not supported".to_string());
+    }
+    let mut objects = scan_objects(data);
+    if objects.is_empty() {
+        return Err("No PDF objects found".to_string());
+    }
+    expand_object_streams(&mut objects);
+
+    let mut document = Document {
+        objects,
+        fonts: HashMap::new(),
+    };
+    let pages: Vec<(Dict, Option<Dict>)> = document
+        .pages()
+        .into_iter()
+        .map(|(page, resources)| (page.clone(), resources.cloned()))
+        .collect();
+
+    let text = pages
+        .iter()
+        .map(|(page, resources)| document.page_text(page, resources.as_ref()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Ok(RawDocument {
+        text,
+        headings: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+
+    const CONTENT: &[u8] = b"BT /F1 12 Tf 72 700 Td (Hello CV) Tj ET";
+
+    /// A one-page PDF whose content stream (object 4) has `stream_dict` and
+    /// `stream` as its body, followed by `tail`.
+    fn pdf(stream_dict: &str, stream: &[u8], tail: &str) -> Vec<u8> {
+        let mut out = b"%PDF-1.4\n".to_vec();
+        out.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+        out.extend_from_slice(b"2 0 obj\n<< /Type /Pages /Kids [3 0 R] /Count 1 >>\nendobj\n");
+        out.extend_from_slice(
+            b"3 0 obj\n<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>\nendobj\n",
+        );
+        out.extend_from_slice(format!("4 0 obj\n{}\nstream\n", stream_dict).as_bytes());
+        out.extend_from_slice(stream);
+        out.extend_from_slice(b"\nendstream\nendobj\n");
+        out.extend_from_slice(b"5 0 obj\n<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj\n");
+        out.extend_from_slice(tail.as_bytes());
+        out
+    }
+
+    fn text_of(data: &[u8]) -> String {
+        extract(data).expect("extraction failed").text
+    }
+
+    #[test]
+    fn extracts_text_with_exact_length() {
+        let data = pdf(&format!("<< /Length {} >>", CONTENT.len()), CONTENT, "%%EOF\n");
+        assert_eq!(text_of(&data), "Hello CV");
+    }
+
+    #[test]
+    fn ignores_malformed_xref() {
+        let tail = "xref\n0 6\nnot an xref table\ntrailer\n<< /Size 6 /Root 1 0 R >>\nstartxref\n999999\n%%EOF\n";
+        let data = pdf(&format!("<< /Length {} >>", CONTENT.len()), CONTENT, tail);
+        assert_eq!(text_of(&data), "Hello CV");
+
+        let truncated = "xref\n0 6\n0000000000 65535 f";
+        let data = pdf(&format!("<< /Length {} >>", CONTENT.len()), CONTENT, truncated);
+        assert_eq!(text_of(&data), "Hello CV");
+    }
+
+    #[test]
+    fn recovers_from_bad_stream_lengths() {
+        for length in ["-5", "-1e30", "1e30", "18446744073709551615", "3.5", "5", "100000", "6 0 R", "/Name"] {
+            let data = pdf(&format!("<< /Length {} >>", length), CONTENT, "%%EOF\n");
+            assert_eq!(text_of(&data), "Hello CV", "/Length {}", length);
+        }
+        let data = pdf("<< >>", CONTENT, "");
+        assert_eq!(text_of(&data), "Hello CV");
+    }
+
+    #[test]
+    fn survives_a_stream_cut_off_at_the_end() {
+        let mut data = pdf(&format!("<< /Length {} >>", CONTENT.len()), CONTENT, "");
+        let cut = find(&data, b"stream\n", 0).unwrap() + 10;
+        data.truncate(cut);
+        assert!(extract(&data).is_ok());
+
+        for length in ["0", "4", "1000"] {
+            let data = format!("%PDF-1.4\n1 0 obj\n<< /Length {} >>\nstream", length);
+            let _ = extract(data.as_bytes());
+        }
+    }
+
+    #[test]
+    fn decodes_filters() {
+        let flate = compress_to_vec_zlib(CONTENT, 6);
+        let data = pdf(&format!("<< /Length {} /Filter /FlateDecode >>", flate.len()), &flate, "");
+        assert_eq!(text_of(&data), "Hello CV");
+
+        let hex: String = CONTENT.iter().map(|b| format!("{:02X}", b)).collect::<String>() + ">";
+        let data = pdf("<< /Filter /AHx >>", hex.as_bytes(), "");
+        assert_eq!(text_of(&data), "Hello CV");
+
+        let chained: String = flate.iter().map(|b| format!("{:02x}", b)).collect::<String>() + ">";
+        let data = pdf("<< /Filter [/ASCIIHexDecode /FlateDecode] >>", chained.as_bytes(), "");
+        assert_eq!(text_of(&data), "Hello CV");
+    }
+
+    #[test]
+    fn rejects_unsupported_and_broken_filters() {
+        let dict = Dict(vec![(b"Filter".to_vec(), Object::Name(b"DCTDecode".to_vec()))]);
+        assert!(decode_stream(&dict, CONTENT).is_err());
+        let data = pdf("<< /Filter /DCTDecode >>", CONTENT, "");
+        assert_eq!(text_of(&data), "");
+
+    }
+
+    #[test]
+    fn keeps_the_decoded_part_of_a_truncated_flate_stream() {
+        let content = CONTENT.repeat(200);
+        let flate = compress_to_vec_zlib(&content, 6);
+        let dict = Dict(vec![(b"Filter".to_vec(), Object::Name(b"FlateDecode".to_vec()))]);
+        let decoded = decode_stream(&dict, &flate[..flate.len() / 2]).unwrap();
+        assert!(!decoded.is_empty() && content.starts_with(&decoded));
+    }
+
+    #[test]
+    fn decodes_ascii85() {
+        assert_eq!(ascii85(b"<~9jqo^~>"), b"Man ");
+        assert_eq!(ascii85(b"z~>"), [0; 4]);
+        assert_eq!(ascii85(b"9jqo~>"), b"Man");
+        assert_eq!(ascii85(b"s8W-!~>"), [0xFF; 4]);
+    }
+
+    #[test]
+    fn rejects_non_pdf_and_encrypted_files() {
+        assert!(extract(b"").is_err());
+        assert!(extract(b"PK\x03\x04 not a pdf").is_err());
+        assert!(extract(b"%PDF-1.7\ntrailer << /Encrypt 9 0 R >>").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Just enough of the ZIP format to read one entry of a DOCX: the central
+//! directory is located through the end-of-central-directory record, and
+//! entries may be stored or deflated. ZIP64 and encryption are not supported.
+
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+const EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x05, 0x06];
+const CENTRAL_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+const LOCAL_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+const EOCD_LEN: usize = 22;
+
+fn u16_at(data: &[u8], at: usize) -> Result<u16, String> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "Truncated ZIP archive".to_string())
+}
+
+fn u32_at(data: &[u8], at: usize) -> Result<u32, String> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "Truncated ZIP archive".to_string())
+}
+
+fn find_eocd(data: &[u8]) -> Result<usize, String> {
+    if data.len() < EOCD_LEN {
+        return Err("Not a ZIP archive".to_string());
+    }
+    // The record is followed by a comment of at most 64 KiB.
+    let lowest = data.len().saturating_sub(EOCD_LEN + u16::MAX as usize);
+    (lowest..=data.len() - EOCD_LEN)
+        .rev()
+        .find(|&at| data[at..at + 4] == EOCD_SIGNATURE)
+        .ok_or_else(|| "Not a ZIP archive".to_string())
+}
+
+/// Decompressed contents of the entry called `name`, at most `limit` bytes.
+pub fn read_entry(data: &[u8], name: &str, limit: usize) -> Result<Vec<u8>, String> {
+    let eocd = find_eocd(data)?;
+    let entries = u16_at(data, eocd + 10)?;
+    let mut at = u32_at(data, eocd + 16)? as usize;
+
+    for _ in 0..entries {
+        if data.get(at..at + 4) != Some(&CENTRAL_SIGNATURE[..]) {
+            return Err("Corrupt ZIP central directory".to_string());
+        }
+        let method = u16_at(data, at + 10)?;
+        let compressed_size = u32_at(data, at + 20)? as usize;
+        let name_len = u16_at(data, at + 28)? as usize;
+        let extra_len = u16_at(data, at + 30)? as usize;
+        let comment_len = u16_at(data, at + 32)? as usize;
+        let local_offset = u32_at(data, at + 42)? as usize;
+        let entry_name = data
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| "Truncated ZIP archive".to_string())?;
+
+        if entry_name == name.as_bytes() {
+            if compressed_size == u32::MAX as usize || local_offset == u32::MAX as usize {
+                return Err("ZIP64 archives are not supported".to_string());
+            }
+            return read_local_entry(data, local_offset, method, compressed_size, limit);
+        }
+        at += 46 + name_len + extra_len + comment_len;
+    }
+
+    Err(format!("{} not found in archive", name))
+}
+
+fn read_local_entry(
+    data: &[u8],
+    offset: usize,
+    method: u16,
+    compressed_size: usize,
+    limit: usize,
+) -> Result<Vec<u8>, String> {
+    if data.get(offset..offset + 4) != Some(&LOCAL_SIGNATURE[..]) {
+        return Err("Corrupt ZIP local header".to_string());
+    }
+    if u16_at(data, offset + 6)? & 1 != 0 {
+        return Err("Encrypted archives are not supported".to_string());
+    }
+    let name_len = u16_at(data, offset + 26)? as usize;
+    let extra_len = u16_at(data, offset + 28)? as usize;
+    let start = offset + 30 + name_len + extra_len;
+    let compressed = data
+        .get(start..start + compressed_size)
+        .ok_or_else(|| "Truncated ZIP archive".to_string())?;
+
+    match method {
+        0 if compressed.len() <= limit => Ok(compressed.to_vec()),
+        0 => Err("Archive entry is too large".to_string()),
+        8 => decompress_to_vec_with_limit(compressed, limit)
+            .map_err(|e| format!("Failed to inflate archive entry: {:?}", e.status)),
+        other => Err(format!("Unsupported ZIP compression method {}", other)),
+    }
+}
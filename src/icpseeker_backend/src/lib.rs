@@ -1,134 +1,143 @@
-use crate::models::cv::{CV, CVAnalysisStatus};
-use crate::storage::CVStorage;
+use crate::models::cv::{CV, CVDiff, CVVersion, CVVersionInfo};
+use crate::storage::{CVStorage, PayoutStorage};
+use crate::services::profile_completion::ProfileChecklist;
+use crate::services::email_verification::EmailVerificationStatus;
+use crate::services::mail::MailConfig;
+use crate::services::key_rotation::{EncryptionStatus, KeyRotationService, KeyRotationStatus};
+use crate::validation::bank::BankSuggestion;
+use crate::models::payout::PayoutMethodRecord;
+use crate::validation::phone::PhoneCountry;
+use crate::models::cv_document::{CvDocument, CvEntry};
+use crate::models::blob::{BlobInfo, BlobUsage, ExtractedText, UploadProgress, UploadSession};
 use candid::{candid_method, Principal};
 use ic_cdk_macros::{query, update};
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use ic_stable_structures::{
-    memory_manager::MemoryManager,
-    DefaultMemoryImpl,
-};
 use std::cell::RefCell;
-use ic_cdk::println;
-use ic_cdk::api::{self, caller}; 
+use std::time::Duration;
 use crate::models::chat::{ChatResponse, ChatHistoryResponse};
-use crate::services::chat::ChatService;
 use crate::ai_service::analyzer;
 pub use crate::ai_service::analyzer::CVAnalyzer;
 use ic_cdk::api::management_canister::http_request::{ http_request, CanisterHttpRequestArgument, HttpMethod, TransformContext, HttpHeader, HttpResponse, TransformArgs };
-use ic_stable_structures::memory_manager::{MemoryId, VirtualMemory};
-use ic_stable_structures::StableBTreeMap;
-use crate::models::{
-    StorageKey,
-    FixedString,
-    user::StableUserProfile,
-    bank::StableBankInformation,
-    education::StableEducationRecord,
-    cv::StableCV,
-    chat::{StableChatMessage, StableChatSession, ChatMessage},
-    rate_limit::StableUserAPIUsage,
+use crate::models::chat::{ChatMessage, ChatSession};
+use crate::models::pagination::{Page, PageRequest};
+use crate::auth::access::{
+    anyone, can_manage_ai_config, can_moderate_content, can_wipe_data, is_admin, is_controller, is_signed_in, Admin,
+    AdminPermission, BusinessMember, Role,
 };
-use ic_cdk::api::time;
-use crate::storage::memory::ChatSessionStorage;  
-use crate::storage::ChatStorage;
-use crate::auth::{AuthService, Session};
+use crate::auth::{AuthService, DuplicateProfile, LinkCode, LinkedPrincipal, Session};
+use crate::encryption::Encryption;
+use crate::migrations::{MigrationService, MigrationStatus, UpgradeState};
+use crate::storage::registry;
+use crate::api::v2;
 
 pub mod ai_service;
+mod api;
 pub mod services {
     pub mod chat;
     pub mod ai; 
+    pub mod cv_history;
+    pub mod cv_document;
+    pub mod cv_parser;
+    pub mod profile_completion;
+    pub mod mail;
+    pub mod email_verification;
+    pub mod key_rotation;
 }
 mod validation;
 mod models;
 mod storage;
 mod types;
 mod auth;
-
-const MEMORY_ID_USERS: MemoryId = MemoryId::new(0);
-const MEMORY_ID_EDUCATION: MemoryId = MemoryId::new(1);
-const MEMORY_ID_BANK: MemoryId = MemoryId::new(2);
-const MEMORY_ID_API_USAGE: MemoryId = MemoryId::new(3);
-const MEMORY_ID_CV: MemoryId = MemoryId::new(4);
-const MEMORY_ID_CHAT: MemoryId = MemoryId::new(5);
-const MEMORY_ID_CHAT_SESSION: MemoryId = MemoryId::new(6);
-
-type CVMemory = VirtualMemory<DefaultMemoryImpl>;
+mod encryption;
+mod migrations;
+mod extraction;
 
 use crate::models::{
     UserProfile,
     education::{
-        EducationRecord, EducationLevel, EducationStatus,
-        HighSchoolEducation, UniversityEducation
+        EducationRecord, EducationDetails, EducationLevel, EducationStatus,
+        GradeScale, NormalizedGrade
     },
     bank::BankInformation
 };
-use crate::storage::memory::{UserStorage, EducationStorage, BankStorage};
-use crate::types::errors::StorageError;
+use crate::storage::memory::IndexRebuildReport;
+use crate::models::experience::{EmploymentType, WorkHistory, YearMonth};
+use crate::types::errors::{ApiError, FieldError, StorageError};
+use crate::validation::describe;
 
 thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
-        MemoryManager::init(DefaultMemoryImpl::default())
-    );
-
-    static CONTROLLER: RefCell<Principal> = RefCell::new(Principal::anonymous());
-    
     static OPENAI_API_KEY: RefCell<String> = RefCell::new(String::new());
+}
 
-    static USERS: RefCell<StableBTreeMap<StorageKey, StableUserProfile, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_USERS));
-        StableBTreeMap::init(memory)
-    });
+#[ic_cdk::init]
+fn init() {
+    registry::check_layout();
 
-    static BANK_INFO: RefCell<StableBTreeMap<StorageKey, StableBankInformation, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_BANK));
-        StableBTreeMap::init(memory)
-    });
+    MigrationService::mark_current();
+    schedule_encryption_setup();
+}
 
-    static CV_STORAGE: RefCell<StableBTreeMap<StorageKey, StableCV, CVMemory>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CV));
-        StableBTreeMap::init(memory)
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    MigrationService::save_upgrade_state(UpgradeState {
+        openai_api_key: OPENAI_API_KEY.with(|k| k.borrow().clone()),
+        auth: AuthService::export_state(),
     });
+}
 
-    static EDUCATION_RECORDS: RefCell<StableBTreeMap<StorageKey, StableEducationRecord, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_EDUCATION));
-        StableBTreeMap::init(memory)
-    });
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    registry::check_layout();
 
-    static API_USAGE_STORAGE: RefCell<StableBTreeMap<FixedString, StableUserAPIUsage, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)));
-        StableBTreeMap::init(memory)
-    });
+    let state = MigrationService::take_upgrade_state();
 
-    static CHAT_STORAGE: RefCell<StableBTreeMap<FixedString, StableChatMessage, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::new(MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CHAT)))
-    );
-    
-   
-    static CHAT_SESSION_STORAGE: RefCell<StableBTreeMap<FixedString, StableChatSession, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CHAT_SESSION));
-        StableBTreeMap::init(memory)
-    });
+    OPENAI_API_KEY.with(|k| *k.borrow_mut() = state.openai_api_key);
+    AuthService::restore_state(state.auth);
+
+    MigrationService::run_pending();
+    schedule_encryption_setup();
 }
 
-#[ic_cdk::init]
-fn init() {
-    let caller = ic_cdk::caller();
-    
-    MEMORY_MANAGER.with(|m| {
-        let _ = m.borrow_mut();
+/// `raw_rand` cannot be awaited in `init` or `post_upgrade`, so the master
+/// key and nonce generator are set up by a timer right after. Bank fields
+/// stored in plain text meanwhile, e.g. by a migration, are sealed then,
+/// and a key rotation the upgrade interrupted carries on.
+fn schedule_encryption_setup() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || {
+        ic_cdk::spawn(async {
+            let sealed = Encryption::ensure_ready()
+                .await
+                .and_then(|()| PayoutStorage::seal_plaintext().map_err(|e| e.to_string()));
+            if sealed.is_ok() {
+                KeyRotationService::resume();
+            }
+            match sealed {
+                Ok(0) => {}
+                Ok(count) => ic_cdk::println!("Encrypted {} payout methods stored in plain text", count),
+                Err(e) => ic_cdk::println!("Encryption setup failed: {}", e),
+            }
+        });
     });
+}
 
-    CONTROLLER.with(|c| {
-        *c.borrow_mut() = caller;
-    });
+#[ic_cdk::query(guard = "is_admin")]
+#[candid_method(query)]
+fn pending_migrations() -> MigrationStatus {
+    MigrationService::status()
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "anyone")]
 #[candid_method(query)]
 fn get_principal() -> Principal {
     ic_cdk::api::caller()
 }
 
+/// The canonical user id of the caller, following linked principals.
+fn caller_user_id() -> String {
+    AuthService::resolve_user_id(&ic_cdk::caller())
+}
+
 #[derive(CandidType, Serialize, Deserialize)]
 pub struct CreateUserPayload {
     pub name: String,
@@ -151,6 +160,7 @@ pub struct UpdateUserPayload {
 pub enum UserResponse {
     Success(UserProfile),
     Error(String),
+    Invalid(Vec<FieldError>),
 }
 
 #[derive(CandidType, Serialize, Deserialize)]
@@ -174,6 +184,7 @@ pub struct UniversityPayload {
     pub start_year: u32,
     pub end_year: Option<u32>,
     pub gpa: Option<f32>,
+    pub gpa_scale: Option<GradeScale>,
     pub status: EducationStatus,
 }
 
@@ -187,6 +198,19 @@ pub struct EducationPayload {
 pub enum EducationResponse {
     Success(EducationRecord),
     Error(String),
+    Invalid(Vec<FieldError>),
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct WorkExperiencePayload {
+    pub company: String,
+    pub title: String,
+    pub employment_type: EmploymentType,
+    pub location: Option<String>,
+    pub start: YearMonth,
+    pub end: Option<YearMonth>,
+    pub is_current: bool,
+    pub description: Option<String>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
@@ -199,10 +223,29 @@ pub struct BankInfoPayload {
     pub bank_branch: Option<String>,
 }
 
+/// Where a new payout method sends money. Ledger accounts come in their
+/// textual forms, whose checksums are checked.
+#[derive(CandidType, Serialize, Deserialize)]
+pub enum PayoutMethodInput {
+    BankAccount(BankInfoPayload),
+    /// The ICRC-1 encoding: the owner principal, or
+    /// `<owner>-<checksum>.<subaccount hex>` for a non-default subaccount.
+    Icrc1Account(String),
+    /// 64 hex digits.
+    IcpAccountIdentifier(String),
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct PayoutMethodPayload {
+    pub label: Option<String>,
+    pub method: PayoutMethodInput,
+}
+
 #[derive(CandidType, Serialize, Deserialize)]
 pub enum BankResponse {
     Success(BankInformation),
     Error(StorageError),
+    Invalid(Vec<FieldError>),
 }
 
 #[derive(CandidType, Serialize, Deserialize)]
@@ -218,10 +261,29 @@ pub struct UpdateCVPayload {
     pub content: String,
 }
 
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct BeginUploadPayload {
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    /// SHA-256 of the whole file, hex encoded.
+    pub sha256: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize)]
+pub struct UploadChunkPayload {
+    pub upload_id: u64,
+    pub index: u32,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
 #[derive(CandidType, Serialize, Deserialize)]
 pub struct CVResponse {
     pub cv: Option<CV>,
     pub message: String,
+    /// Invalid payload fields; empty unless the payload was rejected.
+    pub errors: Vec<FieldError>,
 }
 
 #[derive(CandidType, Serialize, Deserialize)]
@@ -230,578 +292,389 @@ pub struct CVListResponse {
     pub message: String,
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "anyone")]
 fn transform_response(args: TransformArgs) -> HttpResponse {
     args.response
 }
 
-#[ic_cdk::update]
-#[candid_method(update)]
-pub async fn create_user(payload: CreateUserPayload) -> UserResponse {
-    let caller = caller();
-    let user_id = caller.to_string();
-    
-    if payload.name.trim().is_empty() || 
-       payload.email.trim().is_empty() || 
-       payload.phone_number.trim().is_empty() || 
-       payload.city.trim().is_empty() || 
-       payload.country.trim().is_empty() {
-        return UserResponse::Error("All fields are required".to_string());
-    }
-    
-    let user = UserProfile::new(
-        user_id,
-        payload.name,
-        payload.email,
-        payload.phone_number,
-        payload.city,
-        payload.country,
-    );
-
-    // Attempt to save the user
-    match UserStorage::save_with_validation(user.clone()) {
-        Ok(()) => UserResponse::Success(user),
-        Err(e) => UserResponse::Error(format!("Failed to create user: {:?}", e)),
+// The v1 endpoints below are deprecated adapters over `api::v2`, kept until
+// clients have moved over. They keep their v1 response shapes.
+
+impl From<Result<UserProfile, ApiError>> for UserResponse {
+    fn from(result: Result<UserProfile, ApiError>) -> Self {
+        match result {
+            Ok(user) => UserResponse::Success(user),
+            Err(ApiError::Validation(errors)) => UserResponse::Invalid(errors),
+            Err(e) => UserResponse::Error(e.to_string()),
+        }
     }
 }
 
-#[ic_cdk::query]
-#[candid_method(query)]
-pub async fn get_user() -> UserResponse {
-    let principal = ic_cdk::api::caller();
-    
-    match AuthService::get_user_id(&principal) {
-        Some(user_id) => {
-            match UserStorage::get(&user_id) {
-                Some(user) => UserResponse::Success(user),
-                None => UserResponse::Error("User not found".to_string()),
-            }
+impl From<Result<EducationRecord, ApiError>> for EducationResponse {
+    fn from(result: Result<EducationRecord, ApiError>) -> Self {
+        match result {
+            Ok(record) => EducationResponse::Success(record),
+            Err(ApiError::Validation(errors)) => EducationResponse::Invalid(errors),
+            Err(e) => EducationResponse::Error(e.to_string()),
         }
-        None => UserResponse::Error("No user associated with this principal".to_string()),
     }
 }
 
-#[ic_cdk::query]
-#[candid_method(query)]
-pub async fn get_user_by_id(user_id: String) -> UserResponse {
-    let caller = ic_cdk::api::caller().to_string();
-
-    
-    match UserStorage::get(&user_id) {
-        Some(user) => UserResponse::Success(user),
-        None => UserResponse::Error("User not found".to_string()),
+impl From<Result<BankInformation, ApiError>> for BankResponse {
+    fn from(result: Result<BankInformation, ApiError>) -> Self {
+        match result {
+            Ok(info) => BankResponse::Success(info),
+            Err(ApiError::Validation(errors)) => BankResponse::Invalid(errors),
+            Err(ApiError::NotFound(msg)) => BankResponse::Error(StorageError::NotFound(msg)),
+            Err(e) => BankResponse::Error(StorageError::SystemError(e.to_string())),
+        }
     }
 }
 
-#[ic_cdk::update]
-#[candid_method(update)]
-pub async fn update_user(payload: UpdateUserPayload) -> UserResponse {
-    let principal = ic_cdk::api::caller();
-    
-
-    let user_id = match AuthService::get_user_id(&principal) {
-        Some(id) => id,
-        None => {
-            let new_user_id = generate_unique_user_id();
-            let user = UserProfile::new(
-                new_user_id.clone(),
-                payload.name.unwrap_or_default(),
-                payload.email.unwrap_or_default(),
-                payload.phone_number.unwrap_or_default(),
-                payload.city.unwrap_or_default(),
-                payload.country.unwrap_or_default(),
-            );
-            
-            match UserStorage::save_with_validation(user.clone()) {
-                Ok(_) => {
-                    AuthService::associate_user_principal(principal, new_user_id.clone());
-                    return UserResponse::Success(user);
-                }
-                Err(e) => return UserResponse::Error(format!("Failed to create user: {:?}", e)),
-            }
+impl CVResponse {
+    fn from_result(result: Result<CV, ApiError>, success_message: impl Into<String>) -> Self {
+        match result {
+            Ok(cv) => CVResponse {
+                cv: Some(cv),
+                message: success_message.into(),
+                errors: vec![],
+            },
+            Err(ApiError::Validation(errors)) => CVResponse {
+                cv: None,
+                message: describe(&errors),
+                errors,
+            },
+            Err(e) => CVResponse {
+                cv: None,
+                message: e.to_string(),
+                errors: vec![],
+            },
         }
-    };
-
-    let mut user = match UserStorage::get(&user_id) {
-        Some(user) => user,
-        None => return UserResponse::Error("User not found".to_string()),
-    };
-
-    if let Some(name) = payload.name {
-        user.name = name;
-    }
-    if let Some(email) = payload.email {
-        user.email = email;
-    }
-    if let Some(phone) = payload.phone_number {
-        user.phone_number = phone;
-    }
-    if let Some(city) = payload.city {
-        user.city = city;
-    }
-    if let Some(country) = payload.country {
-        user.country = country;
     }
+}
 
-    match UserStorage::update_with_validation(user.clone()) {
-        Ok(()) => UserResponse::Success(user),
-        Err(e) => UserResponse::Error(format!("Failed to update user: {:?}", e)),
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn create_user(payload: CreateUserPayload) -> UserResponse {
+    v2::v2_create_user(payload).into()
 }
 
-fn generate_unique_user_id() -> String {
-    format!("user_{}", ic_cdk::api::time())
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub async fn get_user() -> UserResponse {
+    v2::v2_get_user().into()
 }
 
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub async fn get_user_by_id(user_id: String) -> UserResponse {
+    v2::v2_get_user_by_id(user_id).into()
+}
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
-pub async fn add_education(payload: EducationPayload) -> EducationResponse {
-    let user_id = ic_cdk::api::caller().to_string();
-    let education_id = format!("EDU_{}", user_id);
-
-    let mut education_record = EducationRecord::new(education_id, user_id);
-
-    if let Some(hs_payload) = payload.high_school {
-        let high_school = HighSchoolEducation::new(
-            hs_payload.school_name,
-            hs_payload.track,
-            hs_payload.city,
-            hs_payload.country,
-            hs_payload.start_year,
-            hs_payload.end_year,
-            hs_payload.status,
-        );
-        education_record.add_high_school(high_school);
-    }
+pub async fn update_user(payload: UpdateUserPayload) -> UserResponse {
+    v2::v2_update_user(payload).into()
+}
 
-    if let Some(uni_payloads) = payload.university {
-        education_record.clear_universities(); 
-        for uni_payload in uni_payloads {
-            let university = UniversityEducation::new(
-                uni_payload.university_name,
-                uni_payload.level,
-                uni_payload.major,
-                uni_payload.city,
-                uni_payload.country,
-                uni_payload.start_year,
-                uni_payload.end_year,
-                uni_payload.gpa,
-                uni_payload.status,
-            );
-            education_record.add_university(university);
-        }
-    }
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_profile_checklist() -> Result<ProfileChecklist, String> {
+    v2::v2_get_profile_checklist().map_err(|e| e.to_string())
+}
 
-    match EducationStorage::save_with_validation(education_record.clone()) {
-        Ok(()) => EducationResponse::Success(education_record),
-        Err(e) => EducationResponse::Error(format!("Failed to update education record: {:?}", e)),
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn add_education(payload: EducationPayload) -> EducationResponse {
+    v2::v2_add_education(payload).into()
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub async fn get_education() -> EducationResponse {
-    let user_id = ic_cdk::api::caller().to_string();
-    
-    match EducationStorage::get_by_user(&user_id) {
-        Some(record) => EducationResponse::Success(record),
-        None => EducationResponse::Error("Education record not found".to_string()),
-    }
+    v2::v2_get_education().into()
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 pub async fn update_education(payload: EducationPayload) -> EducationResponse {
-    let user_id = ic_cdk::api::caller().to_string();
-    
-    let mut education_record = match EducationStorage::get_by_user(&user_id) {
-        Some(record) => record,
-        None => return EducationResponse::Error("Education record not found".to_string()),
-    };
-
-    if let Some(hs_payload) = payload.high_school {
-        let high_school = HighSchoolEducation::new(
-            hs_payload.school_name,
-            hs_payload.track,
-            hs_payload.city,
-            hs_payload.country,
-            hs_payload.start_year,
-            hs_payload.end_year,
-            hs_payload.status,
-        );
-        education_record.add_high_school(high_school);
-    }
-
-    if let Some(uni_payloads) = payload.university {
-        education_record.universities.clear(); 
-        for uni_payload in uni_payloads {
-            let university = UniversityEducation::new(
-                uni_payload.university_name,
-                uni_payload.level,
-                uni_payload.major,
-                uni_payload.city,
-                uni_payload.country,
-                uni_payload.start_year,
-                uni_payload.end_year,
-                uni_payload.gpa,
-                uni_payload.status,
-            );
-            education_record.add_university(university);
-        }
-    }
+    v2::v2_update_education(payload).into()
+}
 
-    match EducationStorage::update_with_validation(education_record.clone()) {
-        Ok(()) => EducationResponse::Success(education_record),
-        Err(e) => EducationResponse::Error(format!("Failed to update education record: {:?}", e)),
-    }
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_normalized_grades() -> Vec<NormalizedGrade> {
+    v2::v2_get_normalized_grades().unwrap_or_default()
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
-pub async fn add_bank_info(payload: BankInfoPayload) -> BankResponse {
-    println!("Starting add_bank_info with payload: {:?}", payload);  // Debug log
+pub fn add_education_entry(details: EducationDetails) -> Result<EducationRecord, String> {
+    v2::v2_add_education_entry(details).map_err(|e| e.to_string())
+}
 
-    // Validate payload fields
-    if payload.account_holder_name.trim().is_empty() {
-        return BankResponse::Error(StorageError::ValidationError(
-            "Account holder name cannot be empty".to_string()
-        ));
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn update_education_entry(entry_id: u32, details: EducationDetails) -> Result<EducationRecord, String> {
+    v2::v2_update_education_entry(entry_id, details).map_err(|e| e.to_string())
+}
 
-    if payload.account_number.trim().is_empty() {
-        return BankResponse::Error(StorageError::ValidationError(
-            "Account number cannot be empty".to_string()
-        ));
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn remove_education_entry(entry_id: u32) -> Result<EducationRecord, String> {
+    v2::v2_remove_education_entry(entry_id).map_err(|e| e.to_string())
+}
 
-    if payload.swift_code.trim().is_empty() {
-        return BankResponse::Error(StorageError::ValidationError(
-            "SWIFT code cannot be empty".to_string()
-        ));
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn reorder_education_entries(entry_ids: Vec<u32>) -> Result<EducationRecord, String> {
+    v2::v2_reorder_education_entries(entry_ids).map_err(|e| e.to_string())
+}
 
-    let user_id = ic_cdk::api::caller().to_string();
-    println!("User ID: {}", user_id);  // Debug log
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_work_history() -> WorkHistory {
+    v2::v2_get_work_history().expect("work history is always available")
+}
 
-    // Check if user exists
-    if !UserStorage::exists(&user_id) {
-        println!("User not found: {}", user_id);  // Debug log
-        return BankResponse::Error(StorageError::InvalidReference(
-            "User does not exist".to_string()
-        ));
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn add_work_experience(payload: WorkExperiencePayload) -> Result<WorkHistory, String> {
+    v2::v2_add_work_experience(payload).map_err(|e| e.to_string())
+}
 
-    let bank_id = format!("BANK_{}", user_id);
-    println!("Generated bank_id: {}", bank_id);  // Debug log
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn update_work_experience(id: u32, payload: WorkExperiencePayload) -> Result<WorkHistory, String> {
+    v2::v2_update_work_experience(id, payload).map_err(|e| e.to_string())
+}
 
-    // Check if bank info already exists
-    if let Some(_existing) = BankStorage::get_by_user(&user_id) {
-        println!("Bank info already exists for user: {}", user_id);  // Debug log
-        return BankResponse::Error(StorageError::AlreadyExists(
-            "Bank information already exists for this user".to_string()
-        ));
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn delete_work_experience(id: u32) -> Result<WorkHistory, String> {
+    v2::v2_delete_work_experience(id).map_err(|e| e.to_string())
+}
 
-    let bank_info = BankInformation::new(
-        bank_id.clone(),
-        user_id.clone(),
-        payload.account_holder_name,
-        payload.bank_name,
-        payload.swift_code,
-        payload.account_number,
-        payload.bank_country,
-        payload.bank_branch,
-    );
-
-    println!("Created bank_info object: {:?}", bank_info);  // Debug log
-
-    match BankStorage::save_with_validation(bank_info.clone()) {
-        Ok(()) => {
-            println!("Successfully saved bank info");  // Debug log
-            BankResponse::Success(bank_info)
-        },
-        Err(e) => {
-            println!("Error saving bank info: {:?}", e);  // Debug log
-            BankResponse::Error(e)
-        }
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn add_bank_info(payload: BankInfoPayload) -> BankResponse {
+    v2::v2_add_bank_info(payload).await.into()
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub async fn get_bank_info() -> BankResponse {
-    let user_id = ic_cdk::api::caller().to_string();
-    
-    match BankStorage::get_by_user(&user_id) {
-        Some(info) => BankResponse::Success(info),
-        None => BankResponse::Error(StorageError::NotFound(
-            "Bank information not found".to_string()
-        )),
-    }
+    v2::v2_get_bank_info().into()
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 pub async fn update_bank_info(payload: BankInfoPayload) -> BankResponse {
-    let user_id = ic_cdk::api::caller().to_string();
-    
-    let mut bank_info = match BankStorage::get_by_user(&user_id) {
-        Some(info) => info,
-        None => return BankResponse::Error(StorageError::NotFound(
-            "Bank information not found".to_string()
-        )),
-    };
-
-    bank_info.account_holder_name = payload.account_holder_name;
-    bank_info.bank_name = payload.bank_name;
-    bank_info.swift_code = payload.swift_code;
-    bank_info.account_number = payload.account_number;
-    bank_info.bank_country = payload.bank_country;
-    bank_info.bank_branch = payload.bank_branch;
-    bank_info.updated_at = ic_cdk::api::time();
-
-    match BankStorage::update_with_validation(bank_info.clone()) {
-        Ok(()) => BankResponse::Success(bank_info),
-        Err(e) => BankResponse::Error(e), 
-    }
+    v2::v2_update_bank_info(payload).await.into()
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub async fn get_bank_info_by_user_id(user_id: String) -> BankResponse {
-    match BankStorage::get_by_user(&user_id) {
-        Some(info) => BankResponse::Success(info),
-        None => BankResponse::Error(StorageError::NotFound(
-            "Bank information not found".to_string()
-        )),
-    }
+    v2::v2_get_bank_info_by_user_id(user_id).into()
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 pub async fn upload_cv(payload: CreateCVPayload) -> CVResponse {
-    let caller = ic_cdk::caller();
-    let user_id = caller.to_string();
-    
-    if !UserStorage::exists(&user_id) {
-        return CVResponse {
-            cv: None,
-            message: "User not found".to_string(),
-        };
-    }
-
-    let next_version = CVStorage::get_latest_version(&user_id) + 1;
-    
-    let cv = CV::new(
-        format!("{}_{}", user_id, next_version),
-        user_id,
-        payload.title,
-        payload.content,
-    );
-
-    match CVStorage::store_cv(cv.clone()) {
-        Ok(_) => {
-            let cv_id = cv.id.clone();
-            ic_cdk::spawn(async move {
-                let _ = CVAnalyzer::analyze_cv(cv_id).await;
-            });
-
-            CVResponse {
-                cv: Some(cv),
-                message: "CV uploaded successfully".to_string(),
-            }
-        }
-        Err(e) => CVResponse {
-            cv: None,
-            message: format!("Failed to store CV: {}", e),
-        },
-    }
+    CVResponse::from_result(v2::v2_upload_cv(payload), "CV uploaded successfully")
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub async fn get_cv(id: String) -> CVResponse {
-    let caller = ic_cdk::caller().to_string();
-    
-    match CVStorage::get_cv(&id) {
-        Ok(cv) => {
-            if !cv.user_id.starts_with(&caller[..caller.len().min(cv.user_id.len())]) {
-                return CVResponse {
-                    cv: None,
-                    message: "Access denied".to_string(),
-                };
-            }
-            CVResponse {
-                cv: Some(cv),
-                message: "CV retrieved successfully".to_string(),
-            }
-        }
-        Err(e) => CVResponse {
-            cv: None,
-            message: format!("Failed to retrieve CV: {}", e),
-        },
-    }
+    CVResponse::from_result(v2::v2_get_cv(id), "CV retrieved successfully")
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub async fn get_my_cvs() -> CVListResponse {
-    let caller = ic_cdk::caller();
-    let user_id = caller.to_string();
-    
-    if !UserStorage::exists(&user_id) {
-        return CVListResponse {
-            cvs: vec![],
-            message: "User not found".to_string(),
-        };
-    }
-
-    match CVStorage::get_user_cvs(&user_id) {
+    match v2::v2_get_my_cvs() {
         Ok(cvs) => CVListResponse {
             cvs,
             message: "CVs retrieved successfully".to_string(),
         },
         Err(e) => CVListResponse {
             cvs: vec![],
-            message: format!("Failed to retrieve CVs: {}", e),
+            message: e.to_string(),
         },
     }
 }
 
-#[ic_cdk::update]
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_my_cvs_page(request: PageRequest) -> Result<Page<CV>, String> {
+    v2::v2_get_my_cvs_page(request).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 pub async fn update_cv(payload: UpdateCVPayload) -> CVResponse {
-    let caller = ic_cdk::caller();
-    let user_id = caller.to_string();
-    
-    if !UserStorage::exists(&user_id) {
-        return CVResponse {
-            cv: None,
-            message: "User not found".to_string(),
-        };
-    }
+    CVResponse::from_result(v2::v2_update_cv(payload), "CV updated successfully")
+}
 
-    match CVStorage::get_cv(&payload.id) {
-        Ok(mut cv) => {
-            if cv.user_id != user_id {
-                return CVResponse {
-                    cv: None,
-                    message: "Access denied".to_string(),
-                };
-            }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn begin_upload(payload: BeginUploadPayload) -> Result<UploadSession, String> {
+    v2::v2_begin_upload(payload).map_err(|e| e.to_string())
+}
 
-            cv.title = payload.title;
-            cv.content = payload.content;
-            cv.ai_analysis_status = CVAnalysisStatus::NotAnalyzed;
-            cv.ai_feedback = None;
-
-            match CVStorage::update_cv(cv.clone()) {
-                Ok(_) => {
-                    let cv_id = cv.id.clone();
-                    ic_cdk::spawn(async move {
-                        let _ = CVAnalyzer::analyze_cv(cv_id).await;
-                    });
-
-                    CVResponse {
-                        cv: Some(cv),
-                        message: "CV updated successfully".to_string(),
-                    }
-                }
-                Err(e) => CVResponse {
-                    cv: None,
-                    message: format!("Failed to update CV: {}", e),
-                },
-            }
-        }
-        Err(e) => CVResponse {
-            cv: None,
-            message: format!("Failed to retrieve CV: {}", e),
-        },
-    }
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn upload_chunk(payload: UploadChunkPayload) -> Result<UploadProgress, String> {
+    v2::v2_upload_chunk(payload).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn commit_upload(upload_id: u64) -> Result<BlobInfo, String> {
+    v2::v2_commit_upload(upload_id).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn abort_upload(upload_id: u64) -> Result<(), String> {
+    v2::v2_abort_upload(upload_id).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn list_my_files() -> Vec<BlobInfo> {
+    v2::v2_list_my_files().unwrap_or_default()
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_file_usage() -> BlobUsage {
+    v2::v2_get_file_usage().expect("file usage is always available")
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_file_chunk(blob_id: u64, index: u32) -> Result<Vec<u8>, String> {
+    v2::v2_get_file_chunk(blob_id, index).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_file_text(blob_id: u64) -> Result<ExtractedText, String> {
+    v2::v2_get_file_text(blob_id).map_err(|e| e.to_string())
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn delete_file(blob_id: u64) -> Result<(), String> {
+    v2::v2_delete_file(blob_id).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn attach_file_to_cv(cv_id: String, blob_id: Option<u64>) -> CVResponse {
+    CVResponse::from_result(v2::v2_attach_file_to_cv(cv_id, blob_id), "CV file updated successfully")
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn list_cv_versions(cv_id: String, request: PageRequest) -> Result<Page<CVVersionInfo>, String> {
+    v2::v2_list_cv_versions(cv_id, request).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_cv_version(cv_id: String, version: u32) -> Result<CVVersion, String> {
+    v2::v2_get_cv_version(cv_id, version).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn diff_cv_versions(cv_id: String, from_version: u32, to_version: u32) -> Result<CVDiff, String> {
+    v2::v2_diff_cv_versions(cv_id, from_version, to_version).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_cv_document(cv_id: String) -> Result<CvDocument, String> {
+    v2::v2_get_cv_document(cv_id).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn set_cv_summary(cv_id: String, summary: String) -> Result<CvDocument, String> {
+    v2::v2_set_cv_summary(cv_id, summary).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn add_cv_entry(cv_id: String, entry: CvEntry) -> Result<CvDocument, String> {
+    v2::v2_add_cv_entry(cv_id, entry).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn update_cv_entry(cv_id: String, entry_id: u32, entry: CvEntry) -> Result<CvDocument, String> {
+    v2::v2_update_cv_entry(cv_id, entry_id, entry).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn remove_cv_entry(cv_id: String, entry_id: u32) -> Result<CvDocument, String> {
+    v2::v2_remove_cv_entry(cv_id, entry_id).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub fn import_cv_document(cv_id: String, text: Option<String>) -> Result<CvDocument, String> {
+    v2::v2_import_cv_document(cv_id, text).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn revert_cv(cv_id: String, version: u32) -> CVResponse {
+    CVResponse::from_result(
+        v2::v2_revert_cv(cv_id, version),
+        format!("CV reverted to version {}", version),
+    )
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 pub async fn start_cv_chat(cv_id: String) -> ChatResponse {
-    let caller = ic_cdk::caller().to_string();
-    ChatService::start_chat(&caller, &cv_id).await
-}
-
-#[ic_cdk::update]
-#[candid_method(update)]
-pub async fn send_chat_message(
-    session_id: String, 
-    content: String
-) -> ChatResponse {
-    let caller = ic_cdk::caller().to_string();
-    println!("Caller ID: {}", caller);
-
-    let session = match ChatSessionStorage::get_session(&session_id) {
-        Ok(session) => {
-            println!("Session user_id: {}", session.user_id);
-            if session.user_id != caller {  // Remove .to_string()
-                return ChatResponse {
-                    message: None,
-                    error: Some("Access denied to this chat session".to_string()),
-                };
-            }
-            session
-        }
-        Err(_) => {
-            return ChatResponse {
-                message: None,
-                error: Some("Chat session not found".to_string()),
-            };
-        }
-    };
-
-    let message = ChatMessage {
-        id: format!("msg_{}_{}", session_id, time()),
-        content: content.clone(),
-        is_ai: false,
-        timestamp: time(),
-    };
-
-    if let Err(e) = ChatStorage::store_message(
-        &session.id,
-        message.content.clone(),
-        message.is_ai
-    ) {
-        return ChatResponse {
-            message: None,
-            error: Some(format!("Failed to store message: {}", e)),
-        };
-    }
-    
-    // Let ChatService handle the AI communication
-    let result = ChatService::send_message(&session_id, &caller, content).await;
-    result
+    v2::v2_start_cv_chat(cv_id).await.into()
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+pub async fn send_chat_message(session_id: String, content: String) -> ChatResponse {
+    v2::v2_send_chat_message(session_id, content).await.into()
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "is_signed_in")]
 #[candid_method(query)]
 pub fn get_chat_history(session_id: String) -> ChatHistoryResponse {
-    let caller = ic_cdk::caller().to_string();
-    ChatService::get_chat_history(&session_id, &caller)
+    v2::v2_get_chat_history(session_id).into()
 }
 
-#[ic_cdk::update]
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_chat_history_page(session_id: String, request: PageRequest) -> Result<Page<ChatMessage>, String> {
+    v2::v2_get_chat_history_page(session_id, request).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+pub fn get_my_chat_sessions_page(request: PageRequest) -> Result<Page<ChatSession>, String> {
+    v2::v2_get_my_chat_sessions_page(request).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "can_manage_ai_config")]
 #[candid_method(update)]
 fn set_openai_key(key: String) -> Result<(), String> {
-    let caller = ic_cdk::caller();
-    
-    let canister_id = ic_cdk::api::id();
-    
-    if caller.to_string() != "ftbln-b7mfk-fjq6u-dh3u3-7rylz-2vyi3-jqzhy-7phve-hswv4-u4fze-2qe" {
-        return Err("Unauthorized: Only controller can set API key".to_string());
-    }
-
-    OPENAI_API_KEY.with(|k| {
-        *k.borrow_mut() = key;
-    });
-    
-    Ok(())
+    v2::v2_set_openai_key(key).map_err(|e| e.to_string())
 }
 
 pub fn get_openai_key() -> Result<String, String> {
@@ -815,103 +688,93 @@ pub fn get_openai_key() -> Result<String, String> {
     })
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "can_wipe_data")]
 #[candid_method(update)]
 fn clear_all_storage() -> Result<String, String> {
-    let caller = ic_cdk::caller();
-    
-    if caller.to_string() != "ftbln-b7mfk-fjq6u-dh3u3-7rylz-2vyi3-jqzhy-7phve-hswv4-u4fze-2qe" {
-        return Err("Unauthorized: Only controller can clear storage".to_string());
-    }
-
-    USERS.with(|users| {
-        let mut users = users.borrow_mut();
-        let keys: Vec<_> = users.iter().map(|(k, _)| k).collect();
-        for key in keys {
-            users.remove(&key);
-        }
-    });
-
-    BANK_INFO.with(|bank_info| {
-        let mut bank_info = bank_info.borrow_mut();
-        let keys: Vec<_> = bank_info.iter().map(|(k, _)| k).collect();
-        for key in keys {
-            bank_info.remove(&key);
-        }
-    });
-
-    CV_STORAGE.with(|storage| {
-        let mut storage = storage.borrow_mut();
-        let keys: Vec<_> = storage.iter().map(|(k, _)| k).collect();
-        for key in keys {
-            storage.remove(&key);
-        }
-    });
-
-    Ok("All storage cleared successfully".to_string())
+    v2::v2_clear_all_storage()
+        .map(|()| "All storage cleared successfully".to_string())
+        .map_err(|e| e.to_string())
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "can_wipe_data")]
 #[candid_method(update)]
 fn clear_cv_storage() -> Result<String, String> {
-    let caller = ic_cdk::caller();
-    
-    if caller.to_string() != "ftbln-b7mfk-fjq6u-dh3u3-7rylz-2vyi3-jqzhy-7phve-hswv4-u4fze-2qe" {
-        return Err("Unauthorized: Only controller can clear storage".to_string());
-    }
-
-    CV_STORAGE.with(|storage| {
-        let mut storage = storage.borrow_mut();
-        let keys: Vec<_> = storage.iter().map(|(k, _)| k).collect();
-        for key in keys {
-            storage.remove(&key);
-        }
-    });
+    v2::v2_clear_cv_storage()
+        .map(|()| "CV storage cleared successfully".to_string())
+        .map_err(|e| e.to_string())
+}
 
-    Ok("CV storage cleared successfully".to_string())
+#[ic_cdk::update(guard = "is_admin")]
+#[candid_method(update)]
+fn rebuild_indexes() -> Result<IndexRebuildReport, String> {
+    v2::v2_rebuild_indexes().map_err(|e| e.to_string())
 }
 
-#[ic_cdk::update]
+#[ic_cdk::update(guard = "is_signed_in")]
 #[candid_method(update)]
 async fn login() -> Result<Session, String> {
-    let principal = ic_cdk::api::caller();
-    
-    if principal == Principal::anonymous() {
-        return Err("Anonymous principals cannot log in".to_string());
-    }
-
-    let session = AuthService::create_session(principal);
-    
-    if AuthService::get_user_id(&principal).is_none() {
-        let user_id = generate_unique_user_id(); 
-        let user = UserProfile::new(
-            user_id.clone(),
-            String::new(),
-            String::new(),
-            String::new(),
-            String::new(),
-            String::new(),
-        );
-        
-        match UserStorage::save_with_validation(user) {
-            Ok(_) => {
-                AuthService::associate_user_principal(principal, user_id);
-                Ok(session)
-            }
-            Err(e) => Err(format!("Failed to create user profile: {:?}", e))
-        }
-    } else {
-        Ok(session)
-    }
+    v2::v2_login().map_err(|e| e.to_string())
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "anyone")]
 #[candid_method(query)]
 fn is_logged_in() -> bool {
     let principal = ic_cdk::api::caller();
     AuthService::is_authenticated(&principal)
 }
 
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+async fn create_link_code() -> Result<LinkCode, String> {
+    v2::v2_create_link_code().await.map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+fn link_principal(code: String) -> Result<Vec<LinkedPrincipal>, String> {
+    v2::v2_link_principal(code).map_err(|e| e.to_string())
+}
 
+#[ic_cdk::query(guard = "is_signed_in")]
+#[candid_method(query)]
+fn list_my_principals() -> Vec<LinkedPrincipal> {
+    v2::v2_list_my_principals().unwrap_or_default()
+}
+
+#[ic_cdk::update(guard = "is_signed_in")]
+#[candid_method(update)]
+fn unlink_principal(principal: Principal) -> Result<Vec<LinkedPrincipal>, String> {
+    v2::v2_unlink_principal(principal).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "can_moderate_content")]
+#[candid_method(query)]
+fn find_duplicate_profiles() -> Result<Vec<DuplicateProfile>, String> {
+    v2::v2_find_duplicate_profiles().map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "can_moderate_content")]
+#[candid_method(update)]
+async fn merge_duplicate_profiles(canonical_id: String, duplicate_id: String) -> UserResponse {
+    v2::v2_merge_duplicate_profiles(canonical_id, duplicate_id).await.into()
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+#[candid_method(update)]
+fn add_admin(principal: Principal, permissions: Vec<AdminPermission>) -> Result<Admin, String> {
+    v2::v2_add_admin(principal, permissions).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::update(guard = "is_controller")]
+#[candid_method(update)]
+fn remove_admin(principal: Principal) -> Result<(), String> {
+    v2::v2_remove_admin(principal).map_err(|e| e.to_string())
+}
+
+#[ic_cdk::query(guard = "is_admin")]
+#[candid_method(query)]
+fn list_admins() -> Vec<Admin> {
+    v2::v2_list_admins().unwrap_or_default()
+}
 
-ic_cdk::export_candid!();
\ No newline at end of file
+ic_cdk::export_candid!();
@@ -0,0 +1,283 @@
+use candid::CandidType;
+use ic_stable_structures::{StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::auth::AuthSnapshot;
+use crate::models::types::{decode_record, encode_record, record_schema_version};
+use crate::storage::registry::{self, Memory};
+
+/// Heap-only state that has to survive an upgrade. It is written to stable
+/// memory in `pre_upgrade` and read back (and cleared) in `post_upgrade`.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct UpgradeState {
+    pub openai_api_key: String,
+    pub auth: AuthSnapshot,
+}
+
+impl UpgradeState {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for UpgradeState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            _ => Self::default(),
+        }
+    }
+}
+
+/// One step in the ordered list of state migrations. `run` returns a short
+/// summary for the canister log, or an error that aborts the upgrade.
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub run: fn() -> Result<String, String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub name: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct MigrationStatus {
+    pub current_version: u32,
+    pub latest_version: u32,
+    pub pending: Vec<MigrationInfo>,
+}
+
+/// Every migration, in the order it has to run. Versions must be strictly
+/// increasing; append new steps at the end and never reorder or remove them.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "versioned_record_encoding",
+        run: migrate_versioned_record_encoding,
+    },
+    Migration {
+        version: 2,
+        name: "build_user_indexes",
+        run: build_user_indexes,
+    },
+    Migration {
+        version: 3,
+        name: "chat_message_keys",
+        run: migrate_chat_message_keys,
+    },
+    Migration {
+        version: 4,
+        name: "cv_version_history",
+        run: backfill_cv_versions,
+    },
+    Migration {
+        version: 5,
+        name: "extract_blob_text",
+        run: extract_blob_text,
+    },
+    Migration {
+        version: 6,
+        name: "education_entries",
+        run: convert_education_entries,
+    },
+    Migration {
+        version: 7,
+        name: "profile_completion",
+        run: compute_profile_completion,
+    },
+    Migration {
+        version: 8,
+        name: "canonical_phone_numbers",
+        run: canonicalize_phone_numbers,
+    },
+    Migration {
+        version: 9,
+        name: "payout_methods",
+        run: import_bank_information,
+    },
+    Migration {
+        version: 10,
+        name: "truncated_cv_owners",
+        run: restore_truncated_cv_owners,
+    },
+    Migration {
+        version: 11,
+        name: "chat_session_keys",
+        run: migrate_chat_session_keys,
+    },
+];
+
+fn migrate_versioned_record_encoding() -> Result<String, String> {
+    let migrated = crate::storage::migrate_fixed_width_records();
+    Ok(format!("rewrote {} fixed-width records", migrated))
+}
+
+fn build_user_indexes() -> Result<String, String> {
+    let report = crate::storage::rebuild_user_indexes();
+    Ok(format!("{:?}", report))
+}
+
+fn migrate_chat_message_keys() -> Result<String, String> {
+    let (moved, left_behind) = crate::storage::migrate_chat_message_keys();
+    Ok(format!(
+        "moved {} chat messages, left {} without a resolvable session",
+        moved, left_behind
+    ))
+}
+
+fn backfill_cv_versions() -> Result<String, String> {
+    let recorded = crate::storage::backfill_cv_versions();
+    Ok(format!("recorded {} CV versions", recorded))
+}
+
+fn extract_blob_text() -> Result<String, String> {
+    let extracted = crate::storage::blob::BlobStorage::extract_missing_text();
+    Ok(format!("extracted text of {} files", extracted))
+}
+
+fn convert_education_entries() -> Result<String, String> {
+    let rewritten = crate::storage::EducationStorage::rewrite_all();
+    Ok(format!("rewrote {} education records", rewritten))
+}
+
+fn compute_profile_completion() -> Result<String, String> {
+    let users = crate::services::profile_completion::ProfileCompletion::refresh_all();
+    Ok(format!("computed profile completion of {} users", users))
+}
+
+fn canonicalize_phone_numbers() -> Result<String, String> {
+    let (rewritten, unparsed) = crate::storage::UserStorage::canonicalize_phone_numbers();
+    Ok(format!(
+        "rewrote {} phone numbers into E.164, left {} that do not parse",
+        rewritten, unparsed
+    ))
+}
+
+fn import_bank_information() -> Result<String, String> {
+    let (moved, left_behind) = crate::storage::PayoutStorage::import_bank_information();
+    if moved > 0 {
+        crate::services::profile_completion::ProfileCompletion::refresh_all();
+    }
+    Ok(format!(
+        "moved {} bank records into payout methods, left {} that payout methods refuse",
+        moved, left_behind
+    ))
+}
+
+fn restore_truncated_cv_owners() -> Result<String, String> {
+    let (restored, unresolved) = crate::storage::restore_truncated_cv_owners();
+    if restored > 0 {
+        crate::services::profile_completion::ProfileCompletion::refresh_all();
+    }
+    Ok(format!(
+        "restored the owner of {} CVs, left {} without a unique match",
+        restored, unresolved
+    ))
+}
+
+fn migrate_chat_session_keys() -> Result<String, String> {
+    let rekeyed = crate::storage::migrate_chat_session_keys();
+    Ok(format!("re-keyed {} chat sessions by the hash of their id", rekeyed))
+}
+
+thread_local! {
+    static STATE_VERSION: RefCell<StableCell<u32, Memory>> = RefCell::new(
+        registry::init_cell(registry::STATE_VERSION, 0)
+    );
+
+    static UPGRADE_STATE: RefCell<StableCell<UpgradeState, Memory>> = RefCell::new(
+        registry::init_cell(registry::UPGRADE_STATE, UpgradeState::default())
+    );
+}
+
+pub struct MigrationService;
+
+impl MigrationService {
+    pub fn current_version() -> u32 {
+        STATE_VERSION.with(|v| *v.borrow().get())
+    }
+
+    pub fn latest_version() -> u32 {
+        MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+    }
+
+    /// Marks a freshly installed canister as up to date: there is no old
+    /// data to migrate.
+    pub fn mark_current() {
+        Self::set_version(Self::latest_version());
+    }
+
+    /// Reports what `run_pending` would do without changing anything.
+    pub fn status() -> MigrationStatus {
+        let current_version = Self::current_version();
+        MigrationStatus {
+            current_version,
+            latest_version: Self::latest_version(),
+            pending: MIGRATIONS
+                .iter()
+                .filter(|m| m.version > current_version)
+                .map(|m| MigrationInfo {
+                    version: m.version,
+                    name: m.name.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Runs every migration newer than the stored state version, recording
+    /// the version after each step. A failing step traps, which rolls the
+    /// whole upgrade back.
+    pub fn run_pending() {
+        let current_version = Self::current_version();
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current_version) {
+            match (migration.run)() {
+                Ok(summary) => {
+                    ic_cdk::println!(
+                        "Migration {} ({}) done: {}",
+                        migration.version,
+                        migration.name,
+                        summary
+                    );
+                    Self::set_version(migration.version);
+                }
+                Err(e) => ic_cdk::trap(&format!(
+                    "Migration {} ({}) failed: {}",
+                    migration.version, migration.name, e
+                )),
+            }
+        }
+    }
+
+    pub fn save_upgrade_state(state: UpgradeState) {
+        UPGRADE_STATE.with(|cell| {
+            cell.borrow_mut()
+                .set(state)
+                .expect("Failed to save upgrade state");
+        });
+    }
+
+    /// Returns the state saved by the last `pre_upgrade` and clears it, so a
+    /// later upgrade never restores a stale snapshot.
+    pub fn take_upgrade_state() -> UpgradeState {
+        UPGRADE_STATE.with(|cell| {
+            cell.borrow_mut()
+                .set(UpgradeState::default())
+                .expect("Failed to clear upgrade state")
+        })
+    }
+
+    fn set_version(version: u32) {
+        STATE_VERSION.with(|v| {
+            v.borrow_mut()
+                .set(version)
+                .expect("Failed to store state version");
+        });
+    }
+}
@@ -0,0 +1,208 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use ic_stable_structures::{Storable, BoundedStorable};
+use super::types::{encode_record, decode_record, record_schema_version};
+
+/// Size of every chunk but the last. Values of a `StableBTreeMap` are
+/// reserved at their maximum size, so this is also what each stored chunk
+/// costs.
+pub const BLOB_CHUNK_SIZE: u32 = 256 * 1024;
+pub const MAX_BLOB_SIZE: u64 = 5 * 1024 * 1024;
+/// Committed blobs plus the declared size of pending uploads.
+pub const USER_BLOB_QUOTA: u64 = 20 * 1024 * 1024;
+pub const MAX_PENDING_UPLOADS: usize = 3;
+pub const UPLOAD_TTL_NANOS: u64 = 60 * 60 * 1_000_000_000;
+
+/// An upload in progress. Its chunks are stored under the upload id, which
+/// becomes the blob id on commit, so committing copies nothing.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableUpload {
+    pub user_id: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: [u8; 32],
+    pub chunks_received: u32,
+    pub expires_at: u64,
+}
+
+impl StableUpload {
+    pub const SCHEMA_VERSION: u8 = 1;
+
+    pub fn chunk_count(&self) -> u32 {
+        self.size.div_ceil(BLOB_CHUNK_SIZE as u64) as u32
+    }
+
+    /// Length chunk `index` must have.
+    pub fn chunk_len(&self, index: u32) -> Option<usize> {
+        let start = index as u64 * BLOB_CHUNK_SIZE as u64;
+        (start < self.size).then(|| (self.size - start).min(BLOB_CHUNK_SIZE as u64) as usize)
+    }
+}
+
+impl Storable for StableUpload {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported upload schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StableUpload {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableBlob {
+    pub user_id: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: [u8; 32],
+    pub created_at: u64,
+}
+
+impl StableBlob {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for StableBlob {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported blob schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StableBlob {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Raw chunk bytes, stored as-is.
+pub struct BlobChunk(pub Vec<u8>);
+
+impl Storable for BlobChunk {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for BlobChunk {
+    const MAX_SIZE: u32 = BLOB_CHUNK_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UploadSession {
+    pub upload_id: u64,
+    pub chunk_size: u32,
+    pub chunk_count: u32,
+    pub expires_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UploadProgress {
+    pub upload_id: u64,
+    pub chunks_received: u32,
+    pub chunk_count: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BlobInfo {
+    pub id: u64,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: u64,
+    /// Lowercase hex.
+    pub sha256: String,
+    pub chunk_count: u32,
+    pub created_at: u64,
+}
+
+impl BlobInfo {
+    pub fn new(id: u64, blob: StableBlob) -> Self {
+        Self {
+            id,
+            chunk_count: blob.size.div_ceil(BLOB_CHUNK_SIZE as u64) as u32,
+            sha256: blob.sha256.iter().map(|b| format!("{:02x}", b)).collect(),
+            file_name: blob.file_name,
+            content_type: blob.content_type,
+            size: blob.size,
+            created_at: blob.created_at,
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BlobUsage {
+    pub used_bytes: u64,
+    pub quota_bytes: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentFormat {
+    Pdf,
+    Docx,
+    PlainText,
+    Unsupported,
+}
+
+/// A section heading found in the extracted text. `line` is its zero-based
+/// line number in `ExtractedText::text`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OutlineEntry {
+    pub title: String,
+    pub line: u32,
+}
+
+/// Normalized text of an uploaded file, stored next to the blob. Extraction
+/// failures are recorded in `error` with empty text rather than failing the
+/// upload.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ExtractedText {
+    pub format: DocumentFormat,
+    pub text: String,
+    pub outline: Vec<OutlineEntry>,
+    pub truncated: bool,
+    pub error: Option<String>,
+    pub extracted_at: u64,
+}
+
+impl ExtractedText {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for ExtractedText {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported extracted text schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for ExtractedText {
+    const MAX_SIZE: u32 = 64 * 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
@@ -1,18 +1,15 @@
-use candid::{CandidType, Decode, Encode};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use ic_cdk::api::time;
 use ic_stable_structures::{Storable, BoundedStorable};
 use std::borrow::Cow;
-use super::user::{string_to_fixed, fixed_to_string};
-use std::collections::VecDeque;
+use super::types::{encode_record, decode_record, record_schema_version, legacy_field_to_string};
+use crate::types::errors::ApiError;
 
-pub type FixedString = [u8; 32];
-pub type FixedContent = [u8; 512]; 
-
-#[derive(Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableChatMessage {
-    pub id: FixedString,
-    pub content: FixedContent,
+    pub id: String,
+    pub content: String,
     pub is_ai: bool,
     pub timestamp: u64,
 }
@@ -34,18 +31,18 @@ pub struct ChatSession {
     pub last_interaction: u64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableChatSession {
-    pub id: FixedString,           // 32 bytes
-    pub user_id: FixedString,      // 32 bytes
-    pub cv_id: FixedString,        // 32 bytes
-    pub created_at: u64,           // 8 bytes
-    pub last_interaction: u64,     // 8 bytes
+    pub id: String,
+    pub user_id: String,
+    pub cv_id: String,
+    pub created_at: u64,
+    pub last_interaction: u64,
 }
 
 impl BoundedStorable for StableChatSession {
-    const MAX_SIZE: u32 = (32 * 3) + (8 * 2);  
-    const IS_FIXED_SIZE: bool = true;
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 #[derive(CandidType, Serialize, Deserialize, Debug)]
@@ -60,68 +57,64 @@ pub struct ChatHistoryResponse {
     pub error: Option<String>,
 }
 
-impl Storable for StableChatMessage {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.id);
-        bytes.extend_from_slice(&self.content);
-        bytes.push(if self.is_ai { 1 } else { 0 });
-        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
-        Cow::Owned(bytes)
+impl From<Result<ChatMessage, ApiError>> for ChatResponse {
+    fn from(result: Result<ChatMessage, ApiError>) -> Self {
+        match result {
+            Ok(message) => Self { message: Some(message), error: None },
+            Err(e) => Self { message: None, error: Some(e.to_string()) },
+        }
     }
+}
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let mut pos = 0;
-        
-        let mut id = [0u8; 32];
-        id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
-
-        let mut content = [0u8; 512];
-        content.copy_from_slice(&bytes[pos..pos + 512]);
-        pos += 512;
-
-        let is_ai = bytes[pos] == 1;
-        pos += 1;
+impl From<Result<Vec<ChatMessage>, ApiError>> for ChatHistoryResponse {
+    fn from(result: Result<Vec<ChatMessage>, ApiError>) -> Self {
+        match result {
+            Ok(messages) => Self { messages, error: None },
+            Err(e) => Self { messages: vec![], error: Some(e.to_string()) },
+        }
+    }
+}
 
-        let timestamp = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
+impl StableChatMessage {
+    pub const SCHEMA_VERSION: u8 = 1;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = 32 + 512 + 1 + 8;
 
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
         Self {
-            id,
-            content,
-            is_ai,
-            timestamp,
+            id: legacy_field_to_string(&bytes[0..32]),
+            content: legacy_field_to_string(&bytes[32..544]),
+            is_ai: bytes[544] == 1,
+            timestamp: u64::from_be_bytes(bytes[545..553].try_into().unwrap()),
         }
     }
 }
 
-impl BoundedStorable for StableChatMessage {
-    const MAX_SIZE: u32 = 32 + 512 + 1 + 8;
-    const IS_FIXED_SIZE: bool = true;
-}
+impl Storable for StableChatMessage {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
 
-fn string_to_fixed_content(s: &str) -> FixedContent {
-    let mut fixed = [0u8; 512];
-    let bytes = s.as_bytes();
-    let len = bytes.len().min(512);
-    fixed[..len].copy_from_slice(&bytes[..len]);
-    fixed
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(version) => panic!("Unsupported chat message schema version {}", version),
+            None => Self::from_legacy_bytes(&bytes),
+        }
+    }
 }
 
-fn fixed_content_to_string(fixed: &FixedContent) -> String {
-    String::from_utf8(
-        fixed.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
+impl BoundedStorable for StableChatMessage {
+    const MAX_SIZE: u32 = 16 * 1024;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 impl From<ChatMessage> for StableChatMessage {
     fn from(msg: ChatMessage) -> Self {
         Self {
-            id: string_to_fixed(&msg.id),
-            content: string_to_fixed_content(&msg.content),
+            id: msg.id,
+            content: msg.content,
             is_ai: msg.is_ai,
             timestamp: msg.timestamp,
         }
@@ -131,51 +124,41 @@ impl From<ChatMessage> for StableChatMessage {
 impl From<StableChatMessage> for ChatMessage {
     fn from(msg: StableChatMessage) -> Self {
         Self {
-            id: fixed_to_string(&msg.id),
-            content: fixed_content_to_string(&msg.content),
+            id: msg.id,
+            content: msg.content,
             is_ai: msg.is_ai,
             timestamp: msg.timestamp,
         }
     }
 }
 
-impl Storable for StableChatSession {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.id);
-        bytes.extend_from_slice(&self.user_id);
-        bytes.extend_from_slice(&self.cv_id);
-        bytes.extend_from_slice(&self.created_at.to_be_bytes());
-        bytes.extend_from_slice(&self.last_interaction.to_be_bytes());
-        Cow::Owned(bytes)
-    }
-
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let mut pos = 0;
-        
-        let mut id = [0u8; 32];
-        id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
-
-        let mut user_id = [0u8; 32];
-        user_id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
-
-        let mut cv_id = [0u8; 32];
-        cv_id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
+impl StableChatSession {
+    pub const SCHEMA_VERSION: u8 = 1;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = (32 * 3) + (8 * 2);
 
-        let created_at = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
-        pos += 8;
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
+        Self {
+            id: legacy_field_to_string(&bytes[0..32]),
+            user_id: legacy_field_to_string(&bytes[32..64]),
+            cv_id: legacy_field_to_string(&bytes[64..96]),
+            created_at: u64::from_be_bytes(bytes[96..104].try_into().unwrap()),
+            last_interaction: u64::from_be_bytes(bytes[104..112].try_into().unwrap()),
+        }
+    }
+}
 
-        let last_interaction = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
+impl Storable for StableChatSession {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
 
-        Self {
-            id,
-            user_id,
-            cv_id,
-            created_at,
-            last_interaction,
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(version) => panic!("Unsupported chat session schema version {}", version),
+            None => Self::from_legacy_bytes(&bytes),
         }
     }
 }
@@ -203,9 +186,9 @@ impl ChatSession {
 impl From<StableChatSession> for ChatSession {
     fn from(session: StableChatSession) -> Self {
         Self {
-            id: fixed_to_string(&session.id),
-            user_id: fixed_to_string(&session.user_id),
-            cv_id: fixed_to_string(&session.cv_id),
+            id: session.id,
+            user_id: session.user_id,
+            cv_id: session.cv_id,
             created_at: session.created_at,
             last_interaction: session.last_interaction,
         }
@@ -215,9 +198,9 @@ impl From<StableChatSession> for ChatSession {
 impl From<ChatSession> for StableChatSession {
     fn from(session: ChatSession) -> Self {
         Self {
-            id: string_to_fixed(&session.id),
-            user_id: string_to_fixed(&session.user_id),
-            cv_id: string_to_fixed(&session.cv_id),
+            id: session.id,
+            user_id: session.user_id,
+            cv_id: session.cv_id,
             created_at: session.created_at,
             last_interaction: session.last_interaction,
         }
@@ -1,24 +1,20 @@
-use candid::{CandidType, Decode, Encode};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use ic_cdk::api::time;
 use std::borrow::Cow;
+use ic_cdk::api::time;
 use ic_stable_structures::{Storable, BoundedStorable};
-use super::user::{string_to_fixed, fixed_to_string};
+use super::types::{encode_record, decode_record, record_schema_version, legacy_field_to_string};
 
-pub type StorageKey = [u8; 32];  
-pub type FixedString = [u8; 32];
-pub type FixedContent400 = [u8; 400]; 
-pub type FixedContent32 = [u8; 32];
-
-#[derive(Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableCV {
-    pub id: StorageKey,
-    pub user_id: StorageKey,
-    pub title: FixedString,
-    pub content: FixedContent400,
+    pub id: String,
+    pub user_id: String,
+    pub title: String,
+    pub content: String,
     pub version: u32,
     pub ai_analysis_status: u8,
-    pub ai_feedback: FixedContent32,
+    pub ai_feedback: Option<String>,
+    pub blob_id: Option<u64>,
 }
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct CV {
@@ -29,6 +25,8 @@ pub struct CV {
     pub version: u32,
     pub ai_analysis_status: CVAnalysisStatus,
     pub ai_feedback: Option<String>,
+    /// Uploaded file (PDF/DOCX) this CV was created from, if any.
+    pub blob_id: Option<u64>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -38,78 +36,62 @@ pub enum CVAnalysisStatus {
     Completed,
 }
 
-impl Storable for StableCV {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.id);
-        bytes.extend_from_slice(&self.user_id);
-        bytes.extend_from_slice(&self.title);
-        bytes.extend_from_slice(&self.content);
-        bytes.extend_from_slice(&self.version.to_be_bytes());
-        bytes.push(self.ai_analysis_status);
-        bytes.extend_from_slice(&self.ai_feedback);
-        Cow::Owned(bytes)
-    }
-
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let mut pos = 0;
-        
-        let mut id = [0u8; 32];
-        id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
+impl StableCV {
+    pub const SCHEMA_VERSION: u8 = 1;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = 32 * 3 + 400 + 4 + 1 + 32;
 
-        let mut user_id = [0u8; 32];
-        user_id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
+        let ai_feedback = legacy_field_to_string(&bytes[501..533]);
 
-        let mut title = [0u8; 32];
-        title.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
-
-        let mut content = [0u8; 400];
-        content.copy_from_slice(&bytes[pos..pos + 400]);
-        pos += 400;
-        
-        let version = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
-        pos += 4;
-
-        let ai_analysis_status = bytes[pos];
-        pos += 1;
+        Self {
+            id: legacy_field_to_string(&bytes[0..32]),
+            user_id: legacy_field_to_string(&bytes[32..64]),
+            title: legacy_field_to_string(&bytes[64..96]),
+            content: legacy_field_to_string(&bytes[96..496]),
+            version: u32::from_be_bytes(bytes[496..500].try_into().unwrap()),
+            ai_analysis_status: bytes[500],
+            ai_feedback: (!ai_feedback.is_empty()).then_some(ai_feedback),
+            blob_id: None,
+        }
+    }
+}
 
-        let mut ai_feedback = [0u8; 32];
-        ai_feedback.copy_from_slice(&bytes[pos..pos + 32]);
+impl Storable for StableCV {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
 
-        Self {
-            id,
-            user_id,
-            title,
-            content,
-            version,
-            ai_analysis_status,
-            ai_feedback,
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(version) => panic!("Unsupported CV schema version {}", version),
+            None => Self::from_legacy_bytes(&bytes),
         }
     }
 }
 
 impl BoundedStorable for StableCV {
-    const MAX_SIZE: u32 = 32 * 3 + 400 + 4 + 1 + 32;
-    const IS_FIXED_SIZE: bool = true;
+    const MAX_SIZE: u32 = 64 * 1024;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 impl From<CV> for StableCV {
     fn from(cv: CV) -> Self {
         Self {
-            id: string_to_fixed(&cv.id),
-            user_id: string_to_fixed(&cv.user_id),
-            title: string_to_fixed(&cv.title),
-            content: string_to_fixed_content_400(&cv.content),
+            id: cv.id,
+            user_id: cv.user_id,
+            title: cv.title,
+            content: cv.content,
             version: cv.version,
             ai_analysis_status: match cv.ai_analysis_status {
                 CVAnalysisStatus::NotAnalyzed => 0,
                 CVAnalysisStatus::InProgress => 1,
                 CVAnalysisStatus::Completed => 2,
             },
-            ai_feedback: string_to_fixed_content_32(&cv.ai_feedback.unwrap_or_default()),
+            ai_feedback: cv.ai_feedback,
+            blob_id: cv.blob_id,
         }
     }
 }
@@ -122,7 +104,6 @@ impl CV {
         title: String,
         content: String,
     ) -> Self {
-        let timestamp = time();
         Self {
             id,
             user_id,
@@ -131,6 +112,7 @@ impl CV {
             version: 1,
             ai_analysis_status: CVAnalysisStatus::NotAnalyzed,
             ai_feedback: None,
+            blob_id: None,
         }
     }
 }
@@ -138,51 +120,118 @@ impl CV {
 impl From<StableCV> for CV {
     fn from(cv: StableCV) -> Self {
         Self {
-            id: fixed_to_string(&cv.id),
-            user_id: fixed_to_string(&cv.user_id),
-            title: fixed_to_string(&cv.title),
-            content: fixed_content_to_string_400(&cv.content),
+            id: cv.id,
+            user_id: cv.user_id,
+            title: cv.title,
+            content: cv.content,
             version: cv.version,
             ai_analysis_status: match cv.ai_analysis_status {
                 0 => CVAnalysisStatus::NotAnalyzed,
                 1 => CVAnalysisStatus::InProgress,
                 _ => CVAnalysisStatus::Completed,
             },
-            ai_feedback: Some(fixed_content_to_string_32(&cv.ai_feedback)),
+            ai_feedback: cv.ai_feedback,
+            blob_id: cv.blob_id,
         }
     }
 }
 
-fn string_to_fixed_content_400(s: &str) -> FixedContent400 {
-    let mut fixed = [0u8; 400];
-    let bytes = s.as_bytes();
-    let len = bytes.len().min(400);
-    fixed[..len].copy_from_slice(&bytes[..len]);
-    fixed
+/// Analysis results recorded against the version they were computed for.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CVVersionAnalysis {
+    pub feedback: String,
+    pub analyzed_at: u64,
 }
 
-fn string_to_fixed_content_32(s: &str) -> FixedContent32 {
-    let mut fixed = [0u8; 32];
-    let bytes = s.as_bytes();
-    let len = bytes.len().min(32);
-    fixed[..len].copy_from_slice(&bytes[..len]);
-    fixed
+/// An immutable snapshot of a CV, written on every change to its title,
+/// content or attached file. Only `analysis` is filled in afterwards, once.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CVVersion {
+    pub cv_id: String,
+    pub version: u32,
+    pub title: String,
+    pub content: String,
+    pub blob_id: Option<u64>,
+    pub created_at: u64,
+    pub reverted_from: Option<u32>,
+    pub analysis: Option<CVVersionAnalysis>,
 }
 
-fn fixed_content_to_string_400(fixed: &FixedContent400) -> String {
-    String::from_utf8(
-        fixed.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
+impl CVVersion {
+    pub const SCHEMA_VERSION: u8 = 1;
+
+    pub fn of(cv: &CV, reverted_from: Option<u32>) -> Self {
+        Self {
+            cv_id: cv.id.clone(),
+            version: cv.version,
+            title: cv.title.clone(),
+            content: cv.content.clone(),
+            blob_id: cv.blob_id,
+            created_at: time(),
+            reverted_from,
+            analysis: None,
+        }
+    }
 }
 
-fn fixed_content_to_string_32(fixed: &FixedContent32) -> String {
-    String::from_utf8(
-        fixed.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
+impl Storable for CVVersion {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported CV version schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for CVVersion {
+    const MAX_SIZE: u32 = StableCV::MAX_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// A version without its content, for listings.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CVVersionInfo {
+    pub version: u32,
+    pub title: String,
+    pub created_at: u64,
+    pub reverted_from: Option<u32>,
+    pub analyzed: bool,
+}
+
+impl From<CVVersion> for CVVersionInfo {
+    fn from(version: CVVersion) -> Self {
+        Self {
+            version: version.version,
+            title: version.title,
+            created_at: version.created_at,
+            reverted_from: version.reverted_from,
+            analyzed: version.analysis.is_some(),
+        }
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal,
+    Added,
+    Removed,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CVDiff {
+    pub cv_id: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    pub title_changed: bool,
+    pub lines: Vec<DiffLine>,
 }
@@ -0,0 +1,109 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use ic_stable_structures::{Storable, BoundedStorable};
+use super::types::{encode_record, decode_record, record_schema_version};
+
+/// Dates are `YYYY` or `YYYY-MM`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WorkEntry {
+    pub id: u32,
+    pub employer: String,
+    pub role: String,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub is_current: bool,
+    pub bullets: Vec<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SkillEntry {
+    pub id: u32,
+    pub name: String,
+    pub category: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LanguageEntry {
+    pub id: u32,
+    pub name: String,
+    pub proficiency: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CertificationEntry {
+    pub id: u32,
+    pub name: String,
+    pub issuer: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProjectEntry {
+    pub id: u32,
+    pub name: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub bullets: Vec<String>,
+}
+
+/// One entry of any section, as sent to `add_cv_entry`/`update_cv_entry`.
+/// The `id` inside is ignored; ids are assigned by the canister.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum CvEntry {
+    Work(WorkEntry),
+    Skill(SkillEntry),
+    Language(LanguageEntry),
+    Certification(CertificationEntry),
+    Project(ProjectEntry),
+}
+
+/// The structured form of a CV. Entry ids are unique across all sections of
+/// one document.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CvDocument {
+    pub summary: Option<String>,
+    pub work: Vec<WorkEntry>,
+    pub skills: Vec<SkillEntry>,
+    pub languages: Vec<LanguageEntry>,
+    pub certifications: Vec<CertificationEntry>,
+    pub projects: Vec<ProjectEntry>,
+    pub next_entry_id: u32,
+    pub updated_at: u64,
+}
+
+impl CvDocument {
+    pub const SCHEMA_VERSION: u8 = 1;
+
+    pub fn is_empty(&self) -> bool {
+        self.summary.as_deref().is_none_or(|s| s.trim().is_empty())
+            && self.work.is_empty()
+            && self.skills.is_empty()
+            && self.languages.is_empty()
+            && self.certifications.is_empty()
+            && self.projects.is_empty()
+    }
+
+    pub fn allocate_id(&mut self) -> u32 {
+        self.next_entry_id += 1;
+        self.next_entry_id
+    }
+}
+
+impl Storable for CvDocument {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported CV document schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for CvDocument {
+    const MAX_SIZE: u32 = 64 * 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
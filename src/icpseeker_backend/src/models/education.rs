@@ -1,11 +1,11 @@
-use candid::{CandidType, Decode, Encode};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use ic_cdk::api::time;
 use ic_stable_structures::{Storable, BoundedStorable};
 use std::borrow::Cow;
 
-use super::user::FixedString;
-use super::user::{string_to_fixed, fixed_to_string};
+use super::types::{encode_record, decode_record, record_schema_version, legacy_field_to_string};
+use crate::validation::{describe, Validate};
 
 #[derive(CandidType, Serialize, Deserialize, Debug, Clone)]
 pub enum EducationLevel {
@@ -70,151 +70,261 @@ impl From<u8> for EducationStatus {
     }
 }
 
+/// The scale a grade was given on.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum GradeScale {
+    /// 0–4, as the Indonesian IPK and the US GPA.
+    FourPoint,
+    FivePoint,
+    TenPoint,
+    /// 0–100 scores, as on Indonesian transcripts.
+    Hundred,
+    /// UK honours classes as 1 (First), 2 (Upper Second), 3 (Lower Second)
+    /// or 4 (Third).
+    UkClassification,
+}
+
+impl GradeScale {
+    /// Lowest and highest value a grade on this scale can take.
+    pub fn bounds(self) -> (f32, f32) {
+        match self {
+            GradeScale::FourPoint => (0.0, 4.0),
+            GradeScale::FivePoint => (0.0, 5.0),
+            GradeScale::TenPoint => (0.0, 10.0),
+            GradeScale::Hundred => (0.0, 100.0),
+            GradeScale::UkClassification => (1.0, 4.0),
+        }
+    }
+
+    pub fn validate(self, value: f32) -> Result<(), String> {
+        let (min, max) = self.bounds();
+        if !value.is_finite() || value < min || value > max {
+            return Err(format!("Grade must be between {} and {} on the {:?} scale", min, max, self));
+        }
+        if self == GradeScale::UkClassification && value.fract() != 0.0 {
+            return Err("UK classifications are given as 1, 2, 3 or 4".to_string());
+        }
+        Ok(())
+    }
+
+    /// `value` on a 0–1 scale where 1 is the best grade the scale allows.
+    pub fn normalize(self, value: f32) -> f32 {
+        match self {
+            GradeScale::UkClassification => match value.round() as u32 {
+                1 => 1.0,
+                2 => 0.75,
+                3 => 0.5,
+                _ => 0.25,
+            },
+            _ => {
+                let (min, max) = self.bounds();
+                ((value - min) / (max - min)).clamp(0.0, 1.0)
+            }
+        }
+    }
+
+    /// Best guess at the scale of a grade stored before scales were recorded.
+    pub fn infer(value: f32) -> Self {
+        if value <= 4.0 {
+            GradeScale::FourPoint
+        } else if value <= 5.0 {
+            GradeScale::FivePoint
+        } else if value <= 10.0 {
+            GradeScale::TenPoint
+        } else {
+            GradeScale::Hundred
+        }
+    }
+}
+
+/// A university grade with its scale and its 0–1 normalized value.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NormalizedGrade {
+    pub entry_id: u32,
+    pub university_name: String,
+    pub value: f32,
+    pub scale: GradeScale,
+    pub normalized: f32,
+}
+
+/// Most entries one education record may hold.
+pub const MAX_EDUCATION_ENTRIES: usize = 20;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum EducationDetails {
+    HighSchool(HighSchoolEducation),
+    University(UniversityEducation),
+}
+
+/// One school or university of a user's education history. Ids are unique
+/// within a record and never reused.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct EducationEntry {
+    pub id: u32,
+    pub details: EducationDetails,
+}
+
+/// A user's education history: entries in the order the user arranged them.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct EducationRecord {
     pub id: String,
     pub user_id: String,
-    pub school_name: String,
-    pub track: String,
-    pub university_name: String,
-    pub major: String,
-    pub city: String,
-    pub country: String,
-    pub education_level: EducationLevel,
-    pub status: EducationStatus,
-    pub start_year: u32,
-    pub end_year: Option<u32>,
-    pub gpa: Option<u32>,
+    pub entries: Vec<EducationEntry>,
+    pub next_entry_id: u32,
     pub created_at: u64,
     pub updated_at: u64,
-    pub universities: Vec<UniversityEducation>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableEducationRecord {
-    pub id: FixedString,
-    pub user_id: FixedString,
-    pub school_name: FixedString,
-    pub track: FixedString,
-    pub university_name: FixedString,
-    pub major: FixedString,
-    pub city: FixedString,
-    pub country: FixedString,
-    pub education_level: u8,
-    pub status: u8,
-    pub start_year: u32,
-    pub end_year: Option<u32>,
-    pub gpa: Option<u32>,
+    pub id: String,
+    pub user_id: String,
+    pub entries: Vec<EducationEntry>,
+    pub next_entry_id: u32,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
-impl Storable for StableEducationRecord {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.id);
-        bytes.extend_from_slice(&self.user_id);
-        bytes.extend_from_slice(&self.school_name);
-        bytes.extend_from_slice(&self.track);
-        bytes.extend_from_slice(&self.university_name);
-        bytes.extend_from_slice(&self.major);
-        bytes.extend_from_slice(&self.city);
-        bytes.extend_from_slice(&self.country);
-        bytes.push(self.education_level);
-        bytes.push(self.status);
-        bytes.extend_from_slice(&self.start_year.to_be_bytes());
-        
-        if let Some(end_year) = self.end_year {
-            bytes.push(1);
-            bytes.extend_from_slice(&end_year.to_be_bytes());
-        } else {
-            bytes.push(0);
-            bytes.extend_from_slice(&[0u8; 4]);
-        }
-        
-        if let Some(gpa) = self.gpa {
-            bytes.push(1);
-            bytes.extend_from_slice(&gpa.to_be_bytes());
-        } else {
-            bytes.push(0);
-            bytes.extend_from_slice(&[0u8; 4]);
-        }
+/// The single flattened record stored before schema version 2, where every
+/// university overwrote the fields of the one before it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+struct StableEducationRecordV1 {
+    id: String,
+    user_id: String,
+    school_name: String,
+    track: String,
+    university_name: String,
+    major: String,
+    city: String,
+    country: String,
+    education_level: u8,
+    status: u8,
+    start_year: u32,
+    end_year: Option<u32>,
+    gpa: Option<u32>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+impl StableEducationRecordV1 {
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
+        let field = |index: usize| legacy_field_to_string(&bytes[index * 32..(index + 1) * 32]);
+        let optional_u32 = |start: usize| {
+            (bytes[start] == 1)
+                .then(|| u32::from_be_bytes(bytes[start + 1..start + 5].try_into().unwrap()))
+        };
 
-        bytes.extend_from_slice(&self.created_at.to_be_bytes());
-        bytes.extend_from_slice(&self.updated_at.to_be_bytes());
-        
-        Cow::Owned(bytes)
+        Self {
+            id: field(0),
+            user_id: field(1),
+            school_name: field(2),
+            track: field(3),
+            university_name: field(4),
+            major: field(5),
+            city: field(6),
+            country: field(7),
+            education_level: bytes[256],
+            status: bytes[257],
+            start_year: u32::from_be_bytes(bytes[258..262].try_into().unwrap()),
+            end_year: optional_u32(262),
+            gpa: optional_u32(267),
+            created_at: u64::from_be_bytes(bytes[272..280].try_into().unwrap()),
+            updated_at: u64::from_be_bytes(bytes[280..288].try_into().unwrap()),
+        }
     }
+}
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let mut pos = 0;
-        let mut next_fixed_str = || {
-            let mut arr = [0u8; 32];
-            arr.copy_from_slice(&bytes[pos..pos + 32]);
-            pos += 32;
-            arr
-        };
+impl From<StableEducationRecordV1> for StableEducationRecord {
+    /// Splits the flattened record back into entries. Location, years and
+    /// status belong to the last university when there was one, so the high
+    /// school keeps only its name, track and location.
+    fn from(record: StableEducationRecordV1) -> Self {
+        let has_university = !record.university_name.is_empty();
+        let mut entries = Vec::new();
 
-        let id = next_fixed_str();
-        let user_id = next_fixed_str();
-        let school_name = next_fixed_str();
-        let track = next_fixed_str();
-        let university_name = next_fixed_str();
-        let major = next_fixed_str();
-        let city = next_fixed_str();
-        let country = next_fixed_str();
-        
-        let education_level = bytes[pos];
-        pos += 1;
-        let status = bytes[pos];
-        pos += 1;
-        
-        let start_year = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
-        pos += 4;
-
-        let end_year = if bytes[pos] == 1 {
-            pos += 1;
-            Some(u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()))
-        } else {
-            pos += 5;
-            None
-        };
-        
-        let gpa = if bytes[pos] == 1 {
-            pos += 1;
-            Some(u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()))
-        } else {
-            pos += 5;
-            None
-        };
+        if !record.school_name.is_empty() {
+            let high_school = if has_university {
+                HighSchoolEducation::new(
+                    record.school_name,
+                    record.track,
+                    record.city.clone(),
+                    record.country.clone(),
+                    0,
+                    None,
+                    EducationStatus::Completed,
+                )
+            } else {
+                HighSchoolEducation::new(
+                    record.school_name,
+                    record.track,
+                    record.city.clone(),
+                    record.country.clone(),
+                    record.start_year,
+                    record.end_year,
+                    record.status.into(),
+                )
+            };
+            entries.push(EducationEntry {
+                id: entries.len() as u32 + 1,
+                details: EducationDetails::HighSchool(high_school),
+            });
+        }
 
-        let created_at = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
-        pos += 8;
-        let updated_at = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
+        if has_university {
+            let university = UniversityEducation::new(
+                record.university_name,
+                record.education_level.into(),
+                record.major,
+                record.city,
+                record.country,
+                record.start_year,
+                record.end_year,
+                record.gpa.map(|gpa| gpa as f32 / 100.0),
+                record.gpa.map(|gpa| GradeScale::infer(gpa as f32 / 100.0)),
+                record.status.into(),
+            );
+            entries.push(EducationEntry {
+                id: entries.len() as u32 + 1,
+                details: EducationDetails::University(university),
+            });
+        }
 
         Self {
-            id,
-            user_id,
-            school_name,
-            track,
-            university_name,
-            major,
-            city,
-            country,
-            education_level,
-            status,
-            start_year,
-            end_year,
-            gpa,
-            created_at,
-            updated_at,
+            id: record.id,
+            user_id: record.user_id,
+            next_entry_id: entries.len() as u32,
+            entries,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+impl StableEducationRecord {
+    pub const SCHEMA_VERSION: u8 = 2;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = (32 * 8) + 2 + 4 + 5 + 5 + 16;
+}
+
+impl Storable for StableEducationRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(1) => decode_record::<StableEducationRecordV1>(&bytes).into(),
+            Some(version) => panic!("Unsupported education record schema version {}", version),
+            None => StableEducationRecordV1::from_legacy_bytes(&bytes).into(),
         }
     }
 }
 
 impl BoundedStorable for StableEducationRecord {
-    const MAX_SIZE: u32 = (32 * 8) + 2 + 4 + 5 + 5 + 16; 
-    const IS_FIXED_SIZE: bool = true;
+    const MAX_SIZE: u32 = 8 * 1024;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 impl EducationRecord {  
@@ -223,74 +333,139 @@ impl EducationRecord {
         Self {
             id,
             user_id,
-            school_name: String::new(),
-            track: String::new(),
-            university_name: String::new(),
-            major: String::new(),
-            city: String::new(),
-            country: String::new(),
-            education_level: EducationLevel::HighSchool,
-            status: EducationStatus::InProgress,
-            start_year: 0,
-            end_year: None,
-            gpa: None,
+            entries: Vec::new(),
+            next_entry_id: 0,
             created_at: timestamp,
             updated_at: timestamp,
-            universities: Vec::new(),
         }
     }
 
-    pub fn add_high_school(&mut self, high_school: HighSchoolEducation) {
-        self.school_name = high_school.school_name;
-        self.track = high_school.track;
-        self.city = high_school.city;
-        self.country = high_school.country;
-        self.education_level = EducationLevel::HighSchool;
-        self.status = high_school.status;
-        self.start_year = high_school.start_year;
-        self.end_year = high_school.end_year;
-        self.gpa = None;
+    pub fn high_school(&self) -> Option<&HighSchoolEducation> {
+        self.entries.iter().find_map(|entry| match &entry.details {
+            EducationDetails::HighSchool(high_school) => Some(high_school),
+            EducationDetails::University(_) => None,
+        })
+    }
+
+    pub fn universities(&self) -> impl Iterator<Item = &UniversityEducation> {
+        self.entries.iter().filter_map(|entry| match &entry.details {
+            EducationDetails::University(university) => Some(university),
+            EducationDetails::HighSchool(_) => None,
+        })
+    }
+
+    pub fn normalized_grades(&self) -> Vec<NormalizedGrade> {
+        self.entries
+            .iter()
+            .filter_map(|entry| match &entry.details {
+                EducationDetails::University(university) => Some((entry.id, university)),
+                EducationDetails::HighSchool(_) => None,
+            })
+            .filter_map(|(entry_id, university)| {
+                Some(NormalizedGrade {
+                    entry_id,
+                    university_name: university.university_name.clone(),
+                    value: university.gpa?,
+                    scale: university.grade_scale()?,
+                    normalized: university.normalized_gpa()?,
+                })
+            })
+            .collect()
+    }
+
+    /// The best normalized grade over all universities.
+    pub fn best_normalized_grade(&self) -> Option<f32> {
+        self.universities().filter_map(UniversityEducation::normalized_gpa).reduce(f32::max)
+    }
+
+    /// Appends an entry and returns its id.
+    pub fn add_entry(&mut self, details: EducationDetails) -> Result<u32, String> {
+        validate_details(&details)?;
+        if self.entries.len() >= MAX_EDUCATION_ENTRIES {
+            return Err(format!("At most {} education entries are allowed", MAX_EDUCATION_ENTRIES));
+        }
+        self.next_entry_id += 1;
+        let id = self.next_entry_id;
+        self.entries.push(EducationEntry { id, details });
+        self.updated_at = time();
+        Ok(id)
+    }
+
+    pub fn update_entry(&mut self, id: u32, details: EducationDetails) -> Result<(), String> {
+        validate_details(&details)?;
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| format!("Education entry {} not found", id))?;
+        entry.details = details;
+        self.updated_at = time();
+        Ok(())
+    }
+
+    pub fn remove_entry(&mut self, id: u32) -> Result<(), String> {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.len() == before {
+            return Err(format!("Education entry {} not found", id));
+        }
         self.updated_at = time();
+        Ok(())
     }
 
-    pub fn add_university(&mut self, university: UniversityEducation) {
-        self.university_name = university.university_name.clone();
-        self.major = university.major.clone();
-        self.city = university.city.clone();
-        self.country = university.country.clone();
-        self.education_level = university.level.clone();
-        self.status = university.status.clone();
-        self.start_year = university.start_year;
-        self.end_year = university.end_year;
-        self.gpa = university.gpa.map(|gpa| (gpa * 100.0) as u32);
-        self.universities.push(university);
+    /// Puts the entries in the order of `ids`, which must name every entry
+    /// exactly once.
+    pub fn reorder_entries(&mut self, ids: &[u32]) -> Result<(), String> {
+        let mut sorted = ids.to_vec();
+        sorted.sort_unstable();
+        let mut current: Vec<u32> = self.entries.iter().map(|entry| entry.id).collect();
+        current.sort_unstable();
+        if sorted != current {
+            return Err("Order must list every education entry exactly once".to_string());
+        }
+        self.entries.sort_by_key(|entry| ids.iter().position(|id| *id == entry.id));
         self.updated_at = time();
+        Ok(())
+    }
+
+    /// Replaces the high school entry in place, or adds one.
+    pub fn set_high_school(&mut self, high_school: HighSchoolEducation) -> Result<(), String> {
+        let existing = self
+            .entries
+            .iter()
+            .find(|entry| matches!(entry.details, EducationDetails::HighSchool(_)))
+            .map(|entry| entry.id);
+        let details = EducationDetails::HighSchool(high_school);
+        match existing {
+            Some(id) => self.update_entry(id, details),
+            None => self.add_entry(details).map(|_| ()),
+        }
     }
 
-    pub fn clear_universities(&mut self) {
-        self.universities.clear();
+    /// Replaces every university entry with `universities`.
+    pub fn set_universities(&mut self, universities: Vec<UniversityEducation>) -> Result<(), String> {
+        self.entries.retain(|entry| !matches!(entry.details, EducationDetails::University(_)));
+        for university in universities {
+            self.add_entry(EducationDetails::University(university))?;
+        }
+        self.updated_at = time();
+        Ok(())
     }
 }
 
+fn validate_details(details: &EducationDetails) -> Result<(), String> {
+    details.validate().map_err(|errors| describe(&errors))
+}
+
 impl From<StableEducationRecord> for EducationRecord {
     fn from(record: StableEducationRecord) -> Self {
         Self {
-            id: fixed_to_string(&record.id),
-            user_id: fixed_to_string(&record.user_id),
-            school_name: fixed_to_string(&record.school_name),
-            track: fixed_to_string(&record.track),
-            university_name: fixed_to_string(&record.university_name),
-            major: fixed_to_string(&record.major),
-            city: fixed_to_string(&record.city),
-            country: fixed_to_string(&record.country),
-            education_level: record.education_level.into(),
-            status: record.status.into(),
-            start_year: record.start_year,
-            end_year: record.end_year,
-            gpa: record.gpa,
+            id: record.id,
+            user_id: record.user_id,
+            entries: record.entries,
+            next_entry_id: record.next_entry_id,
             created_at: record.created_at,
             updated_at: record.updated_at,
-            universities: Vec::new(), // Add this field
         }
     }
 }
@@ -298,19 +473,10 @@ impl From<StableEducationRecord> for EducationRecord {
 impl From<EducationRecord> for StableEducationRecord {
     fn from(record: EducationRecord) -> Self {
         Self {
-            id: string_to_fixed(&record.id),
-            user_id: string_to_fixed(&record.user_id),
-            school_name: string_to_fixed(&record.school_name),
-            track: string_to_fixed(&record.track),
-            university_name: string_to_fixed(&record.university_name),
-            major: string_to_fixed(&record.major),
-            city: string_to_fixed(&record.city),
-            country: string_to_fixed(&record.country),
-            education_level: record.education_level.into(),
-            status: record.status.into(),
-            start_year: record.start_year,
-            end_year: record.end_year,
-            gpa: record.gpa,
+            id: record.id,
+            user_id: record.user_id,
+            entries: record.entries,
+            next_entry_id: record.next_entry_id,
             created_at: record.created_at,
             updated_at: record.updated_at,
         }
@@ -338,6 +504,9 @@ pub struct UniversityEducation {
     pub start_year: u32,
     pub end_year: Option<u32>,
     pub gpa: Option<f32>,
+    /// Scale of `gpa`; `None` only on grades stored before scales were
+    /// recorded.
+    pub gpa_scale: Option<GradeScale>,
     pub status: EducationStatus,
 }
 
@@ -373,6 +542,7 @@ impl UniversityEducation {
         start_year: u32,
         end_year: Option<u32>,
         gpa: Option<f32>,
+        gpa_scale: Option<GradeScale>,
         status: EducationStatus,
     ) -> Self {
         Self {
@@ -384,7 +554,127 @@ impl UniversityEducation {
             start_year,
             end_year,
             gpa,
+            gpa_scale,
             status,
         }
     }
+
+    /// Scale of the grade, inferred from its value when none was stored.
+    pub fn grade_scale(&self) -> Option<GradeScale> {
+        self.gpa.map(|gpa| self.gpa_scale.unwrap_or_else(|| GradeScale::infer(gpa)))
+    }
+
+    pub fn normalized_gpa(&self) -> Option<f32> {
+        Some(self.grade_scale()?.normalize(self.gpa?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    fn university(gpa: Option<f32>, gpa_scale: Option<GradeScale>) -> UniversityEducation {
+        UniversityEducation::new(
+            "Universitas Indonesia".to_string(),
+            EducationLevel::Bachelor,
+            "Computer Science".to_string(),
+            "Depok".to_string(),
+            "Indonesia".to_string(),
+            2016,
+            Some(2020),
+            gpa,
+            gpa_scale,
+            EducationStatus::Completed,
+        )
+    }
+
+    #[test]
+    fn normalizes_each_scale() {
+        assert_close(GradeScale::FourPoint.normalize(3.6), 0.9);
+        assert_close(GradeScale::FourPoint.normalize(4.0), 1.0);
+        assert_close(GradeScale::FivePoint.normalize(4.0), 0.8);
+        assert_close(GradeScale::TenPoint.normalize(7.5), 0.75);
+        assert_close(GradeScale::Hundred.normalize(85.0), 0.85);
+        assert_close(GradeScale::Hundred.normalize(0.0), 0.0);
+    }
+
+    #[test]
+    fn normalizes_uk_classifications() {
+        assert_close(GradeScale::UkClassification.normalize(1.0), 1.0);
+        assert_close(GradeScale::UkClassification.normalize(2.0), 0.75);
+        assert_close(GradeScale::UkClassification.normalize(3.0), 0.5);
+        assert_close(GradeScale::UkClassification.normalize(4.0), 0.25);
+    }
+
+    #[test]
+    fn accepts_grades_within_bounds() {
+        for (scale, value) in [
+            (GradeScale::FourPoint, 0.0),
+            (GradeScale::FourPoint, 3.6),
+            (GradeScale::FourPoint, 4.0),
+            (GradeScale::FivePoint, 5.0),
+            (GradeScale::TenPoint, 10.0),
+            (GradeScale::Hundred, 85.0),
+            (GradeScale::UkClassification, 1.0),
+            (GradeScale::UkClassification, 4.0),
+        ] {
+            assert_eq!(scale.validate(value), Ok(()), "{} on {:?}", value, scale);
+        }
+    }
+
+    #[test]
+    fn rejects_grades_outside_bounds() {
+        for (scale, value) in [
+            (GradeScale::FourPoint, 4.01),
+            (GradeScale::FourPoint, -0.1),
+            (GradeScale::FivePoint, 5.5),
+            (GradeScale::TenPoint, 10.5),
+            (GradeScale::Hundred, 100.5),
+            (GradeScale::Hundred, f32::NAN),
+            (GradeScale::FourPoint, f32::INFINITY),
+            (GradeScale::UkClassification, 0.0),
+            (GradeScale::UkClassification, 5.0),
+            (GradeScale::UkClassification, 2.5),
+        ] {
+            assert!(scale.validate(value).is_err(), "{} on {:?}", value, scale);
+        }
+    }
+
+    #[test]
+    fn infers_the_scale_of_unscaled_grades() {
+        assert_eq!(GradeScale::infer(3.6), GradeScale::FourPoint);
+        assert_eq!(GradeScale::infer(4.5), GradeScale::FivePoint);
+        assert_eq!(GradeScale::infer(8.0), GradeScale::TenPoint);
+        assert_eq!(GradeScale::infer(85.0), GradeScale::Hundred);
+
+        assert_close(university(Some(85.0), None).normalized_gpa().unwrap(), 0.85);
+        assert_close(university(Some(3.6), None).normalized_gpa().unwrap(), 0.9);
+        assert_eq!(university(None, Some(GradeScale::FourPoint)).normalized_gpa(), None);
+    }
+
+    #[test]
+    fn picks_the_best_normalized_grade() {
+        let entry = |id, details| EducationEntry { id, details };
+        let record = EducationRecord {
+            id: "edu".to_string(),
+            user_id: "user".to_string(),
+            entries: vec![
+                entry(1, EducationDetails::University(university(Some(3.2), Some(GradeScale::FourPoint)))),
+                entry(2, EducationDetails::University(university(Some(85.0), Some(GradeScale::Hundred)))),
+                entry(3, EducationDetails::University(university(Some(2.0), Some(GradeScale::UkClassification)))),
+                entry(4, EducationDetails::University(university(None, None))),
+            ],
+            next_entry_id: 4,
+            created_at: 0,
+            updated_at: 0,
+        };
+        assert_close(record.best_normalized_grade().unwrap(), 0.85);
+
+        let empty = EducationRecord { entries: Vec::new(), ..record };
+        assert_eq!(empty.best_normalized_grade(), None);
+    }
 }
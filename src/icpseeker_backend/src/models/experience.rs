@@ -0,0 +1,302 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use ic_stable_structures::{Storable, BoundedStorable};
+use super::types::{encode_record, decode_record, record_schema_version};
+
+pub const MAX_WORK_EXPERIENCES: usize = 50;
+const MAX_TEXT_CHARS: usize = 100;
+const MAX_DESCRIPTION_CHARS: usize = 2000;
+
+const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct YearMonth {
+    pub year: u32,
+    pub month: u32,
+}
+
+impl YearMonth {
+    /// The UTC month of a timestamp in nanoseconds since the epoch.
+    pub fn from_timestamp(nanos: u64) -> Self {
+        // Days to civil date, after Howard Hinnant's `civil_from_days`.
+        let days = (nanos / NANOS_PER_DAY) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Self { year: year as u32, month: month as u32 }
+    }
+
+    fn index(self) -> u32 {
+        self.year * 12 + (self.month - 1)
+    }
+
+    fn validate(self, field: &str) -> Result<(), String> {
+        if !(1950..=2100).contains(&self.year) || !(1..=12).contains(&self.month) {
+            return Err(format!("{} must be a month between 1950 and 2100", field));
+        }
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum EmploymentType {
+    FullTime,
+    PartTime,
+    Contract,
+    Internship,
+    Freelance,
+    Other,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WorkExperience {
+    pub id: u32,
+    pub user_id: String,
+    pub company: String,
+    pub title: String,
+    pub employment_type: EmploymentType,
+    pub location: Option<String>,
+    pub start: YearMonth,
+    /// `None` exactly when `is_current`.
+    pub end: Option<YearMonth>,
+    pub is_current: bool,
+    pub description: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl WorkExperience {
+    pub const SCHEMA_VERSION: u8 = 1;
+
+    /// Trims text fields and checks them and the date range. `now` is the
+    /// current month; roles cannot start after it.
+    pub fn validate(&mut self, now: YearMonth) -> Result<(), String> {
+        self.company = self.company.trim().to_string();
+        self.title = self.title.trim().to_string();
+        self.location = self.location.take().map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        self.description = self.description.take().map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+
+        for (value, field) in [(&self.company, "Company"), (&self.title, "Title")] {
+            if value.is_empty() {
+                return Err(format!("{} is required", field));
+            }
+            if value.chars().count() > MAX_TEXT_CHARS {
+                return Err(format!("{} must be at most {} characters", field, MAX_TEXT_CHARS));
+            }
+        }
+        if self.location.as_ref().is_some_and(|l| l.chars().count() > MAX_TEXT_CHARS) {
+            return Err(format!("Location must be at most {} characters", MAX_TEXT_CHARS));
+        }
+        if self.description.as_ref().is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_CHARS) {
+            return Err(format!("Description must be at most {} characters", MAX_DESCRIPTION_CHARS));
+        }
+
+        self.start.validate("Start date")?;
+        if self.start > now {
+            return Err("Start date is in the future".to_string());
+        }
+        match (self.is_current, self.end) {
+            (true, Some(_)) => Err("A current role has no end date".to_string()),
+            (false, None) => Err("End date is required unless the role is current".to_string()),
+            (false, Some(end)) => {
+                end.validate("End date")?;
+                if end < self.start {
+                    return Err("End date is before start date".to_string());
+                }
+                Ok(())
+            }
+            (true, None) => Ok(()),
+        }
+    }
+
+    /// Months covered as a half-open range of month indices, with current
+    /// roles running through `now`.
+    fn months(&self, now: YearMonth) -> (u32, u32) {
+        let end = self.end.unwrap_or(now).max(self.start);
+        (self.start.index(), end.index() + 1)
+    }
+}
+
+impl Storable for WorkExperience {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported work experience schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for WorkExperience {
+    const MAX_SIZE: u32 = 4 * 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// Two roles whose date ranges share `months` months.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ExperienceOverlap {
+    pub first_id: u32,
+    pub second_id: u32,
+    pub months: u32,
+}
+
+/// A user's roles, most recent first, with overlapping roles flagged and
+/// the total experience counting overlapping months once.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct WorkHistory {
+    pub experiences: Vec<WorkExperience>,
+    pub overlaps: Vec<ExperienceOverlap>,
+    pub total_months: u32,
+    pub total_years: f32,
+}
+
+impl WorkHistory {
+    pub fn new(mut experiences: Vec<WorkExperience>, now: YearMonth) -> Self {
+        experiences.sort_by(|a, b| {
+            (b.is_current, b.end, b.start).cmp(&(a.is_current, a.end, a.start))
+        });
+
+        let mut overlaps = Vec::new();
+        for (i, first) in experiences.iter().enumerate() {
+            let (first_start, first_end) = first.months(now);
+            for second in &experiences[i + 1..] {
+                let (second_start, second_end) = second.months(now);
+                let shared = first_end.min(second_end).saturating_sub(first_start.max(second_start));
+                if shared > 0 {
+                    overlaps.push(ExperienceOverlap { first_id: first.id, second_id: second.id, months: shared });
+                }
+            }
+        }
+
+        let mut ranges: Vec<(u32, u32)> = experiences.iter().map(|e| e.months(now)).collect();
+        ranges.sort_unstable();
+        let mut total_months = 0;
+        let mut covered_until = 0;
+        for (start, end) in ranges {
+            let start = start.max(covered_until);
+            if end > start {
+                total_months += end - start;
+                covered_until = end;
+            }
+        }
+
+        Self {
+            experiences,
+            overlaps,
+            total_months,
+            total_years: (total_months as f32 / 12.0 * 10.0).round() / 10.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+    fn ym(year: u32, month: u32) -> YearMonth {
+        YearMonth { year, month }
+    }
+
+    /// Nanoseconds at `seconds` into day `days` after the epoch.
+    fn at(days: u64, seconds: u64) -> u64 {
+        days * NANOS_PER_DAY + seconds * NANOS_PER_SECOND
+    }
+
+    fn role(id: u32, start: YearMonth, end: Option<YearMonth>) -> WorkExperience {
+        WorkExperience {
+            id,
+            user_id: "user".to_string(),
+            company: "Acme".to_string(),
+            title: "Engineer".to_string(),
+            employment_type: EmploymentType::FullTime,
+            location: None,
+            start,
+            end,
+            is_current: end.is_none(),
+            description: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn overlaps(history: &WorkHistory) -> Vec<(u32, u32, u32)> {
+        history.overlaps.iter().map(|o| (o.first_id, o.second_id, o.months)).collect()
+    }
+
+    #[test]
+    fn converts_timestamps_at_month_and_year_boundaries() {
+        assert_eq!(YearMonth::from_timestamp(0), ym(1970, 1));
+        assert_eq!(YearMonth::from_timestamp(at(18_261, 86_399)), ym(2019, 12));
+        assert_eq!(YearMonth::from_timestamp(at(18_262, 0)), ym(2020, 1));
+        assert_eq!(YearMonth::from_timestamp(at(18_321, 86_399)), ym(2020, 2));
+        assert_eq!(YearMonth::from_timestamp(at(18_322, 0)), ym(2020, 3));
+        assert_eq!(YearMonth::from_timestamp(at(11_016, 0)), ym(2000, 2));
+        assert_eq!(YearMonth::from_timestamp(at(11_017, 0)), ym(2000, 3));
+        assert_eq!(YearMonth::from_timestamp(at(19_753, 43_200)), ym(2024, 1));
+        assert_eq!(YearMonth::from_timestamp(at(19_754, 0)), ym(2024, 2));
+    }
+
+    #[test]
+    fn counts_overlapping_roles_once() {
+        let now = ym(2024, 6);
+        let history = WorkHistory::new(
+            vec![role(1, ym(2018, 1), Some(ym(2020, 6))), role(2, ym(2020, 1), Some(ym(2021, 12)))],
+            now,
+        );
+        // Jan 2020 through Jun 2020 is shared.
+        assert_eq!(overlaps(&history), vec![(2, 1, 6)]);
+        assert_eq!(history.total_months, 48);
+        assert_eq!(history.total_years, 4.0);
+        assert_eq!(history.experiences.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 1]);
+    }
+
+    #[test]
+    fn a_nested_role_adds_no_months() {
+        let history = WorkHistory::new(
+            vec![role(1, ym(2015, 1), Some(ym(2019, 12))), role(2, ym(2016, 3), Some(ym(2016, 8)))],
+            ym(2024, 6),
+        );
+        assert_eq!(overlaps(&history), vec![(1, 2, 6)]);
+        assert_eq!(history.total_months, 60);
+        assert_eq!(history.total_years, 5.0);
+    }
+
+    #[test]
+    fn a_current_role_runs_through_now() {
+        let now = ym(2024, 6);
+        let history = WorkHistory::new(
+            vec![role(1, ym(2019, 1), Some(ym(2022, 12))), role(2, ym(2023, 7), None)],
+            now,
+        );
+        assert!(history.overlaps.is_empty());
+        // 48 months, then Jul 2023 through Jun 2024; the gap is not counted.
+        assert_eq!(history.total_months, 48 + 12);
+        assert_eq!(history.experiences[0].id, 2);
+
+        // A side job that is also current overlaps it up to now.
+        let history = WorkHistory::new(vec![role(2, ym(2023, 7), None), role(3, ym(2024, 1), None)], now);
+        assert_eq!(overlaps(&history), vec![(3, 2, 6)]);
+        assert_eq!(history.total_months, 12);
+    }
+
+    #[test]
+    fn adjacent_roles_do_not_overlap() {
+        let history = WorkHistory::new(
+            vec![role(1, ym(2020, 1), Some(ym(2020, 12))), role(2, ym(2021, 1), Some(ym(2021, 6)))],
+            ym(2024, 6),
+        );
+        assert!(history.overlaps.is_empty());
+        assert_eq!(history.total_months, 18);
+        assert_eq!(history.total_years, 1.5);
+    }
+}
@@ -1,12 +1,17 @@
 pub mod types;
+pub mod blob;
 pub mod user;
 pub mod bank;
 pub mod education;
+pub mod experience;
+pub mod payout;
 pub mod cv;
+pub mod cv_document;
 pub mod chat;
 pub mod rate_limit;
+pub mod pagination;
 
-pub use types::{StorageKey, FixedString, FixedContent};
+pub use types::{StorageKey, FixedString};
 pub use rate_limit::{UserAPIUsage, StableUserAPIUsage, RateLimitConfig};
 pub use user::UserProfile;
 pub use bank::BankInformation;
@@ -0,0 +1,57 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_LIMIT: u32 = 20;
+pub const MAX_PAGE_LIMIT: u32 = 100;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SortDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Which page to read. `cursor` is the `next_cursor` of the previous page,
+/// or `None` for the first one; it is only valid with the same direction.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PageRequest {
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+    pub direction: Option<SortDirection>,
+}
+
+impl PageRequest {
+    /// A first page of the largest allowed size, used by the unpaginated
+    /// endpoints that walk every page.
+    pub fn first(direction: SortDirection) -> Self {
+        Self {
+            cursor: None,
+            limit: Some(MAX_PAGE_LIMIT),
+            direction: Some(direction),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT) as usize
+    }
+
+    pub fn direction(&self) -> SortDirection {
+        self.direction.unwrap_or_default()
+    }
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the following page; `None` when this is the last one.
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
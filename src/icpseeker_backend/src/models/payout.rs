@@ -0,0 +1,261 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use ic_stable_structures::{Storable, BoundedStorable};
+use super::bank::BankInformation;
+use crate::encryption::{EncryptedData, Encryption};
+use super::types::{encode_record, decode_record, record_schema_version};
+
+pub const MAX_PAYOUT_METHODS: usize = 10;
+pub const SUBACCOUNT_LENGTH: usize = 32;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BankAccount {
+    pub account_holder_name: String,
+    pub bank_name: String,
+    /// BIC and account number are stored normalized: upper case, no spaces.
+    pub swift_code: String,
+    pub account_number: String,
+    pub bank_country: String,
+    pub bank_branch: Option<String>,
+}
+
+/// Where a payout goes.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PayoutMethod {
+    BankAccount(BankAccount),
+    /// An ICRC-1 ledger account; `None` is the default subaccount.
+    Icrc1Account {
+        owner: Principal,
+        subaccount: Option<Vec<u8>>,
+    },
+    /// A legacy ICP ledger account identifier, as 64 lower-case hex digits.
+    IcpAccountIdentifier(String),
+}
+
+impl PayoutMethod {
+    /// Whether both send money to the same place, ignoring the account
+    /// holder and bank names.
+    pub fn same_destination(&self, other: &PayoutMethod) -> bool {
+        match (self, other) {
+            (PayoutMethod::BankAccount(a), PayoutMethod::BankAccount(b)) => {
+                a.swift_code == b.swift_code && a.account_number == b.account_number
+            }
+            _ => self == other,
+        }
+    }
+}
+
+/// One of a user's payout methods. Exactly one method of a user with any
+/// methods is primary.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PayoutMethodRecord {
+    pub id: u32,
+    pub user_id: String,
+    pub label: Option<String>,
+    pub method: PayoutMethod,
+    pub is_primary: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl PayoutMethodRecord {
+    /// The record in the shape of the single-account bank API, if it is a
+    /// bank account.
+    pub fn to_bank_information(&self) -> Option<BankInformation> {
+        let PayoutMethod::BankAccount(account) = &self.method else {
+            return None;
+        };
+        Some(BankInformation {
+            id: format!("PAYOUT_{}", self.id),
+            user_id: self.user_id.clone(),
+            account_holder_name: account.account_holder_name.clone(),
+            bank_name: account.bank_name.clone(),
+            swift_code: account.swift_code.clone(),
+            account_number: account.account_number.clone(),
+            bank_country: account.bank_country.clone(),
+            bank_branch: account.bank_branch.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+/// A sensitive field as stored. `Plain` is only left by records written
+/// before the master key existed, until they are sealed.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum StoredField {
+    Plain(String),
+    Encrypted(EncryptedData),
+}
+
+impl StoredField {
+    fn seal(user_id: &str, field: &str, value: String) -> Result<Self, String> {
+        Encryption::encrypt(user_id, field, &value).map(StoredField::Encrypted)
+    }
+
+    fn open(self, user_id: &str, field: &str) -> Result<String, String> {
+        match self {
+            StoredField::Plain(value) => Ok(value),
+            StoredField::Encrypted(data) => Encryption::decrypt(user_id, field, &data),
+        }
+    }
+}
+
+/// `BankAccount` with the BIC and account number encrypted.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableBankAccount {
+    pub account_holder_name: String,
+    pub bank_name: String,
+    pub swift_code: StoredField,
+    pub account_number: StoredField,
+    pub bank_country: String,
+    pub bank_branch: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum StablePayoutMethod {
+    BankAccount(StableBankAccount),
+    Icrc1Account {
+        owner: Principal,
+        subaccount: Option<Vec<u8>>,
+    },
+    IcpAccountIdentifier(String),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StablePayoutMethodRecord {
+    pub id: u32,
+    pub user_id: String,
+    pub label: Option<String>,
+    pub method: StablePayoutMethod,
+    pub is_primary: bool,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl StablePayoutMethodRecord {
+    pub const SCHEMA_VERSION: u8 = 2;
+    /// Version 1 stored `PayoutMethodRecord` as it is, in plain text.
+    const PLAINTEXT_SCHEMA_VERSION: u8 = 1;
+
+    /// Encrypts the record's bank fields under its user's key.
+    pub fn seal(record: PayoutMethodRecord) -> Result<Self, String> {
+        let user_id = record.user_id.clone();
+        Self::convert(record, |field, value| StoredField::seal(&user_id, field, value))
+    }
+
+    /// Stores the record without encrypting it, for when no key exists yet.
+    pub fn plain(record: PayoutMethodRecord) -> Self {
+        let stored: Result<Self, Infallible> = Self::convert(record, |_, value| Ok(StoredField::Plain(value)));
+        match stored {
+            Ok(stored) => stored,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Whether no field is left in plain text.
+    pub fn is_sealed(&self) -> bool {
+        self.fields().all(|field| matches!(field, StoredField::Encrypted(_)))
+    }
+
+    /// Whether every field is encrypted under master key `version`.
+    pub fn is_sealed_under(&self, version: u32) -> bool {
+        self.fields()
+            .all(|field| matches!(field, StoredField::Encrypted(data) if data.key_version() == version))
+    }
+
+    fn fields(&self) -> impl Iterator<Item = &StoredField> {
+        let fields = match &self.method {
+            StablePayoutMethod::BankAccount(account) => vec![&account.swift_code, &account.account_number],
+            _ => Vec::new(),
+        };
+        fields.into_iter()
+    }
+
+    /// Decrypts the record.
+    pub fn open(self) -> Result<PayoutMethodRecord, String> {
+        let user_id = self.user_id;
+        let method = match self.method {
+            StablePayoutMethod::BankAccount(account) => PayoutMethod::BankAccount(BankAccount {
+                account_holder_name: account.account_holder_name,
+                bank_name: account.bank_name,
+                swift_code: account.swift_code.open(&user_id, "swift_code")?,
+                account_number: account.account_number.open(&user_id, "account_number")?,
+                bank_country: account.bank_country,
+                bank_branch: account.bank_branch,
+            }),
+            StablePayoutMethod::Icrc1Account { owner, subaccount } => PayoutMethod::Icrc1Account { owner, subaccount },
+            StablePayoutMethod::IcpAccountIdentifier(id) => PayoutMethod::IcpAccountIdentifier(id),
+        };
+        Ok(PayoutMethodRecord {
+            id: self.id,
+            user_id,
+            label: self.label,
+            method,
+            is_primary: self.is_primary,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+
+    fn convert<E>(
+        record: PayoutMethodRecord,
+        mut store: impl FnMut(&str, String) -> Result<StoredField, E>,
+    ) -> Result<Self, E> {
+        let method = match record.method {
+            PayoutMethod::BankAccount(account) => StablePayoutMethod::BankAccount(StableBankAccount {
+                account_holder_name: account.account_holder_name,
+                bank_name: account.bank_name,
+                swift_code: store("swift_code", account.swift_code)?,
+                account_number: store("account_number", account.account_number)?,
+                bank_country: account.bank_country,
+                bank_branch: account.bank_branch,
+            }),
+            PayoutMethod::Icrc1Account { owner, subaccount } => StablePayoutMethod::Icrc1Account { owner, subaccount },
+            PayoutMethod::IcpAccountIdentifier(id) => StablePayoutMethod::IcpAccountIdentifier(id),
+        };
+        Ok(Self {
+            id: record.id,
+            user_id: record.user_id,
+            label: record.label,
+            method,
+            is_primary: record.is_primary,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        })
+    }
+}
+
+impl Storable for StablePayoutMethodRecord {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(Self::PLAINTEXT_SCHEMA_VERSION) => Self::plain(decode_record(&bytes)),
+            version => panic!("Unsupported payout method schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StablePayoutMethodRecord {
+    const MAX_SIZE: u32 = 2 * 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl From<BankInformation> for BankAccount {
+    fn from(info: BankInformation) -> Self {
+        Self {
+            account_holder_name: info.account_holder_name,
+            bank_name: info.bank_name,
+            swift_code: info.swift_code,
+            account_number: info.account_number,
+            bank_country: info.bank_country,
+            bank_branch: info.bank_branch,
+        }
+    }
+}
@@ -1,14 +1,10 @@
-use candid::{CandidType, Decode, Encode};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use ic_cdk::api::time;
-use std::borrow::Cow;
-use ic_stable_structures::{Storable, BoundedStorable};
-use crate::models::FixedString;
- 
 
-#[derive(Clone, Debug)]
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableUserAPIUsage {
-    pub user_id: FixedString,
+    pub user_id: String,
     pub daily_requests: u32,
     pub last_reset: u64,
     pub total_requests: u64,
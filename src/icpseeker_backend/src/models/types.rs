@@ -1,6 +1,5 @@
 use candid::{CandidType, Decode, Encode};
-use serde::{Deserialize, Serialize};
-use ic_cdk::api::time;
+use serde::de::DeserializeOwned;
 use std::borrow::Cow;
 use ic_stable_structures::{Storable, BoundedStorable};
 use crate::models::StableUserAPIUsage;
@@ -8,13 +7,53 @@ use crate::models::UserAPIUsage;
 
 pub type StorageKey = [u8; 32];  
 pub type FixedString = [u8; 32];  
-pub type FixedContent = [u8; 1024];  
 
 #[derive(Clone, Debug)]
 pub struct Convertible<T: Clone> {
     pub data: T,
 }
 
+/// First byte of every record written with the versioned encoding.
+///
+/// 0xFE never appears in UTF-8 text, so it cannot be the first byte of a
+/// legacy fixed-width record (those start with the record id).
+pub const RECORD_MAGIC: u8 = 0xFE;
+
+/// Encodes a record as `[RECORD_MAGIC, schema_version, candid bytes...]`.
+///
+/// Candid is self-describing, so records keep decoding after `opt` fields are
+/// added; the schema version is there for changes candid cannot absorb.
+pub fn encode_record<T: CandidType>(schema_version: u8, record: &T) -> Vec<u8> {
+    let mut bytes = vec![RECORD_MAGIC, schema_version];
+    bytes.extend(Encode!(record).expect("Failed to encode record"));
+    bytes
+}
+
+/// Returns the schema version of a versioned record, or `None` for legacy
+/// fixed-width bytes.
+pub fn record_schema_version(bytes: &[u8]) -> Option<u8> {
+    match bytes {
+        [RECORD_MAGIC, version, ..] => Some(*version),
+        _ => None,
+    }
+}
+
+pub fn decode_record<T: CandidType + DeserializeOwned>(bytes: &[u8]) -> T {
+    Decode!(&bytes[2..], T).expect("Failed to decode record")
+}
+
+/// Reads a zero-padded field of a legacy fixed-width record.
+///
+/// The old encoding could cut a multi-byte character in half; instead of
+/// dropping the whole field we keep the valid UTF-8 prefix.
+pub fn legacy_field_to_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    match std::str::from_utf8(&bytes[..end]) {
+        Ok(s) => s.to_string(),
+        Err(e) => String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned(),
+    }
+}
+
 pub fn string_to_storage_key(s: &str) -> StorageKey {
     let mut key = [0u8; 32];
     let bytes = s.as_bytes();
@@ -23,15 +62,6 @@ pub fn string_to_storage_key(s: &str) -> StorageKey {
     key
 }
 
-pub fn storage_key_to_string(key: &StorageKey) -> String {
-    String::from_utf8(
-        key.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
-}
-
 pub fn string_to_fixed(s: &str) -> FixedString {
     let mut fixed = [0u8; 32];
     let bytes = s.as_bytes();
@@ -40,75 +70,45 @@ pub fn string_to_fixed(s: &str) -> FixedString {
     fixed
 }
 
-pub fn fixed_to_string(fixed: &FixedString) -> String {
-    String::from_utf8(
-        fixed.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
-}
-
-pub fn string_to_content(s: &str) -> FixedContent {
-    let mut content = [0u8; 1024];
-    let bytes = s.as_bytes();
-    let len = bytes.len().min(1024);
-    content[..len].copy_from_slice(&bytes[..len]);
-    content
-}
+impl StableUserAPIUsage {
+    pub const SCHEMA_VERSION: u8 = 1;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = 32 + 4 + 8 + 8;
 
-pub fn content_to_string(content: &FixedContent) -> String {
-    String::from_utf8(
-        content.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
+        Self {
+            user_id: legacy_field_to_string(&bytes[0..32]),
+            daily_requests: u32::from_be_bytes(bytes[32..36].try_into().unwrap()),
+            last_reset: u64::from_be_bytes(bytes[36..44].try_into().unwrap()),
+            total_requests: u64::from_be_bytes(bytes[44..52].try_into().unwrap()),
+        }
+    }
 }
 
 impl Storable for StableUserAPIUsage {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.user_id);
-        bytes.extend_from_slice(&self.daily_requests.to_be_bytes());
-        bytes.extend_from_slice(&self.last_reset.to_be_bytes());
-        bytes.extend_from_slice(&self.total_requests.to_be_bytes());
-        Cow::Owned(bytes)
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
     }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        let mut pos = 0;
-        
-        let mut user_id = [0u8; 32];
-        user_id.copy_from_slice(&bytes[pos..pos + 32]);
-        pos += 32;
-
-        let daily_requests = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
-        pos += 4;
-
-        let last_reset = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
-        pos += 8;
-
-        let total_requests = u64::from_be_bytes(bytes[pos..pos + 8].try_into().unwrap());
-
-        Self {
-            user_id,
-            daily_requests,
-            last_reset,
-            total_requests,
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(version) => panic!("Unsupported API usage schema version {}", version),
+            None => Self::from_legacy_bytes(&bytes),
         }
     }
 }
 
 impl BoundedStorable for StableUserAPIUsage {
-    const MAX_SIZE: u32 = 32 + 4 + 8 + 8;
-    const IS_FIXED_SIZE: bool = true;
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 impl From<UserAPIUsage> for StableUserAPIUsage {
     fn from(usage: UserAPIUsage) -> Self {
         Self {
-            user_id: string_to_fixed(&usage.user_id),
+            user_id: usage.user_id,
             daily_requests: usage.daily_requests,
             last_reset: usage.last_reset,
             total_requests: usage.total_requests,
@@ -119,7 +119,7 @@ impl From<UserAPIUsage> for StableUserAPIUsage {
 impl From<StableUserAPIUsage> for UserAPIUsage {
     fn from(usage: StableUserAPIUsage) -> Self {
         Self {
-            user_id: fixed_to_string(&usage.user_id),
+            user_id: usage.user_id,
             daily_requests: usage.daily_requests,
             last_reset: usage.last_reset,
             total_requests: usage.total_requests,
@@ -1,18 +1,13 @@
-use candid::{CandidType, Decode, Encode};
+use candid::CandidType;
 use serde::{Deserialize, Serialize};
-use ic_cdk::api::time;
 use std::borrow::Cow;
 use ic_stable_structures::{Storable, BoundedStorable};
+use super::types::{encode_record, decode_record, record_schema_version, legacy_field_to_string};
 
-pub type FixedString = [u8; 32];
-
-pub fn string_to_fixed(s: &str) -> FixedString {
-    let mut fixed = [0u8; 32];
-    let bytes = s.as_bytes();
-    let len = bytes.len().min(32);
-    fixed[..len].copy_from_slice(&bytes[..len]);
-    fixed
-}
+/// `UserProfile::status` while checklist items are missing.
+pub const PROFILE_STATUS_INCOMPLETE: u8 = 0;
+/// `UserProfile::status` once every checklist item is done.
+pub const PROFILE_STATUS_COMPLETE: u8 = 1;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct UserProfile {
@@ -26,133 +21,74 @@ pub struct UserProfile {
     pub bank_info_id: Option<String>,
     pub status: u8,
     pub profile_completion: u8,
+    /// When the current `email` was confirmed with a code; `None` until
+    /// then, and again after the address changes.
+    pub email_verified_at: Option<u64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct StableUserProfile {
-    pub id: FixedString,
-    pub name: FixedString,
-    pub email: FixedString,
-    pub phone_number: FixedString,
-    pub city: FixedString,
-    pub country: FixedString,
-    pub education_id: Option<FixedString>,
-    pub bank_info_id: Option<FixedString>,
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    pub phone_number: String,
+    pub city: String,
+    pub country: String,
+    pub education_id: Option<String>,
+    pub bank_info_id: Option<String>,
     pub status: u8,
     pub profile_completion: u8,
+    pub email_verified_at: Option<u64>,
 }
 
-pub fn fixed_to_string(fixed: &FixedString) -> String {
-    String::from_utf8(
-        fixed.iter()
-            .take_while(|&&x| x != 0)
-            .copied()
-            .collect()
-    ).unwrap_or_default()
-}
+impl StableUserProfile {
+    pub const SCHEMA_VERSION: u8 = 1;
+    /// `MAX_SIZE` of the fixed-width layout, needed to reopen legacy maps.
+    pub const LEGACY_MAX_SIZE: u32 = (32 * 8) + 2 + 16;
 
-impl Storable for StableUserProfile {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(&self.id);
-        bytes.extend_from_slice(&self.name);
-        bytes.extend_from_slice(&self.email);
-        bytes.extend_from_slice(&self.phone_number);
-        bytes.extend_from_slice(&self.city);
-        bytes.extend_from_slice(&self.country);
-        
-        if let Some(edu_id) = &self.education_id {
-            bytes.push(1);
-            bytes.extend_from_slice(edu_id);
-        } else {
-            bytes.push(0);
-            bytes.extend_from_slice(&[0u8; 32]);
+    /// Decodes the fixed-width layout used before records were versioned.
+    fn from_legacy_bytes(bytes: &[u8]) -> Self {
+        fn read_fixed_string(bytes: &[u8], start: usize) -> String {
+            legacy_field_to_string(&bytes[start..start + 32])
         }
-        
-        if let Some(bank_id) = &self.bank_info_id {
-            bytes.push(1);
-            bytes.extend_from_slice(bank_id);
-        } else {
-            bytes.push(0);
-            bytes.extend_from_slice(&[0u8; 32]);
-        }
-
-        bytes.push(self.status);
-        bytes.push(self.profile_completion);
-        
-        Cow::Owned(bytes)
-    }
 
-    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
-        fn read_fixed_string(bytes: &[u8], start: usize) -> FixedString {
-            let mut arr = [0u8; 32];
-            arr.copy_from_slice(&bytes[start..start + 32]);
-            arr
+        fn read_optional(bytes: &[u8], start: usize) -> Option<String> {
+            (bytes[start] == 1).then(|| read_fixed_string(bytes, start + 1))
         }
 
-        let mut pos = 0;
-        
-        let id = read_fixed_string(&bytes, pos);
-        pos += 32;
-        
-        let name = read_fixed_string(&bytes, pos);
-        pos += 32;
-        
-        let email = read_fixed_string(&bytes, pos);
-        pos += 32;
-        
-        let phone_number = read_fixed_string(&bytes, pos);
-        pos += 32;
-        
-        let city = read_fixed_string(&bytes, pos);
-        pos += 32;
-        
-        let country = read_fixed_string(&bytes, pos);
-        pos += 32;
-
-        let education_id = if bytes[pos] == 1 {
-            pos += 1;
-            let edu_id = read_fixed_string(&bytes, pos);
-            pos += 32;
-            Some(edu_id)
-        } else {
-            pos += 33;
-            None
-        };
-
-        let bank_info_id = if bytes[pos] == 1 {
-            pos += 1;
-            let bank_id = read_fixed_string(&bytes, pos);
-            pos += 32;
-            Some(bank_id)
-        } else {
-            pos += 33;
-            None
-        };
+        Self {
+            id: read_fixed_string(bytes, 0),
+            name: read_fixed_string(bytes, 32),
+            email: read_fixed_string(bytes, 64),
+            phone_number: read_fixed_string(bytes, 96),
+            city: read_fixed_string(bytes, 128),
+            country: read_fixed_string(bytes, 160),
+            education_id: read_optional(bytes, 192),
+            bank_info_id: read_optional(bytes, 225),
+            status: bytes[258],
+            profile_completion: bytes[259],
+            email_verified_at: None,
+        }
+    }
+}
 
-        let status = bytes[pos];
-        pos += 1;
-        let profile_completion = bytes[pos];
-        pos += 1;
+impl Storable for StableUserProfile {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
 
-        Self {
-            id,
-            name,
-            email,
-            phone_number,
-            city,
-            country,
-            education_id,
-            bank_info_id,
-            status,
-            profile_completion,
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            Some(version) => panic!("Unsupported user profile schema version {}", version),
+            None => Self::from_legacy_bytes(&bytes),
         }
     }
 }
 
 impl BoundedStorable for StableUserProfile {
-    const MAX_SIZE: u32 = (32 * 8) + 2 + 16; 
-    const IS_FIXED_SIZE: bool = true;
+    const MAX_SIZE: u32 = 4 * 1024;
+    const IS_FIXED_SIZE: bool = false;
 }
 
 impl UserProfile {
@@ -175,23 +111,31 @@ impl UserProfile {
             bank_info_id: None,
             status: 0,
             profile_completion: 0,
+            email_verified_at: None,
         }
     }
+
+    /// Whether the current email address was confirmed with a mailed code,
+    /// for features that have to reach the user.
+    pub fn email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
 }
 
 impl From<StableUserProfile> for UserProfile {
     fn from(profile: StableUserProfile) -> Self {
         Self {
-            id: fixed_to_string(&profile.id),
-            name: fixed_to_string(&profile.name),
-            email: fixed_to_string(&profile.email),
-            phone_number: fixed_to_string(&profile.phone_number),
-            city: fixed_to_string(&profile.city),
-            country: fixed_to_string(&profile.country),
-            education_id: profile.education_id.map(|id| fixed_to_string(&id)),
-            bank_info_id: profile.bank_info_id.map(|id| fixed_to_string(&id)),
+            id: profile.id,
+            name: profile.name,
+            email: profile.email,
+            phone_number: profile.phone_number,
+            city: profile.city,
+            country: profile.country,
+            education_id: profile.education_id,
+            bank_info_id: profile.bank_info_id,
             status: profile.status,
             profile_completion: profile.profile_completion,
+            email_verified_at: profile.email_verified_at,
         }
     }
 }
@@ -199,16 +143,17 @@ impl From<StableUserProfile> for UserProfile {
 impl From<UserProfile> for StableUserProfile {
     fn from(profile: UserProfile) -> Self {
         Self {
-            id: string_to_fixed(&profile.id),
-            name: string_to_fixed(&profile.name),
-            email: string_to_fixed(&profile.email),
-            phone_number: string_to_fixed(&profile.phone_number),
-            city: string_to_fixed(&profile.city),
-            country: string_to_fixed(&profile.country),
-            education_id: profile.education_id.map(|id| string_to_fixed(&id)),
-            bank_info_id: profile.bank_info_id.map(|id| string_to_fixed(&id)),
+            id: profile.id,
+            name: profile.name,
+            email: profile.email,
+            phone_number: profile.phone_number,
+            city: profile.city,
+            country: profile.country,
+            education_id: profile.education_id,
+            bank_info_id: profile.bank_info_id,
             status: profile.status,
             profile_completion: profile.profile_completion,
+            email_verified_at: profile.email_verified_at,
         }
     }
 }
\ No newline at end of file
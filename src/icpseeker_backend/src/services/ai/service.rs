@@ -1,4 +1,4 @@
-use crate::models::cv::CV;
+use crate::services::cv_document;
 use crate::storage::memory::CVStorage;
 use ic_cdk::api::management_canister::http_request::{
     http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse,
@@ -14,6 +14,7 @@ use ic_cdk::api::call::CallResult;
 use candid::Principal;
 use candid::Nat;
 use crate::get_openai_key;
+use crate::auth::access::anyone;
 
 pub struct AIService;
 
@@ -26,7 +27,7 @@ impl AIService {
         let cv = CVStorage::get_cv(cv_id)
             .map_err(|e| format!("Failed to get CV: {}", e))?;
 
-        let system_message = Self::create_system_message(&cv);
+        let system_message = Self::create_system_message(&cv_document::cv_text(&cv));
         
         let mut messages = vec![OpenAIMessage {
             role: "system".to_string(),
@@ -59,7 +60,7 @@ impl AIService {
             .ok_or_else(|| "No response generated".to_string())
     }
 
-    fn create_system_message(cv: &CV) -> String {
+    fn create_system_message(cv_text: &str) -> String {
         format!(
             "You are a helpful CV assistant. You help users improve their CVs and provide career advice. \
             You have access to the user's CV with the following content:\n\n{}\n\n\
@@ -69,7 +70,7 @@ impl AIService {
             3. Suggest concrete improvements\n\
             4. Keep responses concise but helpful\n\
             5. Focus on professional development",
-            cv.content
+            cv_text
         )
     }
 
@@ -138,7 +139,7 @@ impl AIService {
     }
 }
 
-#[ic_cdk::query]
+#[ic_cdk::query(guard = "anyone")]
 fn transform_response_query(args: TransformArgs) -> HttpResponse {
     let mut response = args.response;
     
@@ -0,0 +1,458 @@
+use ic_cdk::api::time;
+
+use crate::models::cv::CV;
+use crate::models::cv_document::{
+    CertificationEntry, CvDocument, CvEntry, LanguageEntry, ProjectEntry, SkillEntry, WorkEntry,
+};
+use crate::services::cv_history::CVHistoryService;
+use crate::services::cv_parser;
+use crate::storage::blob::BlobStorage;
+use crate::storage::memory::CvDocumentStorage;
+use crate::types::errors::ApiError;
+
+pub const MAX_SUMMARY_CHARS: usize = 2000;
+pub const MAX_ENTRIES_PER_SECTION: usize = 50;
+pub const MAX_BULLETS_PER_ENTRY: usize = 20;
+pub const MAX_BULLET_CHARS: usize = 500;
+pub const MAX_FIELD_CHARS: usize = 200;
+
+const MONTH_ABBREVIATIONS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+pub struct CvDocumentService;
+
+impl CvDocumentService {
+    /// The document of `cv_id`; empty when none has been written yet.
+    pub fn get(cv_id: &str, user_id: &str) -> Result<CvDocument, ApiError> {
+        CVHistoryService::owned_cv(cv_id, user_id)?;
+        Ok(CvDocumentStorage::get(cv_id).unwrap_or_default())
+    }
+
+    pub fn set_summary(cv_id: &str, user_id: &str, summary: String) -> Result<CvDocument, ApiError> {
+        let summary = summary.trim().to_string();
+        if summary.chars().count() > MAX_SUMMARY_CHARS {
+            return Err(ApiError::invalid(
+                "summary",
+                "too_long",
+                format!("Summary must be at most {} characters", MAX_SUMMARY_CHARS),
+            ));
+        }
+        Self::modify(cv_id, user_id, |document| {
+            document.summary = (!summary.is_empty()).then_some(summary);
+            Ok(())
+        })
+    }
+
+    pub fn add_entry(cv_id: &str, user_id: &str, entry: CvEntry) -> Result<CvDocument, ApiError> {
+        let entry = validate_entry(entry).map_err(|e| ApiError::invalid("entry", "invalid", e))?;
+        Self::modify(cv_id, user_id, |document| {
+            let id = document.allocate_id();
+            match entry {
+                CvEntry::Work(e) => push_entry(&mut document.work, WorkEntry { id, ..e }),
+                CvEntry::Skill(e) => push_entry(&mut document.skills, SkillEntry { id, ..e }),
+                CvEntry::Language(e) => push_entry(&mut document.languages, LanguageEntry { id, ..e }),
+                CvEntry::Certification(e) => {
+                    push_entry(&mut document.certifications, CertificationEntry { id, ..e })
+                }
+                CvEntry::Project(e) => push_entry(&mut document.projects, ProjectEntry { id, ..e }),
+            }
+        })
+    }
+
+    /// Replaces entry `entry_id`, which must belong to the section `entry` is for.
+    pub fn update_entry(cv_id: &str, user_id: &str, entry_id: u32, entry: CvEntry) -> Result<CvDocument, ApiError> {
+        let entry = validate_entry(entry).map_err(|e| ApiError::invalid("entry", "invalid", e))?;
+        Self::modify(cv_id, user_id, |document| match entry {
+            CvEntry::Work(e) => replace_entry(&mut document.work, |x| x.id, WorkEntry { id: entry_id, ..e }),
+            CvEntry::Skill(e) => replace_entry(&mut document.skills, |x| x.id, SkillEntry { id: entry_id, ..e }),
+            CvEntry::Language(e) => {
+                replace_entry(&mut document.languages, |x| x.id, LanguageEntry { id: entry_id, ..e })
+            }
+            CvEntry::Certification(e) => replace_entry(
+                &mut document.certifications,
+                |x| x.id,
+                CertificationEntry { id: entry_id, ..e },
+            ),
+            CvEntry::Project(e) => {
+                replace_entry(&mut document.projects, |x| x.id, ProjectEntry { id: entry_id, ..e })
+            }
+        })
+    }
+
+    pub fn remove_entry(cv_id: &str, user_id: &str, entry_id: u32) -> Result<CvDocument, ApiError> {
+        Self::modify(cv_id, user_id, |document| {
+            let before = entry_count(document);
+            document.work.retain(|e| e.id != entry_id);
+            document.skills.retain(|e| e.id != entry_id);
+            document.languages.retain(|e| e.id != entry_id);
+            document.certifications.retain(|e| e.id != entry_id);
+            document.projects.retain(|e| e.id != entry_id);
+            if entry_count(document) == before {
+                return Err(ApiError::NotFound(format!("Entry {} not found", entry_id)));
+            }
+            Ok(())
+        })
+    }
+
+    /// Replaces the document with one parsed from `text`, or from the CV's
+    /// attached file (falling back to its content) when `text` is `None`.
+    pub fn import(cv_id: &str, user_id: &str, text: Option<String>) -> Result<CvDocument, ApiError> {
+        let cv = CVHistoryService::owned_cv(cv_id, user_id)?;
+        let text = text.unwrap_or_else(|| source_text(&cv));
+        let mut parsed = cv_parser::parse(&text);
+        if parsed.is_empty() {
+            return Err(ApiError::invalid("text", "no_sections", "No CV sections recognized in text"));
+        }
+        fit_limits(&mut parsed);
+
+        Self::modify(cv_id, user_id, |document| {
+            // Keep ids increasing so clients never see an old id reused.
+            let offset = document.next_entry_id;
+            parsed.work.iter_mut().for_each(|e| e.id += offset);
+            parsed.skills.iter_mut().for_each(|e| e.id += offset);
+            parsed.languages.iter_mut().for_each(|e| e.id += offset);
+            parsed.certifications.iter_mut().for_each(|e| e.id += offset);
+            parsed.projects.iter_mut().for_each(|e| e.id += offset);
+            parsed.next_entry_id += offset;
+            *document = parsed;
+            Ok(())
+        })
+    }
+
+    fn modify(
+        cv_id: &str,
+        user_id: &str,
+        change: impl FnOnce(&mut CvDocument) -> Result<(), ApiError>,
+    ) -> Result<CvDocument, ApiError> {
+        CVHistoryService::owned_cv(cv_id, user_id)?;
+        let mut document = CvDocumentStorage::get(cv_id).unwrap_or_default();
+        change(&mut document)?;
+        document.updated_at = time();
+        CvDocumentStorage::put(cv_id, document.clone())?;
+        Ok(document)
+    }
+}
+
+/// Text the analyzer and chat work from: the structured document when the CV
+/// has one, otherwise the attached file's text, otherwise the CV's content.
+pub fn cv_text(cv: &CV) -> String {
+    CvDocumentStorage::get(&cv.id)
+        .filter(|document| !document.is_empty())
+        .map(|document| render(&document))
+        .unwrap_or_else(|| source_text(cv))
+}
+
+fn source_text(cv: &CV) -> String {
+    cv.blob_id
+        .and_then(BlobStorage::get_text)
+        .map(|extracted| extracted.text)
+        .filter(|text| !text.trim().is_empty())
+        .unwrap_or_else(|| cv.content.clone())
+}
+
+/// Renders the document as plain text with one upper-case heading per
+/// section, in the layout `cv_parser::parse` reads back.
+pub fn render(document: &CvDocument) -> String {
+    let mut sections: Vec<String> = Vec::new();
+
+    if let Some(summary) = document.summary.as_deref().filter(|s| !s.trim().is_empty()) {
+        sections.push(format!("SUMMARY\n{}", summary.trim()));
+    }
+
+    if !document.work.is_empty() {
+        let entries: Vec<String> = document
+            .work
+            .iter()
+            .map(|e| {
+                let mut lines = vec![join_header(&e.role, &e.employer, " at ")];
+                if let Some(dates) = date_range(e.start_date.as_deref(), e.end_date.as_deref(), e.is_current) {
+                    lines.push(dates);
+                }
+                lines.extend(e.bullets.iter().map(|b| format!("- {}", b)));
+                lines.join("\n")
+            })
+            .collect();
+        sections.push(format!("WORK EXPERIENCE\n{}", entries.join("\n\n")));
+    }
+
+    if !document.skills.is_empty() {
+        let mut groups: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+        for skill in &document.skills {
+            let category = skill.category.as_deref();
+            match groups.iter_mut().find(|(c, _)| *c == category) {
+                Some((_, names)) => names.push(&skill.name),
+                None => groups.push((category, vec![&skill.name])),
+            }
+        }
+        let lines: Vec<String> = groups
+            .into_iter()
+            .map(|(category, names)| match category {
+                Some(category) => format!("{}: {}", category, names.join(", ")),
+                None => names.join(", "),
+            })
+            .collect();
+        sections.push(format!("SKILLS\n{}", lines.join("\n")));
+    }
+
+    if !document.languages.is_empty() {
+        let languages: Vec<String> = document
+            .languages
+            .iter()
+            .map(|l| match &l.proficiency {
+                Some(proficiency) => format!("{} ({})", l.name, proficiency),
+                None => l.name.clone(),
+            })
+            .collect();
+        sections.push(format!("LANGUAGES\n{}", languages.join(", ")));
+    }
+
+    if !document.certifications.is_empty() {
+        let lines: Vec<String> = document
+            .certifications
+            .iter()
+            .map(|c| {
+                let mut line = format!("- {}", join_header(&c.name, c.issuer.as_deref().unwrap_or(""), " - "));
+                if let Some(date) = &c.date {
+                    line.push_str(&format!(" ({})", format_date(date)));
+                }
+                line
+            })
+            .collect();
+        sections.push(format!("CERTIFICATIONS\n{}", lines.join("\n")));
+    }
+
+    if !document.projects.is_empty() {
+        let entries: Vec<String> = document
+            .projects
+            .iter()
+            .map(|p| {
+                let mut lines = vec![join_header(&p.name, p.url.as_deref().unwrap_or(""), " - ")];
+                lines.extend(p.description.clone());
+                lines.extend(p.bullets.iter().map(|b| format!("- {}", b)));
+                lines.join("\n")
+            })
+            .collect();
+        sections.push(format!("PROJECTS\n{}", entries.join("\n\n")));
+    }
+
+    sections.join("\n\n")
+}
+
+fn join_header(first: &str, second: &str, separator: &str) -> String {
+    match (first.is_empty(), second.is_empty()) {
+        (_, true) => first.to_string(),
+        (true, false) => second.to_string(),
+        (false, false) => format!("{}{}{}", first, separator, second),
+    }
+}
+
+/// `2020-03` as "Mar 2020"; a bare year as is.
+fn format_date(date: &str) -> String {
+    match date.split_once('-') {
+        Some((year, month)) => match month.parse::<usize>() {
+            Ok(month @ 1..=12) => format!("{} {}", MONTH_ABBREVIATIONS[month - 1], year),
+            _ => date.to_string(),
+        },
+        None => date.to_string(),
+    }
+}
+
+fn date_range(start: Option<&str>, end: Option<&str>, is_current: bool) -> Option<String> {
+    let start = start.map(format_date);
+    let end = if is_current { Some("Present".to_string()) } else { end.map(format_date) };
+    match (start, end) {
+        (Some(start), Some(end)) => Some(format!("{} - {}", start, end)),
+        (Some(date), None) | (None, Some(date)) => Some(date),
+        (None, None) => None,
+    }
+}
+
+fn entry_count(document: &CvDocument) -> usize {
+    document.work.len()
+        + document.skills.len()
+        + document.languages.len()
+        + document.certifications.len()
+        + document.projects.len()
+}
+
+fn push_entry<T>(section: &mut Vec<T>, entry: T) -> Result<(), ApiError> {
+    if section.len() >= MAX_ENTRIES_PER_SECTION {
+        return Err(ApiError::invalid(
+            "entry",
+            "too_many",
+            format!("A section holds at most {} entries", MAX_ENTRIES_PER_SECTION),
+        ));
+    }
+    section.push(entry);
+    Ok(())
+}
+
+fn replace_entry<T>(section: &mut [T], id_of: impl Fn(&T) -> u32, entry: T) -> Result<(), ApiError> {
+    let id = id_of(&entry);
+    let slot = section
+        .iter_mut()
+        .find(|existing| id_of(existing) == id)
+        .ok_or_else(|| ApiError::NotFound(format!("Entry {} not found in this section", id)))?;
+    *slot = entry;
+    Ok(())
+}
+
+/// `YYYY` or `YYYY-MM`.
+pub fn is_valid_date(date: &str) -> bool {
+    let (year, month) = match date.split_once('-') {
+        Some((year, month)) => (year, Some(month)),
+        None => (date, None),
+    };
+    let year_ok = year.len() == 4
+        && year.parse::<u32>().is_ok_and(|y| (1900..=2100).contains(&y));
+    let month_ok = month.is_none_or(|m| m.len() == 2 && m.parse::<u32>().is_ok_and(|m| (1..=12).contains(&m)));
+    year_ok && month_ok
+}
+
+fn required(value: String, field: &str) -> Result<String, String> {
+    bounded(value, field, MAX_FIELD_CHARS)
+}
+
+fn bounded(value: String, field: &str, max: usize) -> Result<String, String> {
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        return Err(format!("{} is required", field));
+    }
+    if value.chars().count() > max {
+        return Err(format!("{} must be at most {} characters", field, max));
+    }
+    Ok(value)
+}
+
+fn optional(value: Option<String>, field: &str) -> Result<Option<String>, String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .map(|v| required(v, field))
+        .transpose()
+}
+
+fn optional_date(value: Option<String>, field: &str) -> Result<Option<String>, String> {
+    let value = optional(value, field)?;
+    if value.as_deref().is_some_and(|d| !is_valid_date(d)) {
+        return Err(format!("{} must be YYYY or YYYY-MM", field));
+    }
+    Ok(value)
+}
+
+fn bullets(bullets: Vec<String>) -> Result<Vec<String>, String> {
+    let bullets: Vec<String> = bullets
+        .into_iter()
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty())
+        .collect();
+    if bullets.len() > MAX_BULLETS_PER_ENTRY {
+        return Err(format!("An entry holds at most {} bullet points", MAX_BULLETS_PER_ENTRY));
+    }
+    if bullets.iter().any(|b| b.chars().count() > MAX_BULLET_CHARS) {
+        return Err(format!("Bullet points must be at most {} characters", MAX_BULLET_CHARS));
+    }
+    Ok(bullets)
+}
+
+/// Trims and checks an entry from a client.
+fn validate_entry(entry: CvEntry) -> Result<CvEntry, String> {
+    Ok(match entry {
+        CvEntry::Work(e) => {
+            let start_date = optional_date(e.start_date, "Start date")?;
+            let end_date = optional_date(e.end_date, "End date")?;
+            if e.is_current && end_date.is_some() {
+                return Err("A current position has no end date".to_string());
+            }
+            if let (Some(start), Some(end)) = (&start_date, &end_date) {
+                if end < start {
+                    return Err("End date is before start date".to_string());
+                }
+            }
+            CvEntry::Work(WorkEntry {
+                id: 0,
+                employer: required(e.employer, "Employer")?,
+                role: required(e.role, "Role")?,
+                start_date,
+                end_date,
+                is_current: e.is_current,
+                bullets: bullets(e.bullets)?,
+            })
+        }
+        CvEntry::Skill(e) => CvEntry::Skill(SkillEntry {
+            id: 0,
+            name: required(e.name, "Skill")?,
+            category: optional(e.category, "Category")?,
+        }),
+        CvEntry::Language(e) => CvEntry::Language(LanguageEntry {
+            id: 0,
+            name: required(e.name, "Language")?,
+            proficiency: optional(e.proficiency, "Proficiency")?,
+        }),
+        CvEntry::Certification(e) => CvEntry::Certification(CertificationEntry {
+            id: 0,
+            name: required(e.name, "Name")?,
+            issuer: optional(e.issuer, "Issuer")?,
+            date: optional_date(e.date, "Date")?,
+        }),
+        CvEntry::Project(e) => CvEntry::Project(ProjectEntry {
+            id: 0,
+            name: required(e.name, "Name")?,
+            description: e
+                .description
+                .filter(|d| !d.trim().is_empty())
+                .map(|d| bounded(d, "Description", MAX_BULLET_CHARS))
+                .transpose()?,
+            url: optional(e.url, "URL")?,
+            bullets: bullets(e.bullets)?,
+        }),
+    })
+}
+
+fn clip(text: &mut String, max: usize) {
+    if let Some((at, _)) = text.char_indices().nth(max) {
+        text.truncate(at);
+    }
+}
+
+/// Cuts a parsed document down to the limits entries from clients are held to.
+fn fit_limits(document: &mut CvDocument) {
+    if let Some(summary) = &mut document.summary {
+        clip(summary, MAX_SUMMARY_CHARS);
+    }
+    document.work.truncate(MAX_ENTRIES_PER_SECTION);
+    document.skills.truncate(MAX_ENTRIES_PER_SECTION);
+    document.languages.truncate(MAX_ENTRIES_PER_SECTION);
+    document.certifications.truncate(MAX_ENTRIES_PER_SECTION);
+    document.projects.truncate(MAX_ENTRIES_PER_SECTION);
+
+    let fit_bullets = |bullets: &mut Vec<String>| {
+        bullets.truncate(MAX_BULLETS_PER_ENTRY);
+        bullets.iter_mut().for_each(|b| clip(b, MAX_BULLET_CHARS));
+    };
+    for e in &mut document.work {
+        clip(&mut e.employer, MAX_FIELD_CHARS);
+        clip(&mut e.role, MAX_FIELD_CHARS);
+        fit_bullets(&mut e.bullets);
+    }
+    for e in &mut document.skills {
+        clip(&mut e.name, MAX_FIELD_CHARS);
+    }
+    for e in &mut document.languages {
+        clip(&mut e.name, MAX_FIELD_CHARS);
+    }
+    for e in &mut document.certifications {
+        clip(&mut e.name, MAX_FIELD_CHARS);
+        if let Some(issuer) = &mut e.issuer {
+            clip(issuer, MAX_FIELD_CHARS);
+        }
+    }
+    for e in &mut document.projects {
+        clip(&mut e.name, MAX_FIELD_CHARS);
+        if let Some(description) = &mut e.description {
+            clip(description, MAX_BULLET_CHARS);
+        }
+        fit_bullets(&mut e.bullets);
+    }
+}
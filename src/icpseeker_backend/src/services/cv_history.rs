@@ -0,0 +1,244 @@
+use crate::models::cv::{CV, CVAnalysisStatus, CVDiff, CVVersion, CVVersionInfo, DiffLine, DiffOp};
+use crate::models::pagination::{Page, PageRequest};
+use crate::services::profile_completion::ProfileCompletion;
+use crate::storage::blob::BlobStorage;
+use crate::storage::memory::{CVStorage, CVVersionStorage};
+use crate::types::errors::ApiError;
+
+/// Above this many LCS cells a diff falls back to "everything removed,
+/// everything added" rather than risk the instruction limit.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+pub struct CVHistoryService;
+
+impl CVHistoryService {
+    /// Stores the edited `cv` as its new head and records it as a new
+    /// version. The previous analysis no longer applies and is cleared.
+    ///
+    /// The version is checked before the head is written, so a version that
+    /// cannot be stored leaves the head unchanged too.
+    pub fn commit(mut cv: CV, reverted_from: Option<u32>) -> Result<CV, ApiError> {
+        cv.version += 1;
+        cv.ai_analysis_status = CVAnalysisStatus::NotAnalyzed;
+        cv.ai_feedback = None;
+
+        let version = CVVersion::of(&cv, reverted_from);
+        CVVersionStorage::check(&version)?;
+        CVStorage::update_cv(cv.clone())?;
+        CVVersionStorage::record(version)?;
+        ProfileCompletion::refresh(&cv.user_id);
+        Ok(cv)
+    }
+
+    pub fn list_versions(cv_id: &str, user_id: &str, request: &PageRequest) -> Result<Page<CVVersionInfo>, ApiError> {
+        Self::owned_cv(cv_id, user_id)?;
+        let page = CVVersionStorage::get_page(cv_id, request).map_err(|e| ApiError::invalid("cursor", "invalid", e))?;
+        Ok(page.map(CVVersionInfo::from))
+    }
+
+    pub fn get_version(cv_id: &str, user_id: &str, version: u32) -> Result<CVVersion, ApiError> {
+        Self::owned_cv(cv_id, user_id)?;
+        CVVersionStorage::get(cv_id, version).map_err(ApiError::from)
+    }
+
+    pub fn diff(cv_id: &str, user_id: &str, from_version: u32, to_version: u32) -> Result<CVDiff, ApiError> {
+        Self::owned_cv(cv_id, user_id)?;
+        let from = CVVersionStorage::get(cv_id, from_version)?;
+        let to = CVVersionStorage::get(cv_id, to_version)?;
+
+        Ok(CVDiff {
+            cv_id: cv_id.to_string(),
+            from_version,
+            to_version,
+            title_changed: from.title != to.title,
+            lines: line_diff(&from.content, &to.content),
+        })
+    }
+
+    /// Restores `version` as a new head; the versions in between are kept.
+    pub fn revert(cv_id: &str, user_id: &str, version: u32) -> Result<CV, ApiError> {
+        let mut cv = Self::owned_cv(cv_id, user_id)?;
+        if version == cv.version {
+            return Err(ApiError::invalid(
+                "version",
+                "current_version",
+                format!("Version {} is already the current version", version),
+            ));
+        }
+        let target = CVVersionStorage::get(cv_id, version)?;
+        cv.title = target.title;
+        cv.content = target.content;
+        // The file may have been deleted since.
+        cv.blob_id = target.blob_id.filter(|id| BlobStorage::get_blob(user_id, *id).is_ok());
+        Self::commit(cv, Some(version))
+    }
+
+    /// Attaches an uploaded file to `cv_id` (or detaches with `None`) as a
+    /// new version.
+    pub fn attach_blob(cv_id: &str, user_id: &str, blob_id: Option<u64>) -> Result<CV, ApiError> {
+        let mut cv = Self::owned_cv(cv_id, user_id)?;
+        if let Some(blob_id) = blob_id {
+            BlobStorage::get_blob(user_id, blob_id)?;
+        }
+        cv.blob_id = blob_id;
+        Self::commit(cv, None)
+    }
+
+    pub(crate) fn owned_cv(cv_id: &str, user_id: &str) -> Result<CV, ApiError> {
+        let cv = CVStorage::get_cv(cv_id)?;
+        if cv.user_id != user_id {
+            return Err(ApiError::Unauthorized("Access denied".to_string()));
+        }
+        Ok(cv)
+    }
+}
+
+/// Line-level diff of `old` against `new`, as a longest common subsequence
+/// of lines with the shared prefix and suffix trimmed off first.
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let line = |op, text: &str| DiffLine { op, text: text.to_string() };
+    let mut lines: Vec<DiffLine> = old[..prefix].iter().map(|t| line(DiffOp::Equal, t)).collect();
+
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_DIFF_CELLS {
+        lines.extend(old_mid.iter().map(|t| line(DiffOp::Removed, t)));
+        lines.extend(new_mid.iter().map(|t| line(DiffOp::Added, t)));
+    } else {
+        // lcs[i][j]: length of the LCS of old_mid[i..] and new_mid[j..].
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_mid[i] == new_mid[j] {
+                lines.push(line(DiffOp::Equal, old_mid[i]));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                lines.push(line(DiffOp::Removed, old_mid[i]));
+                i += 1;
+            } else {
+                lines.push(line(DiffOp::Added, new_mid[j]));
+                j += 1;
+            }
+        }
+        lines.extend(old_mid[i..].iter().map(|t| line(DiffOp::Removed, t)));
+        lines.extend(new_mid[j..].iter().map(|t| line(DiffOp::Added, t)));
+    }
+
+    lines.extend(old[old.len() - suffix..].iter().map(|t| line(DiffOp::Equal, t)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffOp::{Added, Equal, Removed};
+
+    fn ops(old: &str, new: &str) -> Vec<(DiffOp, String)> {
+        let lines = line_diff(old, new);
+        let (before, after) = sides(&lines);
+        assert_eq!(before, old.lines().collect::<Vec<_>>(), "old side of {:?}", lines);
+        assert_eq!(after, new.lines().collect::<Vec<_>>(), "new side of {:?}", lines);
+        lines.into_iter().map(|line| (line.op, line.text)).collect()
+    }
+
+    /// The texts `lines` turns into on each side.
+    fn sides(lines: &[DiffLine]) -> (Vec<&str>, Vec<&str>) {
+        let side = |skip: DiffOp| lines.iter().filter(|l| l.op != skip).map(|l| l.text.as_str()).collect();
+        (side(Added), side(Removed))
+    }
+
+    fn expect(lines: &[(DiffOp, &str)]) -> Vec<(DiffOp, String)> {
+        lines.iter().map(|(op, text)| (*op, text.to_string())).collect()
+    }
+
+    #[test]
+    fn identical_inputs_are_all_equal() {
+        assert_eq!(ops("a\nb\nc", "a\nb\nc"), expect(&[(Equal, "a"), (Equal, "b"), (Equal, "c")]));
+        assert_eq!(ops("", ""), expect(&[]));
+    }
+
+    #[test]
+    fn pure_insertions_and_deletions() {
+        assert_eq!(
+            ops("a\nc", "a\nb\nc"),
+            expect(&[(Equal, "a"), (Added, "b"), (Equal, "c")])
+        );
+        assert_eq!(
+            ops("a\nb\nc\nd", "a\nd"),
+            expect(&[(Equal, "a"), (Removed, "b"), (Removed, "c"), (Equal, "d")])
+        );
+        assert_eq!(ops("b", "a\nb"), expect(&[(Added, "a"), (Equal, "b")]));
+        assert_eq!(ops("a\nb", "a"), expect(&[(Equal, "a"), (Removed, "b")]));
+    }
+
+    #[test]
+    fn a_change_in_the_middle() {
+        assert_eq!(
+            ops("Name\nSkills: Rust\nEmail", "Name\nSkills: Rust, Go\nEmail"),
+            expect(&[(Equal, "Name"), (Removed, "Skills: Rust"), (Added, "Skills: Rust, Go"), (Equal, "Email")])
+        );
+        // Lines kept between two changes stay equal.
+        assert_eq!(
+            ops("a\nx\nb\ny\nc", "a\nX\nb\nY\nc"),
+            expect(&[
+                (Equal, "a"),
+                (Removed, "x"),
+                (Added, "X"),
+                (Equal, "b"),
+                (Removed, "y"),
+                (Added, "Y"),
+                (Equal, "c"),
+            ])
+        );
+    }
+
+    #[test]
+    fn empty_sides() {
+        assert_eq!(ops("", "a\nb"), expect(&[(Added, "a"), (Added, "b")]));
+        assert_eq!(ops("a\nb", ""), expect(&[(Removed, "a"), (Removed, "b")]));
+    }
+
+    #[test]
+    fn large_changes_fall_back_to_remove_then_add() {
+        let count = 1_000;
+        assert!((count + 1) * (count + 1) > MAX_DIFF_CELLS);
+        // Both middles contain "shared", which the LCS would keep as equal.
+        let side = |prefix: &str| {
+            let mut lines = vec!["head".to_string()];
+            lines.extend((0..count - 1).map(|i| format!("{}{}", prefix, i)));
+            lines.insert(count / 2, "shared".to_string());
+            lines.push("tail".to_string());
+            lines.join("\n")
+        };
+
+        let lines = ops(&side("old "), &side("new "));
+        assert_eq!(lines.len(), 2 + 2 * count);
+        assert_eq!(lines[0], (Equal, "head".to_string()));
+        assert!(lines[1..=count].iter().all(|(op, _)| *op == Removed));
+        assert!(lines[count + 1..=2 * count].iter().all(|(op, _)| *op == Added));
+        assert_eq!(lines[2 * count + 1], (Equal, "tail".to_string()));
+    }
+}
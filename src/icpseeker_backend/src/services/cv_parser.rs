@@ -0,0 +1,724 @@
+//! Fills a `CvDocument` from a pasted plain-text résumé.
+//!
+//! Sections are found by their headings (English and Indonesian). Within a
+//! section, bullet lines become bullet points, lines holding a date range
+//! give an entry its dates, and the remaining short lines name entries. This
+//! is best effort: whatever cannot be placed is dropped, and the result is
+//! meant to be reviewed through the section endpoints.
+
+use crate::extraction::normalize;
+use crate::models::cv_document::{
+    CertificationEntry, CvDocument, LanguageEntry, ProjectEntry, SkillEntry, WorkEntry,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Section {
+    Summary,
+    Work,
+    Skills,
+    Languages,
+    Certifications,
+    Projects,
+    /// A section the document has no place for, such as education.
+    Other,
+}
+
+/// Lowercase headings per section.
+const SECTION_HEADINGS: &[(Section, &[&str])] = &[
+    (Section::Summary, &[
+        "summary", "professional summary", "profile", "professional profile", "about me", "about",
+        "objective", "career objective", "ringkasan", "profil", "tentang saya",
+    ]),
+    (Section::Work, &[
+        "experience", "work experience", "professional experience", "employment",
+        "employment history", "work history", "career history", "pengalaman", "pengalaman kerja",
+        "riwayat pekerjaan",
+    ]),
+    (Section::Skills, &[
+        "skills", "technical skills", "core skills", "key skills", "competencies", "skills & tools",
+        "keahlian", "keterampilan", "kemampuan",
+    ]),
+    (Section::Languages, &["languages", "language skills", "bahasa", "kemampuan bahasa"]),
+    (Section::Certifications, &[
+        "certifications", "certification", "certificates", "licenses & certifications",
+        "sertifikasi", "sertifikat",
+    ]),
+    (Section::Projects, &["projects", "personal projects", "key projects", "portfolio", "proyek", "portofolio"]),
+    (Section::Other, &[
+        "education", "pendidikan", "riwayat pendidikan", "awards", "achievements", "honors & awards",
+        "publications", "volunteer experience", "volunteering", "organizations",
+        "organizational experience", "pengalaman organisasi", "organisasi", "courses", "training",
+        "pelatihan", "interests", "hobbies", "minat", "references", "referensi", "contact",
+        "contact information", "kontak", "personal details", "data diri", "data pribadi",
+        "penghargaan", "prestasi",
+    ]),
+];
+
+const MONTHS: &[(&str, u32)] = &[
+    ("january", 1), ("januari", 1), ("february", 2), ("februari", 2), ("march", 3), ("maret", 3),
+    ("april", 4), ("may", 5), ("mei", 5), ("june", 6), ("juni", 6), ("july", 7), ("juli", 7),
+    ("august", 8), ("agustus", 8), ("september", 9), ("october", 10), ("oktober", 10),
+    ("november", 11), ("december", 12), ("desember", 12),
+];
+
+const PRESENT_WORDS: &[&str] = &["present", "current", "now", "today", "sekarang", "kini"];
+
+const BULLET_MARKERS: &[char] = &['•', '-', '*', '·', '▪', '◦', '●', '○', '■', '►', '➢', '✓'];
+
+/// Words that mark the role half of "Role - Employer" style headers.
+const ROLE_WORDS: &[&str] = &[
+    "engineer", "developer", "manager", "intern", "analyst", "designer", "consultant", "lead",
+    "director", "officer", "staff", "specialist", "assistant", "scientist", "architect",
+    "administrator", "coordinator", "head", "executive", "programmer", "researcher", "associate",
+    "magang", "staf", "karyawan",
+];
+
+/// Non-bullet lines longer than this are description text, not headers.
+const MAX_HEADER_CHARS: usize = 100;
+
+pub fn parse(text: &str) -> CvDocument {
+    let text = normalize(text);
+    let mut sections: Vec<(Section, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        if let Some(section) = section_of(line) {
+            sections.push((section, Vec::new()));
+        } else if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    let mut document = CvDocument::default();
+    for (section, lines) in sections {
+        match section {
+            Section::Summary if document.summary.is_none() => document.summary = parse_summary(&lines),
+            Section::Work => document.work.extend(parse_work(&lines)),
+            Section::Skills => parse_skills(&lines, &mut document.skills),
+            Section::Languages => document.languages.extend(parse_languages(&lines)),
+            Section::Certifications => document.certifications.extend(parse_certifications(&lines)),
+            Section::Projects => document.projects.extend(parse_projects(&lines)),
+            _ => {}
+        }
+    }
+
+    let mut next_id = 0;
+    let mut id = || {
+        next_id += 1;
+        next_id
+    };
+    document.work.iter_mut().for_each(|e| e.id = id());
+    document.skills.iter_mut().for_each(|e| e.id = id());
+    document.languages.iter_mut().for_each(|e| e.id = id());
+    document.certifications.iter_mut().for_each(|e| e.id = id());
+    document.projects.iter_mut().for_each(|e| e.id = id());
+    document.next_entry_id = next_id;
+    document
+}
+
+fn section_of(line: &str) -> Option<Section> {
+    let key = line.trim_end_matches(':').trim().to_lowercase().replace(" and ", " & ");
+    if let Some((section, _)) = SECTION_HEADINGS.iter().find(|(_, headings)| headings.contains(&key.as_str())) {
+        return Some(*section);
+    }
+
+    // Unknown all-caps headings ("VOLUNTEER WORK") still end the previous
+    // section, so their lines are not read as part of it.
+    let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+    (letters >= 6
+        && line.chars().all(|c| c.is_uppercase() || c == ' ' || c == '&' || c == ':')
+        && line.split_whitespace().count() <= 4)
+        .then_some(Section::Other)
+}
+
+fn strip_bullet(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let marker = line.chars().next().filter(|c| BULLET_MARKERS.contains(c))?;
+    let rest = line[marker.len_utf8()..].trim();
+    (!rest.is_empty()).then_some(rest)
+}
+
+fn parse_summary(lines: &[&str]) -> Option<String> {
+    let paragraphs: Vec<String> = lines
+        .split(|line| line.trim().is_empty())
+        .map(|paragraph| {
+            paragraph.iter().map(|line| strip_bullet(line).unwrap_or(line)).collect::<Vec<_>>().join(" ")
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    (!paragraphs.is_empty()).then(|| paragraphs.join("\n"))
+}
+
+/// A date range found in a line, with the text around it.
+struct DateRange {
+    start: String,
+    end: Option<String>,
+    is_current: bool,
+    rest: String,
+}
+
+struct Token<'a> {
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+fn tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (at, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+        let word_char = c.is_alphanumeric() || c == '/' || c == '.';
+        match (start, word_char) {
+            (None, true) => start = Some(at),
+            (Some(s), false) => {
+                tokens.push(Token { text: &line[s..at], start: s, end: at });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+fn month_number(word: &str) -> Option<u32> {
+    let word = word.trim_end_matches('.').to_lowercase();
+    if word.chars().count() < 3 {
+        return None;
+    }
+    MONTHS.iter().find(|(name, _)| name.starts_with(&word)).map(|(_, month)| *month)
+}
+
+fn year(word: &str) -> Option<u32> {
+    let word = word.trim_end_matches('.');
+    if word.len() != 4 {
+        return None;
+    }
+    word.parse().ok().filter(|year| (1950..=2100).contains(year))
+}
+
+/// `03/2020` or `3/2020`.
+fn month_slash_year(word: &str) -> Option<(u32, u32)> {
+    let (month, rest) = word.split_once('/')?;
+    let month: u32 = month.parse().ok().filter(|m| (1..=12).contains(m))?;
+    Some((month, year(rest)?))
+}
+
+/// The first date range in `line`: one or two dates, where the second may
+/// be a word for "present". `None` when the line does not start a range
+/// with a date.
+fn parse_dates(line: &str) -> Option<DateRange> {
+    let tokens = tokens(line);
+    // (date, start, end); a `None` date means "present".
+    let mut found: Vec<(Option<String>, usize, usize)> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() && found.len() < 2 {
+        let token = &tokens[i];
+        let word = token.text.to_lowercase();
+        if let (Some(month), Some(next)) = (month_number(token.text), tokens.get(i + 1)) {
+            if let Some(year) = year(next.text) {
+                found.push((Some(format!("{:04}-{:02}", year, month)), token.start, next.end));
+                i += 2;
+                continue;
+            }
+        }
+        if let Some(year) = year(token.text) {
+            found.push((Some(format!("{:04}", year)), token.start, token.end));
+        } else if let Some((month, year)) = month_slash_year(token.text) {
+            found.push((Some(format!("{:04}-{:02}", year, month)), token.start, token.end));
+        } else if !found.is_empty() && PRESENT_WORDS.contains(&word.as_str()) {
+            found.push((None, token.start, token.end));
+        } else if !found.is_empty() && word == "saat" && tokens.get(i + 1).is_some_and(|t| t.text.eq_ignore_ascii_case("ini")) {
+            found.push((None, token.start, tokens[i + 1].end));
+            i += 1;
+        } else if !found.is_empty() && !is_range_word(&word) {
+            break;
+        }
+        i += 1;
+    }
+
+    let (first, range_start, first_end) = found.first()?.clone();
+    let start = first?;
+    let (end, is_current, range_end) = match found.get(1) {
+        Some((Some(date), _, end)) => (Some(date.clone()), false, *end),
+        Some((None, _, end)) => (None, true, *end),
+        None => (None, false, first_end),
+    };
+
+    let trim = |text: &str| {
+        text.trim_matches(|c: char| c.is_whitespace() || "-|,()·:/".contains(c)).to_string()
+    };
+    let before = trim(&line[..range_start]);
+    let after = trim(&line[range_end..]);
+    let rest = match (before.is_empty(), after.is_empty()) {
+        (false, false) => format!("{} - {}", before, after),
+        (false, true) => before,
+        _ => after,
+    };
+    Some(DateRange { start, end, is_current, rest })
+}
+
+fn is_range_word(word: &str) -> bool {
+    matches!(word, "to" | "until" | "till" | "hingga" | "sampai" | "s.d" | "s.d." | "sd")
+}
+
+fn has_role_word(text: &str) -> bool {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| ROLE_WORDS.contains(&word))
+}
+
+/// Splits "Role at Employer", "Employer | Role" and similar headers into
+/// (role, employer).
+fn split_header(text: &str) -> (String, String) {
+    for separator in [" at ", " @ ", " di "] {
+        if let Some((role, employer)) = text.split_once(separator) {
+            return (role.trim().to_string(), employer.trim().to_string());
+        }
+    }
+    for separator in [" | ", " - ", ", "] {
+        if let Some((first, second)) = text.split_once(separator) {
+            let (first, second) = (first.trim().to_string(), second.trim().to_string());
+            return if has_role_word(&second) && !has_role_word(&first) {
+                (second, first)
+            } else {
+                (first, second)
+            };
+        }
+    }
+    (text.trim().to_string(), String::new())
+}
+
+/// Fills whichever of role and employer is still missing from `text`.
+fn apply_header(entry: &mut WorkEntry, text: &str) {
+    if entry.role.is_empty() && entry.employer.is_empty() {
+        let (role, employer) = split_header(text);
+        entry.role = role;
+        entry.employer = employer;
+    } else if entry.employer.is_empty() {
+        entry.employer = text.to_string();
+    } else if entry.role.is_empty() {
+        entry.role = text.to_string();
+    }
+}
+
+/// Appends a wrapped line to the last bullet, or starts a bullet with it.
+fn continue_bullet(bullets: &mut Vec<String>, line: &str) {
+    match bullets.last_mut() {
+        Some(last) if line.starts_with(char::is_lowercase) => {
+            last.push(' ');
+            last.push_str(line);
+        }
+        _ => bullets.push(line.to_string()),
+    }
+}
+
+fn parse_work(lines: &[&str]) -> Vec<WorkEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<WorkEntry> = None;
+
+    for line in lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if let Some(bullet) = strip_bullet(line) {
+            current.get_or_insert_with(WorkEntry::default).bullets.push(bullet.to_string());
+        } else if let Some(dates) = parse_dates(line) {
+            if current.as_ref().is_some_and(|e| e.start_date.is_some() || !e.bullets.is_empty()) {
+                entries.extend(current.take());
+            }
+            let entry = current.get_or_insert_with(WorkEntry::default);
+            entry.start_date = Some(dates.start);
+            entry.end_date = dates.end;
+            entry.is_current = dates.is_current;
+            if !dates.rest.is_empty() {
+                apply_header(entry, &dates.rest);
+            }
+        } else if let Some(entry) = current.as_mut().filter(|e| {
+            (!e.bullets.is_empty() && line.starts_with(char::is_lowercase))
+                || line.chars().count() > MAX_HEADER_CHARS
+        }) {
+            continue_bullet(&mut entry.bullets, line);
+        } else {
+            let starts_new = current.as_ref().is_none_or(|e| {
+                !e.bullets.is_empty() || (!e.role.is_empty() && !e.employer.is_empty())
+            });
+            if starts_new {
+                entries.extend(current.take());
+            }
+            apply_header(current.get_or_insert_with(WorkEntry::default), line);
+        }
+    }
+    entries.extend(current);
+    entries.retain(|e| !e.role.is_empty() || !e.employer.is_empty());
+    entries
+}
+
+fn split_list(text: &str) -> impl Iterator<Item = &str> {
+    text.split([',', ';', '|', '•', '·'])
+        .map(|item| item.trim().trim_end_matches('.').trim())
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_skills(lines: &[&str], skills: &mut Vec<SkillEntry>) {
+    for line in lines {
+        let line = strip_bullet(line).unwrap_or(line.trim());
+        let (category, list) = match line.split_once(':') {
+            Some((category, list)) if category.chars().count() <= 40 && !list.trim().is_empty() => {
+                (Some(category.trim().to_string()), list)
+            }
+            _ => (None, line),
+        };
+        for name in split_list(list).filter(|name| name.chars().count() <= 60) {
+            if !skills.iter().any(|s| s.name.eq_ignore_ascii_case(name)) {
+                skills.push(SkillEntry { id: 0, name: name.to_string(), category: category.clone() });
+            }
+        }
+    }
+}
+
+/// "English (Fluent)", "English - Fluent", "English: Fluent" or "English".
+fn parse_language(item: &str) -> LanguageEntry {
+    let (name, proficiency) = if let Some((name, rest)) = item.split_once('(') {
+        (name, Some(rest.trim_end_matches(')')))
+    } else if let Some((name, proficiency)) = item.split_once(" - ").or_else(|| item.split_once(':')) {
+        (name, Some(proficiency))
+    } else {
+        (item, None)
+    };
+    LanguageEntry {
+        id: 0,
+        name: name.trim().to_string(),
+        proficiency: proficiency.map(str::trim).filter(|p| !p.is_empty()).map(str::to_string),
+    }
+}
+
+fn parse_languages(lines: &[&str]) -> Vec<LanguageEntry> {
+    let mut languages: Vec<LanguageEntry> = Vec::new();
+    for line in lines {
+        let line = strip_bullet(line).unwrap_or(line.trim());
+        for language in split_list(line).map(parse_language).filter(|l| !l.name.is_empty()) {
+            if !languages.iter().any(|l| l.name.eq_ignore_ascii_case(&language.name)) {
+                languages.push(language);
+            }
+        }
+    }
+    languages
+}
+
+fn parse_certifications(lines: &[&str]) -> Vec<CertificationEntry> {
+    let mut certifications = Vec::new();
+    for line in lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        let line = strip_bullet(line).unwrap_or(line);
+        let (text, date) = match parse_dates(line) {
+            Some(dates) => (dates.rest, Some(dates.end.unwrap_or(dates.start))),
+            None => (line.to_string(), None),
+        };
+        if text.is_empty() {
+            continue;
+        }
+        let (name, issuer) = match text.split_once(" - ").or_else(|| text.split_once(" | ")) {
+            Some((name, issuer)) => (name.trim().to_string(), Some(issuer.trim().to_string())),
+            None => (text, None),
+        };
+        certifications.push(CertificationEntry { id: 0, name, issuer, date });
+    }
+    certifications
+}
+
+fn is_url(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.starts_with("http://")
+        || word.starts_with("https://")
+        || word.starts_with("www.")
+        || word.starts_with("github.com/")
+        || word.starts_with("gitlab.com/")
+}
+
+/// Splits a project header into its name and the URL it mentions, if any.
+fn project_header(line: &str) -> (String, Option<String>) {
+    let mut url = None;
+    let mut name_words = Vec::new();
+    for word in line.split_whitespace() {
+        let bare = word.trim_matches(|c| c == '(' || c == ')' || c == '<' || c == '>');
+        if url.is_none() && is_url(bare) {
+            url = Some(bare.to_string());
+        } else {
+            name_words.push(word);
+        }
+    }
+    let name = name_words
+        .join(" ")
+        .trim_matches(|c: char| c.is_whitespace() || "-|,:()".contains(c))
+        .to_string();
+    (name, url)
+}
+
+fn parse_projects(lines: &[&str]) -> Vec<ProjectEntry> {
+    let mut projects = Vec::new();
+    let mut current: Option<ProjectEntry> = None;
+
+    for line in lines.iter().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        if let Some(bullet) = strip_bullet(line) {
+            current.get_or_insert_with(ProjectEntry::default).bullets.push(bullet.to_string());
+            continue;
+        }
+        match current.as_mut() {
+            Some(project) if !project.bullets.is_empty() && line.starts_with(char::is_lowercase) => {
+                continue_bullet(&mut project.bullets, line);
+            }
+            Some(project)
+                if project.bullets.is_empty()
+                    && (line.chars().count() > MAX_HEADER_CHARS || line.starts_with(char::is_lowercase)) =>
+            {
+                let description = project.description.get_or_insert_with(String::new);
+                if !description.is_empty() {
+                    description.push(' ');
+                }
+                description.push_str(line);
+            }
+            Some(project) if project.bullets.is_empty() && project.description.is_none() && !project.name.is_empty() => {
+                project.description = Some(line.to_string());
+            }
+            _ => {
+                projects.extend(current.take());
+                let (name, url) = project_header(line);
+                current = Some(ProjectEntry { name, url, ..ProjectEntry::default() });
+            }
+        }
+    }
+    projects.extend(current);
+    projects.retain(|p| !p.name.is_empty());
+    projects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dates(line: &str) -> Option<(String, Option<String>, bool, String)> {
+        parse_dates(line).map(|d| (d.start, d.end, d.is_current, d.rest))
+    }
+
+    fn range(start: &str, end: Option<&str>, is_current: bool, rest: &str) -> Option<(String, Option<String>, bool, String)> {
+        Some((start.to_string(), end.map(str::to_string), is_current, rest.to_string()))
+    }
+
+    fn header(role: &str, employer: &str) -> (String, String) {
+        (role.to_string(), employer.to_string())
+    }
+
+    fn work(employer: &str, role: &str, start: &str, end: Option<&str>, is_current: bool, bullets: &[&str]) -> WorkEntry {
+        WorkEntry {
+            id: 0,
+            employer: employer.to_string(),
+            role: role.to_string(),
+            start_date: Some(start.to_string()),
+            end_date: end.map(str::to_string),
+            is_current,
+            bullets: bullets.iter().map(|b| b.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_english_date_ranges() {
+        assert_eq!(dates("Jan 2021 - Present"), range("2021-01", None, true, ""));
+        assert_eq!(dates("March 2018 to December 2020"), range("2018-03", Some("2020-12"), false, ""));
+        assert_eq!(dates("2016 - 2018"), range("2016", Some("2018"), false, ""));
+        assert_eq!(dates("03/2020 - 11/2022"), range("2020-03", Some("2022-11"), false, ""));
+        assert_eq!(dates("Sept. 2019 - current"), range("2019-09", None, true, ""));
+        assert_eq!(dates("May 2020"), range("2020-05", None, false, ""));
+    }
+
+    #[test]
+    fn parses_indonesian_date_ranges() {
+        assert_eq!(dates("Agustus 2019 s.d. saat ini"), range("2019-08", None, true, ""));
+        assert_eq!(dates("Januari 2021 hingga sekarang"), range("2021-01", None, true, ""));
+        assert_eq!(dates("Mei 2017 sampai Desember 2018"), range("2017-05", Some("2018-12"), false, ""));
+        assert_eq!(dates("02/2015 sd 06/2016"), range("2015-02", Some("2016-06"), false, ""));
+    }
+
+    #[test]
+    fn keeps_the_text_around_a_date_range() {
+        assert_eq!(
+            dates("Tokopedia | Backend Developer | Mar 2018 - Dec 2020"),
+            range("2018-03", Some("2020-12"), false, "Tokopedia | Backend Developer")
+        );
+        assert_eq!(dates("PT Maju Jaya (Agustus 2019 - Juni 2021)"), range("2019-08", Some("2021-06"), false, "PT Maju Jaya"));
+        assert_eq!(dates("2019 - 2020, Jakarta"), range("2019", Some("2020"), false, "Jakarta"));
+        assert_eq!(dates("Data Analyst, 2020 - 2022, Remote"), range("2020", Some("2022"), false, "Data Analyst - Remote"));
+    }
+
+    #[test]
+    fn lines_without_dates_are_not_ranges() {
+        assert_eq!(dates("Led a team of 5 engineers"), None);
+        assert_eq!(dates("Senior Software Engineer at Acme Corp"), None);
+        assert_eq!(dates("Present at the meetup"), None);
+        assert_eq!(dates("Mar 99"), None);
+        assert_eq!(dates(""), None);
+    }
+
+    #[test]
+    fn splits_headers_into_role_and_employer() {
+        assert_eq!(split_header("Software Engineer at Google"), header("Software Engineer", "Google"));
+        assert_eq!(split_header("Data Scientist @ Gojek"), header("Data Scientist", "Gojek"));
+        assert_eq!(split_header("Staf Keuangan di PT Bank Mandiri"), header("Staf Keuangan", "PT Bank Mandiri"));
+        assert_eq!(split_header("Backend Developer - Tokopedia"), header("Backend Developer", "Tokopedia"));
+        // The half with a role word is the role, whichever side it is on.
+        assert_eq!(split_header("Tokopedia | Backend Developer"), header("Backend Developer", "Tokopedia"));
+        assert_eq!(split_header("Bukalapak, Product Manager"), header("Product Manager", "Bukalapak"));
+        assert_eq!(split_header("Freelance"), header("Freelance", ""));
+    }
+
+    #[test]
+    fn parses_an_english_resume() {
+        let document = parse(
+            "John Doe\n\
+             john@example.com\n\
+             \n\
+             SUMMARY\n\
+             Backend engineer with six years of experience\n\
+             building payment systems.\n\
+             \n\
+             WORK EXPERIENCE\n\
+             Senior Software Engineer at Acme Corp\n\
+             Jan 2021 \u{2013} Present\n\
+             \u{2022} Led the migration of the billing service to Rust,\n\
+             \x20 cutting p99 latency by 40%\n\
+             \u{2022} Mentored four engineers\n\
+             \n\
+             Tokopedia | Backend Developer | Mar 2018 \u{2013} Dec 2020\n\
+             - Built the order pipeline\n\
+             \n\
+             VOLUNTEER WORK\n\
+             Coach at Code Club\n\
+             \n\
+             EDUCATION\n\
+             Universitas Indonesia\n\
+             2014 - 2018\n\
+             \n\
+             Skills:\n\
+             Languages: Rust, Go, Python\n\
+             Tools: Docker, Kubernetes\n\
+             \n\
+             LANGUAGES\n\
+             English (Fluent), Indonesian (Native)\n\
+             \n\
+             CERTIFICATIONS\n\
+             AWS Certified Developer - Amazon Web Services, 2022\n\
+             \n\
+             PROJECTS\n\
+             ledger-sync (github.com/jdoe/ledger-sync)\n\
+             Synchronises ICP ledger blocks into Postgres.\n",
+        );
+
+        assert_eq!(
+            document.summary.as_deref(),
+            Some("Backend engineer with six years of experience building payment systems.")
+        );
+        assert_eq!(
+            document.work,
+            vec![
+                WorkEntry {
+                    id: 1,
+                    ..work(
+                        "Acme Corp",
+                        "Senior Software Engineer",
+                        "2021-01",
+                        None,
+                        true,
+                        &["Led the migration of the billing service to Rust, cutting p99 latency by 40%", "Mentored four engineers"],
+                    )
+                },
+                WorkEntry {
+                    id: 2,
+                    ..work("Tokopedia", "Backend Developer", "2018-03", Some("2020-12"), false, &["Built the order pipeline"])
+                },
+            ]
+        );
+        let skills: Vec<(&str, Option<&str>)> =
+            document.skills.iter().map(|s| (s.name.as_str(), s.category.as_deref())).collect();
+        assert_eq!(
+            skills,
+            vec![
+                ("Rust", Some("Languages")),
+                ("Go", Some("Languages")),
+                ("Python", Some("Languages")),
+                ("Docker", Some("Tools")),
+                ("Kubernetes", Some("Tools")),
+            ]
+        );
+        let languages: Vec<(&str, Option<&str>)> =
+            document.languages.iter().map(|l| (l.name.as_str(), l.proficiency.as_deref())).collect();
+        assert_eq!(languages, vec![("English", Some("Fluent")), ("Indonesian", Some("Native"))]);
+        assert_eq!(
+            document.certifications,
+            vec![CertificationEntry {
+                id: 10,
+                name: "AWS Certified Developer".to_string(),
+                issuer: Some("Amazon Web Services".to_string()),
+                date: Some("2022".to_string()),
+            }]
+        );
+        assert_eq!(
+            document.projects,
+            vec![ProjectEntry {
+                id: 11,
+                name: "ledger-sync".to_string(),
+                description: Some("Synchronises ICP ledger blocks into Postgres.".to_string()),
+                url: Some("github.com/jdoe/ledger-sync".to_string()),
+                bullets: Vec::new(),
+            }]
+        );
+        assert_eq!(document.next_entry_id, 11);
+    }
+
+    #[test]
+    fn parses_an_indonesian_resume() {
+        let document = parse(
+            "RINGKASAN\n\
+             Lulusan akuntansi yang teliti.\n\
+             \n\
+             PENGALAMAN KERJA\n\
+             Staf Keuangan di PT Bank Mandiri\n\
+             Agustus 2019 s.d. saat ini\n\
+             \u{2022} Menyusun laporan keuangan bulanan\n\
+             \u{2022} Rekonsiliasi bank\n\
+             \n\
+             Magang di PT Telkom Indonesia\n\
+             Jan 2019 - Jul 2019\n\
+             \n\
+             KEAHLIAN\n\
+             Microsoft Excel; SAP; Akuntansi\n\
+             \n\
+             BAHASA\n\
+             Bahasa Indonesia - Native, Inggris: Menengah\n",
+        );
+
+        assert_eq!(document.summary.as_deref(), Some("Lulusan akuntansi yang teliti."));
+        assert_eq!(
+            document.work,
+            vec![
+                WorkEntry {
+                    id: 1,
+                    ..work(
+                        "PT Bank Mandiri",
+                        "Staf Keuangan",
+                        "2019-08",
+                        None,
+                        true,
+                        &["Menyusun laporan keuangan bulanan", "Rekonsiliasi bank"],
+                    )
+                },
+                WorkEntry { id: 2, ..work("PT Telkom Indonesia", "Magang", "2019-01", Some("2019-07"), false, &[]) },
+            ]
+        );
+        let skills: Vec<&str> = document.skills.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(skills, vec!["Microsoft Excel", "SAP", "Akuntansi"]);
+        let languages: Vec<(&str, Option<&str>)> =
+            document.languages.iter().map(|l| (l.name.as_str(), l.proficiency.as_deref())).collect();
+        assert_eq!(languages, vec![("Bahasa Indonesia", Some("Native")), ("Inggris", Some("Menengah"))]);
+    }
+
+    #[test]
+    fn ignores_text_outside_known_sections() {
+        let document = parse("Jane Doe\n\nEDUCATION\nInstitut Teknologi Bandung\n2012 - 2016\n");
+        assert!(document.summary.is_none());
+        assert!(document.work.is_empty());
+        assert_eq!(document.next_entry_id, 0);
+    }
+}
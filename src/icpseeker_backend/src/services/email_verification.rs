@@ -0,0 +1,218 @@
+//! Confirms that a user controls their email address with a short code
+//! mailed to it. One code is pending per user; it expires, allows a few
+//! wrong guesses, and is only good for the address it was sent to.
+
+use candid::CandidType;
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::time;
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::models::types::{decode_record, encode_record, record_schema_version, string_to_storage_key};
+use crate::models::{StorageKey, UserProfile};
+use crate::services::mail::{MailMessage, MailService};
+use crate::storage::registry::{self, Memory};
+use crate::storage::UserStorage;
+use crate::types::errors::ApiError;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const CODE_TTL_MINUTES: u64 = 15;
+const CODE_TTL_NANOS: u64 = CODE_TTL_MINUTES * 60 * NANOS_PER_SECOND;
+/// A new code can be mailed this long after the previous one.
+const RESEND_AFTER_NANOS: u64 = 60 * NANOS_PER_SECOND;
+const MAX_ATTEMPTS: u8 = 5;
+/// Six-digit codes. Reducing 64 random bits modulo a million leaves a bias
+/// far too small to matter.
+const CODE_MODULUS: u64 = 1_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct StableEmailVerification {
+    pub email: String,
+    /// SHA-256 of the user id and the code; the code itself is not kept.
+    pub code_hash: Vec<u8>,
+    pub sent_at: u64,
+    pub expires_at: u64,
+    pub attempts: u8,
+}
+
+impl StableEmailVerification {
+    pub const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for StableEmailVerification {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported email verification schema version {:?}", version),
+        }
+    }
+}
+
+impl BoundedStorable for StableEmailVerification {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// What the caller needs to know about the code that was just sent.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct EmailVerificationStatus {
+    pub email: String,
+    pub expires_at: u64,
+    /// No new code is sent before this time.
+    pub resend_after: u64,
+    pub attempts_left: u8,
+}
+
+thread_local! {
+    static EMAIL_VERIFICATIONS: RefCell<StableBTreeMap<StorageKey, StableEmailVerification, Memory>> = RefCell::new(
+        registry::init_map(registry::EMAIL_VERIFICATIONS)
+    );
+}
+
+fn code_hash(user_id: &str, code: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(user_id.as_bytes());
+    hasher.update(b":");
+    hasher.update(code.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Identifies one verification mail to the provider without revealing the
+/// user id.
+fn idempotency_key(user_id: &str, sent_at: u64) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"email-verification:");
+    hasher.update(user_id.as_bytes());
+    hasher.update(b":");
+    hasher.update(sent_at.to_be_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn pending(key: &StorageKey) -> Option<StableEmailVerification> {
+    EMAIL_VERIFICATIONS.with(|map| map.borrow().get(key))
+}
+
+fn discard(key: &StorageKey) {
+    EMAIL_VERIFICATIONS.with(|map| map.borrow_mut().remove(key));
+}
+
+pub struct EmailVerificationService;
+
+impl EmailVerificationService {
+    /// Mails a fresh code to the user's current address, replacing any code
+    /// sent before.
+    pub async fn request(user_id: &str) -> Result<EmailVerificationStatus, ApiError> {
+        let user = UserStorage::get(user_id).ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+        if user.email_verified() {
+            return Err(ApiError::invalid("email", "already_verified", "This email address is already verified"));
+        }
+
+        let key = string_to_storage_key(user_id);
+        let now = time();
+        if let Some(previous) = pending(&key) {
+            let resend_after = previous.sent_at + RESEND_AFTER_NANOS;
+            if previous.email == user.email && now < resend_after {
+                return Err(ApiError::RateLimited {
+                    retry_after: (resend_after - now).div_ceil(NANOS_PER_SECOND),
+                });
+            }
+        }
+
+        let random_bytes = match raw_rand().await {
+            Ok((bytes,)) => bytes,
+            Err(e) => return Err(ApiError::Upstream(format!("Failed to generate a code: {:?}", e))),
+        };
+        let seed: [u8; 8] = random_bytes
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| ApiError::Upstream("Failed to generate a code: not enough randomness".to_string()))?;
+        let code = format!("{:06}", u64::from_be_bytes(seed) % CODE_MODULUS);
+
+        let sent_at = time();
+        let verification = StableEmailVerification {
+            email: user.email.clone(),
+            code_hash: code_hash(user_id, &code),
+            sent_at,
+            expires_at: sent_at + CODE_TTL_NANOS,
+            attempts: 0,
+        };
+        EMAIL_VERIFICATIONS.with(|map| map.borrow_mut().insert(key, verification.clone()));
+
+        let message = MailMessage {
+            to: user.email.clone(),
+            subject: "Your ICPSeeker verification code".to_string(),
+            text: format!(
+                "Your ICPSeeker verification code is {}. It expires in {} minutes.\n\n\
+                If you did not ask for this code, you can ignore this email.",
+                code, CODE_TTL_MINUTES
+            ),
+            idempotency_key: idempotency_key(user_id, sent_at),
+        };
+        if let Err(e) = MailService::send(message).await {
+            // Only drop the code if no newer request replaced it meanwhile.
+            if pending(&key).is_some_and(|current| current.code_hash == verification.code_hash) {
+                discard(&key);
+            }
+            return Err(e);
+        }
+
+        Ok(EmailVerificationStatus {
+            email: verification.email,
+            expires_at: verification.expires_at,
+            resend_after: sent_at + RESEND_AFTER_NANOS,
+            attempts_left: MAX_ATTEMPTS,
+        })
+    }
+
+    /// Checks `code` against the pending one and marks the address verified
+    /// on a match. A wrong code uses up one attempt.
+    pub fn confirm(user_id: &str, code: &str) -> Result<UserProfile, ApiError> {
+        let key = string_to_storage_key(user_id);
+        let mut verification = pending(&key)
+            .ok_or_else(|| ApiError::NotFound("No email verification is pending".to_string()))?;
+
+        if verification.expires_at <= time() {
+            discard(&key);
+            return Err(ApiError::invalid("code", "expired", "The code has expired; request a new one"));
+        }
+
+        let user = UserStorage::get(user_id).ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
+        if user.email != verification.email {
+            discard(&key);
+            return Err(ApiError::invalid(
+                "code",
+                "email_changed",
+                "The email address changed after the code was sent; request a new one",
+            ));
+        }
+
+        if code_hash(user_id, code.trim()) != verification.code_hash {
+            verification.attempts += 1;
+            if verification.attempts >= MAX_ATTEMPTS {
+                discard(&key);
+                return Err(ApiError::invalid(
+                    "code",
+                    "too_many_attempts",
+                    "Too many incorrect codes; request a new one",
+                ));
+            }
+            let attempts_left = MAX_ATTEMPTS - verification.attempts;
+            EMAIL_VERIFICATIONS.with(|map| map.borrow_mut().insert(key, verification));
+            return Err(ApiError::invalid(
+                "code",
+                "mismatch",
+                format!("Incorrect code; {} attempts left", attempts_left),
+            ));
+        }
+
+        discard(&key);
+        UserStorage::mark_email_verified(user_id, time()).map_err(ApiError::from)
+    }
+}
@@ -0,0 +1,177 @@
+//! Master key rotation without downtime. `start` adds a new key version at
+//! once, so every new ciphertext uses it, and a timer job then re-encrypts
+//! the stored records in batches, one message each, kept well inside the
+//! instruction limit. Old ciphertexts stay readable until the job has
+//! replaced them; when it finishes without failures the old keys are
+//! dropped.
+
+use candid::CandidType;
+use ic_cdk::api::{instruction_counter, time};
+use ic_stable_structures::{StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::time::Duration;
+
+use crate::encryption::{Encryption, KeyVersionInfo};
+use crate::models::types::{decode_record, encode_record, record_schema_version};
+use crate::storage::registry::{self, Memory};
+use crate::storage::{PayoutMethodKey, PayoutStorage};
+use crate::types::errors::ApiError;
+
+/// Instructions one batch may use: a twentieth of the 40B per-message
+/// limit, so a batch ends long before a single record could push it over.
+const BATCH_INSTRUCTIONS: u64 = 2_000_000_000;
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct KeyRotationStatus {
+    /// The master key version records are re-encrypted under.
+    pub key_version: u32,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    /// Records stored when the rotation started.
+    pub total: u64,
+    /// Records visited so far, including those already under the new key.
+    pub processed: u64,
+    pub re_encrypted: u64,
+    /// Records that could not be decrypted. Old keys are kept if any failed.
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+/// What controllers see of the encryption keys.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct EncryptionStatus {
+    pub key_versions: Vec<KeyVersionInfo>,
+    /// The latest rotation, running or finished.
+    pub rotation: Option<KeyRotationStatus>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+struct KeyRotationState {
+    rotation: Option<KeyRotationStatus>,
+    /// The last record the job visited.
+    cursor: Option<PayoutMethodKey>,
+}
+
+impl KeyRotationState {
+    const SCHEMA_VERSION: u8 = 1;
+
+    fn running(&self) -> Option<&KeyRotationStatus> {
+        self.rotation.as_ref().filter(|rotation| rotation.finished_at.is_none())
+    }
+}
+
+impl Storable for KeyRotationState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported key rotation schema version {:?}", version),
+        }
+    }
+}
+
+thread_local! {
+    static KEY_ROTATION: RefCell<StableCell<KeyRotationState, Memory>> = RefCell::new(
+        registry::init_cell(registry::KEY_ROTATION, KeyRotationState::default())
+    );
+}
+
+pub struct KeyRotationService;
+
+impl KeyRotationService {
+    /// Adds a new master key version and starts re-encrypting under it.
+    pub async fn start() -> Result<KeyRotationStatus, ApiError> {
+        if Self::state().running().is_some() {
+            return Err(ApiError::invalid("", "rotation_in_progress", "A key rotation is already running"));
+        }
+
+        let key_version = Encryption::rotate().await.map_err(ApiError::Internal)?;
+        // Should another rotation have started during the await, this one
+        // supersedes it; its timer stops at the version check in `step`.
+        let rotation = KeyRotationStatus {
+            key_version,
+            started_at: time(),
+            finished_at: None,
+            total: PayoutStorage::count(),
+            processed: 0,
+            re_encrypted: 0,
+            failed: 0,
+            last_error: None,
+        };
+        Self::save(KeyRotationState { rotation: Some(rotation.clone()), cursor: None })?;
+        Self::schedule(key_version);
+        Ok(rotation)
+    }
+
+    pub fn status() -> EncryptionStatus {
+        EncryptionStatus {
+            key_versions: Encryption::key_versions(),
+            rotation: Self::state().rotation,
+        }
+    }
+
+    /// Continues a rotation an upgrade interrupted; timers do not survive
+    /// upgrades. Needs `Encryption::ensure_ready`.
+    pub fn resume() {
+        if let Some(rotation) = Self::state().running() {
+            Self::schedule(rotation.key_version);
+        }
+    }
+
+    fn schedule(key_version: u32) {
+        ic_cdk_timers::set_timer(Duration::ZERO, move || Self::step(key_version));
+    }
+
+    /// Re-encrypts one batch, then schedules the next or finishes.
+    fn step(key_version: u32) {
+        let mut state = Self::state();
+        let Some(rotation) = state.rotation.as_mut().filter(|r| r.finished_at.is_none() && r.key_version == key_version)
+        else {
+            return;
+        };
+
+        let batch = PayoutStorage::re_encrypt_batch(state.cursor, key_version, || {
+            instruction_counter() > BATCH_INSTRUCTIONS
+        });
+        rotation.processed += batch.visited;
+        rotation.re_encrypted += batch.re_encrypted;
+        rotation.failed += batch.failed;
+        if batch.last_error.is_some() {
+            rotation.last_error = batch.last_error;
+        }
+        state.cursor = batch.next;
+
+        if state.cursor.is_none() {
+            rotation.finished_at = Some(time());
+            if rotation.failed == 0 {
+                if let Err(e) = Encryption::retire_keys_before(key_version) {
+                    rotation.last_error = Some(e);
+                }
+            }
+        } else {
+            Self::schedule(key_version);
+        }
+
+        if let Err(e) = Self::save(state) {
+            ic_cdk::println!("Key rotation {}: {}", key_version, e);
+        }
+    }
+
+    fn state() -> KeyRotationState {
+        KEY_ROTATION.with(|cell| cell.borrow().get().clone())
+    }
+
+    fn save(state: KeyRotationState) -> Result<(), ApiError> {
+        KEY_ROTATION.with(|cell| {
+            cell.borrow_mut()
+                .set(state)
+                .map(|_| ())
+                .map_err(|e| ApiError::Internal(format!("Failed to store the key rotation: {:?}", e)))
+        })
+    }
+}
@@ -0,0 +1,174 @@
+//! Transactional mail, sent as a JSON `POST` over an HTTPS outcall.
+//!
+//! The endpoint is set at runtime by an admin, so a local mock server can
+//! stand in for the real provider during development and tests. The body is
+//! `{ "from", "to": [..], "subject", "text" }` with the API key as a bearer
+//! token, which the common transactional-mail APIs accept.
+//!
+//! Every replica of the subnet makes the outcall, so the provider sees the
+//! same `POST` up to once per node. Each message carries an
+//! `Idempotency-Key` header, and the provider delivers it only once.
+
+use candid::{CandidType, Func, Nat};
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs,
+    TransformContext, TransformFunc,
+};
+use ic_stable_structures::{StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+use crate::auth::access::anyone;
+use crate::models::types::{decode_record, encode_record, record_schema_version};
+use crate::storage::registry::{self, Memory};
+use crate::types::errors::ApiError;
+
+/// Far above the cost of one small request on a 13-node subnet; whatever
+/// the call does not use is refunded.
+const MAIL_OUTCALL_CYCLES: u128 = 1_000_000_000;
+const MAX_RESPONSE_BYTES: u64 = 4 * 1024;
+
+/// Plain HTTP is only accepted for a mock server on the local replica.
+const LOCAL_ENDPOINT_PREFIXES: &[&str] = &["http://localhost", "http://127.0.0.1"];
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct MailConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    /// Sender address, e.g. `ICPSeeker <no-reply@example.com>`.
+    pub from: String,
+}
+
+impl MailConfig {
+    const SCHEMA_VERSION: u8 = 1;
+}
+
+impl Storable for MailConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(Self::SCHEMA_VERSION, self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        match record_schema_version(&bytes) {
+            Some(Self::SCHEMA_VERSION) => decode_record(&bytes),
+            version => panic!("Unsupported mail config schema version {:?}", version),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MailMessage {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    /// The same for every replica's copy of one message and different for
+    /// every message, e.g. derived from the recipient and the send time.
+    pub idempotency_key: String,
+}
+
+thread_local! {
+    static MAIL_CONFIG: RefCell<StableCell<MailConfig, Memory>> = RefCell::new(
+        registry::init_cell(registry::MAIL_CONFIG, MailConfig::default())
+    );
+}
+
+pub struct MailService;
+
+impl MailService {
+    pub fn configure(config: MailConfig) -> Result<(), ApiError> {
+        let endpoint = config.endpoint.trim();
+        if !endpoint.starts_with("https://")
+            && !LOCAL_ENDPOINT_PREFIXES.iter().any(|prefix| endpoint.starts_with(prefix))
+        {
+            return Err(ApiError::invalid(
+                "endpoint",
+                "invalid_url",
+                "Must be an https:// URL, or http://localhost for a local mock",
+            ));
+        }
+        if config.from.trim().is_empty() {
+            return Err(ApiError::invalid("from", "required", "This field is required"));
+        }
+
+        let config = MailConfig {
+            endpoint: endpoint.to_string(),
+            api_key: config.api_key.trim().to_string(),
+            from: config.from.trim().to_string(),
+        };
+        MAIL_CONFIG.with(|cell| cell.borrow_mut().set(config))
+            .map(|_| ())
+            .map_err(|e| ApiError::Internal(format!("Failed to store mail config: {:?}", e)))
+    }
+
+    /// The configured endpoint, or `None` before an admin set one.
+    pub fn config() -> Option<MailConfig> {
+        let config = MAIL_CONFIG.with(|cell| cell.borrow().get().clone());
+        (!config.endpoint.is_empty()).then_some(config)
+    }
+
+    pub async fn send(message: MailMessage) -> Result<(), ApiError> {
+        let config = Self::config()
+            .ok_or_else(|| ApiError::Internal("Mail delivery is not configured".to_string()))?;
+
+        let mut headers = vec![
+            HttpHeader {
+                name: "Content-Type".to_string(),
+                value: "application/json".to_string(),
+            },
+            HttpHeader {
+                name: "Idempotency-Key".to_string(),
+                value: message.idempotency_key,
+            },
+        ];
+        if !config.api_key.is_empty() {
+            headers.push(HttpHeader {
+                name: "Authorization".to_string(),
+                value: format!("Bearer {}", config.api_key),
+            });
+        }
+
+        let body = json!({
+            "from": config.from,
+            "to": [message.to],
+            "subject": message.subject,
+            "text": message.text,
+        });
+
+        let request = CanisterHttpRequestArgument {
+            url: config.endpoint,
+            method: HttpMethod::POST,
+            body: Some(body.to_string().into_bytes()),
+            max_response_bytes: Some(MAX_RESPONSE_BYTES),
+            transform: Some(TransformContext {
+                function: TransformFunc(Func {
+                    method: "transform_mail_response".to_string(),
+                    principal: ic_cdk::id(),
+                }),
+                context: vec![],
+            }),
+            headers,
+        };
+
+        match http_request(request, MAIL_OUTCALL_CYCLES).await {
+            Ok((response,)) if (Nat::from(200u32)..Nat::from(300u32)).contains(&response.status) => Ok(()),
+            Ok((response,)) => Err(ApiError::Upstream(format!(
+                "Mail endpoint answered with status {}",
+                response.status
+            ))),
+            Err((code, msg)) => Err(ApiError::Upstream(format!("Mail request failed: {:?} - {}", code, msg))),
+        }
+    }
+}
+
+/// Keeps only the status, so replicas agree even though providers put
+/// message ids and dates in the headers and body.
+#[ic_cdk::query(guard = "anyone")]
+fn transform_mail_response(args: TransformArgs) -> HttpResponse {
+    HttpResponse {
+        status: args.response.status,
+        headers: vec![],
+        body: vec![],
+    }
+}
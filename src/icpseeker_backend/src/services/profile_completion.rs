@@ -0,0 +1,143 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::cv::{CVAnalysisStatus, CV};
+use crate::models::education::EducationRecord;
+use crate::models::user::{UserProfile, PROFILE_STATUS_COMPLETE, PROFILE_STATUS_INCOMPLETE};
+use crate::storage::memory::{CVStorage, EducationStorage, PayoutStorage, UserStorage, WorkExperienceStorage};
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ChecklistItem {
+    pub key: String,
+    pub label: String,
+    /// Share of the completion score, in percent; all items add up to 100.
+    pub weight: u8,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProfileChecklist {
+    pub completion: u8,
+    pub status: u8,
+    /// Items still to do, heaviest first.
+    pub missing: Vec<ChecklistItem>,
+    pub completed: Vec<ChecklistItem>,
+}
+
+/// Everything the checks look at, read once per calculation.
+struct ProfileFacts {
+    user: UserProfile,
+    education: Option<EducationRecord>,
+    has_payout_method: bool,
+    has_work_history: bool,
+    cvs: Vec<CV>,
+}
+
+struct Check {
+    key: &'static str,
+    label: &'static str,
+    weight: u8,
+    done: fn(&ProfileFacts) -> bool,
+}
+
+const CHECKS: &[Check] = &[
+    Check { key: "name", label: "Add your full name", weight: 5, done: |f| !f.user.name.trim().is_empty() },
+    Check { key: "email", label: "Add your email address", weight: 10, done: |f| !f.user.email.trim().is_empty() },
+    Check {
+        key: "phone_number",
+        label: "Add your phone number",
+        weight: 10,
+        done: |f| !f.user.phone_number.trim().is_empty(),
+    },
+    Check {
+        key: "location",
+        label: "Add your city and country",
+        weight: 5,
+        done: |f| !f.user.city.trim().is_empty() && !f.user.country.trim().is_empty(),
+    },
+    Check {
+        key: "education",
+        label: "Add your education history",
+        weight: 10,
+        done: |f| f.education.as_ref().is_some_and(|e| !e.entries.is_empty()),
+    },
+    Check {
+        key: "education_grades",
+        label: "Add your grade to a university entry",
+        weight: 5,
+        done: |f| f.education.as_ref().is_some_and(|e| e.universities().any(|u| u.gpa.is_some())),
+    },
+    Check { key: "work_history", label: "Add your work experience", weight: 10, done: |f| f.has_work_history },
+    Check { key: "payout_method", label: "Add a payout method", weight: 15, done: |f| f.has_payout_method },
+    Check { key: "cv_uploaded", label: "Upload your CV", weight: 15, done: |f| !f.cvs.is_empty() },
+    Check {
+        key: "cv_analyzed",
+        label: "Have your CV analyzed",
+        weight: 15,
+        done: |f| f.cvs.iter().any(|cv| matches!(cv.ai_analysis_status, CVAnalysisStatus::Completed)),
+    },
+];
+
+pub struct ProfileCompletion;
+
+impl ProfileCompletion {
+    pub fn checklist(user_id: &str) -> Result<ProfileChecklist, String> {
+        let user = UserStorage::get(user_id).ok_or_else(|| "User not found".to_string())?;
+        Ok(Self::evaluate(user))
+    }
+
+    /// Recomputes and stores `profile_completion` and `status` of `user_id`.
+    /// Called by the write paths whenever a record that counts towards it
+    /// changes; does nothing for users that do not exist (yet).
+    pub fn refresh(user_id: &str) {
+        let Some(user) = UserStorage::get(user_id) else {
+            return;
+        };
+        let checklist = Self::evaluate(user.clone());
+        if checklist.completion != user.profile_completion || checklist.status != user.status {
+            UserStorage::set_completion(user_id, checklist.completion, checklist.status);
+        }
+    }
+
+    /// Recomputes every user; returns how many there are.
+    pub fn refresh_all() -> u64 {
+        let users = UserStorage::get_all();
+        for user in &users {
+            Self::refresh(&user.id);
+        }
+        users.len() as u64
+    }
+
+    fn evaluate(user: UserProfile) -> ProfileChecklist {
+        let facts = ProfileFacts {
+            education: EducationStorage::get_by_user(&user.id),
+            has_payout_method: PayoutStorage::has_any(&user.id),
+            has_work_history: !WorkExperienceStorage::get_by_user(&user.id).is_empty(),
+            cvs: CVStorage::get_user_cvs(&user.id).unwrap_or_default(),
+            user,
+        };
+
+        let mut missing = Vec::new();
+        let mut completed = Vec::new();
+        for check in CHECKS {
+            let item = ChecklistItem {
+                key: check.key.to_string(),
+                label: check.label.to_string(),
+                weight: check.weight,
+            };
+            if (check.done)(&facts) {
+                completed.push(item);
+            } else {
+                missing.push(item);
+            }
+        }
+        missing.sort_by_key(|item| std::cmp::Reverse(item.weight));
+
+        let completion = completed.iter().map(|item| item.weight).sum::<u8>().min(100);
+        ProfileChecklist {
+            completion,
+            status: if missing.is_empty() { PROFILE_STATUS_COMPLETE } else { PROFILE_STATUS_INCOMPLETE },
+            missing,
+            completed,
+        }
+    }
+}
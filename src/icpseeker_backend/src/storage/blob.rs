@@ -0,0 +1,353 @@
+//! Binary file storage with chunked uploads.
+//!
+//! `begin_upload` reserves an id and the declared size against the user's
+//! quota, chunks are written under that id as they arrive (in any order, and
+//! a retried chunk simply overwrites), and `commit` checks that every chunk is
+//! present and that the SHA-256 of the whole file matches before turning the
+//! upload into a blob. Uploads that are never committed expire.
+//!
+//! On commit the file's text is extracted and stored under the blob id.
+
+use ic_cdk::api::time;
+use ic_stable_structures::{StableBTreeMap, StableCell};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+
+use crate::extraction;
+use crate::models::blob::{
+    BlobChunk, BlobInfo, BlobUsage, ExtractedText, StableBlob, StableUpload, UploadProgress, UploadSession,
+    BLOB_CHUNK_SIZE, MAX_BLOB_SIZE, MAX_PENDING_UPLOADS, UPLOAD_TTL_NANOS, USER_BLOB_QUOTA,
+};
+use crate::storage::index::SecondaryIndex;
+use crate::storage::registry::{self, Memory};
+use crate::types::errors::StorageError;
+
+thread_local! {
+    static UPLOADS: RefCell<StableBTreeMap<u64, StableUpload, Memory>> = RefCell::new(
+        registry::init_map(registry::UPLOADS)
+    );
+
+    static BLOBS: RefCell<StableBTreeMap<u64, StableBlob, Memory>> = RefCell::new(
+        registry::init_map(registry::BLOBS)
+    );
+
+    static BLOB_CHUNKS: RefCell<StableBTreeMap<(u64, u32), BlobChunk, Memory>> = RefCell::new(
+        registry::init_map(registry::BLOB_CHUNKS)
+    );
+
+    static BLOB_ID_SEQ: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        registry::init_cell(registry::BLOB_ID_SEQ, 0)
+    );
+
+    static BLOB_BY_USER: RefCell<SecondaryIndex<u64>> = RefCell::new(
+        SecondaryIndex::init(registry::BLOB_BY_USER)
+    );
+
+    static BLOB_TEXT: RefCell<StableBTreeMap<u64, ExtractedText, Memory>> = RefCell::new(
+        registry::init_map(registry::BLOB_TEXT)
+    );
+}
+
+fn parse_sha256(hex: &str) -> Result<[u8; 32], StorageError> {
+    let invalid = || StorageError::ValidationError("sha256 must be 64 hex characters".to_string());
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut digest = [0u8; 32];
+    for (i, byte) in digest.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(digest)
+}
+
+fn next_blob_id() -> u64 {
+    BLOB_ID_SEQ.with(|seq| {
+        let mut seq = seq.borrow_mut();
+        let next = seq.get() + 1;
+        seq.set(next).expect("Failed to store blob id sequence");
+        next
+    })
+}
+
+fn read_chunks(id: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    BLOB_CHUNKS.with(|chunks| {
+        for (_, chunk) in chunks.borrow().range((id, 0)..=(id, u32::MAX)) {
+            bytes.extend_from_slice(&chunk.0);
+        }
+    });
+    bytes
+}
+
+fn remove_chunks(id: u64) {
+    BLOB_CHUNKS.with(|chunks| {
+        let mut chunks = chunks.borrow_mut();
+        let keys: Vec<(u64, u32)> = chunks
+            .range((id, 0)..=(id, u32::MAX))
+            .map(|(key, _)| key)
+            .collect();
+        for key in keys {
+            chunks.remove(&key);
+        }
+    });
+}
+
+pub struct BlobStorage;
+
+impl BlobStorage {
+    pub fn begin_upload(
+        user_id: &str,
+        file_name: String,
+        content_type: String,
+        size: u64,
+        sha256: &str,
+    ) -> Result<UploadSession, StorageError> {
+        if size == 0 || size > MAX_BLOB_SIZE {
+            return Err(StorageError::ValidationError(format!(
+                "File size must be between 1 and {} bytes",
+                MAX_BLOB_SIZE
+            )));
+        }
+        if file_name.trim().is_empty() || file_name.len() > 255 {
+            return Err(StorageError::ValidationError("File name must be 1 to 255 bytes".to_string()));
+        }
+        if content_type.len() > 255 {
+            return Err(StorageError::ValidationError("Content type is too long".to_string()));
+        }
+        let sha256 = parse_sha256(sha256)?;
+
+        Self::purge_expired_uploads();
+
+        let pending = Self::pending_uploads(user_id);
+        if pending.len() >= MAX_PENDING_UPLOADS {
+            return Err(StorageError::ValidationError(format!(
+                "At most {} uploads may be in progress",
+                MAX_PENDING_UPLOADS
+            )));
+        }
+        let used = Self::usage(user_id).used_bytes;
+        if used + size > USER_BLOB_QUOTA {
+            return Err(StorageError::ValidationError(format!(
+                "Storage quota exceeded ({} of {} bytes used)",
+                used, USER_BLOB_QUOTA
+            )));
+        }
+
+        let upload = StableUpload {
+            user_id: user_id.to_string(),
+            file_name,
+            content_type,
+            size,
+            sha256,
+            chunks_received: 0,
+            expires_at: time() + UPLOAD_TTL_NANOS,
+        };
+        let upload_id = next_blob_id();
+        let session = UploadSession {
+            upload_id,
+            chunk_size: BLOB_CHUNK_SIZE,
+            chunk_count: upload.chunk_count(),
+            expires_at: upload.expires_at,
+        };
+        UPLOADS.with(|uploads| uploads.borrow_mut().insert(upload_id, upload));
+        Ok(session)
+    }
+
+    pub fn upload_chunk(user_id: &str, upload_id: u64, index: u32, data: Vec<u8>) -> Result<UploadProgress, StorageError> {
+        let mut upload = Self::get_upload(user_id, upload_id)?;
+        let expected = upload.chunk_len(index).ok_or_else(|| {
+            StorageError::ValidationError(format!("Chunk index {} is out of range", index))
+        })?;
+        if data.len() != expected {
+            return Err(StorageError::ValidationError(format!(
+                "Chunk {} must be {} bytes, got {}",
+                index,
+                expected,
+                data.len()
+            )));
+        }
+
+        let replaced = BLOB_CHUNKS.with(|chunks| chunks.borrow_mut().insert((upload_id, index), BlobChunk(data)));
+        if replaced.is_none() {
+            upload.chunks_received += 1;
+            UPLOADS.with(|uploads| uploads.borrow_mut().insert(upload_id, upload.clone()));
+        }
+
+        Ok(UploadProgress {
+            upload_id,
+            chunks_received: upload.chunks_received,
+            chunk_count: upload.chunk_count(),
+        })
+    }
+
+    /// Verifies the upload and makes it a blob with the same id. A checksum
+    /// mismatch discards the upload, since its chunks cannot be trusted.
+    pub fn commit_upload(user_id: &str, upload_id: u64) -> Result<BlobInfo, StorageError> {
+        let upload = Self::get_upload(user_id, upload_id)?;
+        if upload.chunks_received != upload.chunk_count() {
+            return Err(StorageError::ValidationError(format!(
+                "Upload is incomplete ({} of {} chunks received)",
+                upload.chunks_received,
+                upload.chunk_count()
+            )));
+        }
+
+        let bytes = read_chunks(upload_id);
+        let digest: [u8; 32] = Sha256::digest(&bytes).into();
+        if digest != upload.sha256 {
+            Self::abort_upload(user_id, upload_id)?;
+            return Err(StorageError::ValidationError(
+                "Checksum mismatch; the upload was discarded".to_string(),
+            ));
+        }
+
+        let blob = StableBlob {
+            user_id: upload.user_id,
+            file_name: upload.file_name,
+            content_type: upload.content_type,
+            size: upload.size,
+            sha256: upload.sha256,
+            created_at: time(),
+        };
+        let text = extraction::extract(&blob.content_type, &bytes);
+        UPLOADS.with(|uploads| uploads.borrow_mut().remove(&upload_id));
+        BLOBS.with(|blobs| blobs.borrow_mut().insert(upload_id, blob.clone()));
+        BLOB_BY_USER.with(|index| index.borrow_mut().insert(user_id, upload_id));
+        BLOB_TEXT.with(|texts| texts.borrow_mut().insert(upload_id, text));
+
+        Ok(BlobInfo::new(upload_id, blob))
+    }
+
+    pub fn abort_upload(user_id: &str, upload_id: u64) -> Result<(), StorageError> {
+        Self::get_upload(user_id, upload_id)?;
+        UPLOADS.with(|uploads| uploads.borrow_mut().remove(&upload_id));
+        remove_chunks(upload_id);
+        Ok(())
+    }
+
+    pub fn get_blob(user_id: &str, blob_id: u64) -> Result<BlobInfo, StorageError> {
+        BLOBS.with(|blobs| blobs.borrow().get(&blob_id))
+            .filter(|blob| blob.user_id == user_id)
+            .map(|blob| BlobInfo::new(blob_id, blob))
+            .ok_or_else(|| StorageError::NotFound("File not found".to_string()))
+    }
+
+    pub fn get_user_blobs(user_id: &str) -> Vec<BlobInfo> {
+        let ids = BLOB_BY_USER.with(|index| index.borrow().keys(user_id));
+        BLOBS.with(|blobs| {
+            let blobs = blobs.borrow();
+            ids.into_iter()
+                .filter_map(|id| blobs.get(&id).map(|blob| BlobInfo::new(id, blob)))
+                .collect()
+        })
+    }
+
+    pub fn get_chunk(user_id: &str, blob_id: u64, index: u32) -> Result<Vec<u8>, StorageError> {
+        Self::get_blob(user_id, blob_id)?;
+        BLOB_CHUNKS.with(|chunks| chunks.borrow().get(&(blob_id, index)))
+            .map(|chunk| chunk.0)
+            .ok_or_else(|| StorageError::NotFound(format!("Chunk {} not found", index)))
+    }
+
+    pub fn get_text(blob_id: u64) -> Option<ExtractedText> {
+        BLOB_TEXT.with(|texts| texts.borrow().get(&blob_id))
+    }
+
+    pub fn get_user_text(user_id: &str, blob_id: u64) -> Result<ExtractedText, StorageError> {
+        Self::get_blob(user_id, blob_id)?;
+        Self::get_text(blob_id).ok_or_else(|| StorageError::NotFound("No text extracted for file".to_string()))
+    }
+
+    /// Extracts the text of every blob that has none yet, for blobs stored
+    /// before extraction existed.
+    pub fn extract_missing_text() -> u64 {
+        let missing: Vec<(u64, String)> = BLOBS.with(|blobs| {
+            blobs.borrow()
+                .iter()
+                .filter(|(id, _)| Self::get_text(*id).is_none())
+                .map(|(id, blob)| (id, blob.content_type))
+                .collect()
+        });
+        let count = missing.len() as u64;
+        for (id, content_type) in missing {
+            let text = extraction::extract(&content_type, &read_chunks(id));
+            BLOB_TEXT.with(|texts| texts.borrow_mut().insert(id, text));
+        }
+        count
+    }
+
+    pub fn delete_blob(user_id: &str, blob_id: u64) -> Result<(), StorageError> {
+        Self::get_blob(user_id, blob_id)?;
+        BLOBS.with(|blobs| blobs.borrow_mut().remove(&blob_id));
+        BLOB_TEXT.with(|texts| texts.borrow_mut().remove(&blob_id));
+        BLOB_BY_USER.with(|index| index.borrow_mut().remove(user_id, &blob_id));
+        remove_chunks(blob_id);
+        Ok(())
+    }
+
+    /// Moves the files of `from_user` to `to_user`, even past the quota of
+    /// `to_user`. Uploads still in progress are left to expire. Returns how
+    /// many files moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> u64 {
+        let ids = BLOB_BY_USER.with(|index| index.borrow().keys(from_user));
+        for id in &ids {
+            BLOBS.with(|blobs| {
+                let mut blobs = blobs.borrow_mut();
+                if let Some(mut blob) = blobs.get(id) {
+                    blob.user_id = to_user.to_string();
+                    blobs.insert(*id, blob);
+                }
+            });
+            BLOB_BY_USER.with(|index| {
+                let mut index = index.borrow_mut();
+                index.remove(from_user, id);
+                index.insert(to_user, *id);
+            });
+        }
+        ids.len() as u64
+    }
+
+    pub fn usage(user_id: &str) -> BlobUsage {
+        let committed: u64 = Self::get_user_blobs(user_id).iter().map(|blob| blob.size).sum();
+        let pending: u64 = Self::pending_uploads(user_id).iter().map(|(_, upload)| upload.size).sum();
+        BlobUsage {
+            used_bytes: committed + pending,
+            quota_bytes: USER_BLOB_QUOTA,
+        }
+    }
+
+    fn get_upload(user_id: &str, upload_id: u64) -> Result<StableUpload, StorageError> {
+        let upload = UPLOADS.with(|uploads| uploads.borrow().get(&upload_id))
+            .filter(|upload| upload.user_id == user_id)
+            .ok_or_else(|| StorageError::NotFound("Upload not found".to_string()))?;
+        if upload.expires_at <= time() {
+            return Err(StorageError::ValidationError("Upload has expired".to_string()));
+        }
+        Ok(upload)
+    }
+
+    fn pending_uploads(user_id: &str) -> Vec<(u64, StableUpload)> {
+        UPLOADS.with(|uploads| {
+            uploads.borrow()
+                .iter()
+                .filter(|(_, upload)| upload.user_id == user_id)
+                .collect()
+        })
+    }
+
+    fn purge_expired_uploads() {
+        let now = time();
+        let expired: Vec<u64> = UPLOADS.with(|uploads| {
+            uploads.borrow()
+                .iter()
+                .filter(|(_, upload)| upload.expires_at <= now)
+                .map(|(id, _)| id)
+                .collect()
+        });
+        for id in expired {
+            UPLOADS.with(|uploads| uploads.borrow_mut().remove(&id));
+            remove_chunks(id);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Stable secondary indexes from an owner (usually a user id) to the keys of
+//! the records it owns.
+//!
+//! Each index is a `StableBTreeMap<(owner, record_key), ()>`, so all keys of
+//! one owner are adjacent and can be read with a single range scan instead of
+//! iterating the whole record map. Storage types keep an index in sync by
+//! calling `on_write`/`remove` in the same call that writes the record;
+//! canister messages run to completion, so both changes land together or,
+//! on a trap, not at all.
+
+use ic_stable_structures::{BoundedStorable, StableBTreeMap};
+
+use crate::models::pagination::{Page, PageRequest};
+use crate::models::types::string_to_storage_key;
+use crate::models::StorageKey;
+use crate::storage::pagination::{decode_cursor, encode_cursor, page_range, prefix_end};
+use crate::storage::registry::{self, Memory, MemoryRegion};
+
+pub struct SecondaryIndex<K>
+where
+    K: BoundedStorable + Ord + Clone + Default,
+{
+    map: StableBTreeMap<(StorageKey, K), (), Memory>,
+}
+
+impl<K> SecondaryIndex<K>
+where
+    K: BoundedStorable + Ord + Clone + Default,
+{
+    pub fn init(region: MemoryRegion) -> Self {
+        Self {
+            map: registry::init_map(region),
+        }
+    }
+
+    pub fn insert(&mut self, owner: &str, key: K) {
+        self.map.insert((string_to_storage_key(owner), key), ());
+    }
+
+    pub fn remove(&mut self, owner: &str, key: &K) {
+        self.map.remove(&(string_to_storage_key(owner), key.clone()));
+    }
+
+    /// Records that `key` was written for `owner`, replacing the entry of
+    /// `previous_owner` (the owner of the value it overwrote, if any).
+    pub fn on_write(&mut self, previous_owner: Option<&str>, owner: &str, key: K) {
+        if let Some(previous_owner) = previous_owner {
+            self.remove(previous_owner, &key);
+        }
+        self.insert(owner, key);
+    }
+
+    /// Record keys owned by `owner`, in key order.
+    pub fn keys(&self, owner: &str) -> Vec<K> {
+        let owner_key = string_to_storage_key(owner);
+        self.map
+            .range((owner_key, K::default())..)
+            .take_while(|((o, _), _)| *o == owner_key)
+            .map(|((_, key), _)| key)
+            .collect()
+    }
+
+    /// One page of the record keys owned by `owner`. Cursors are the record
+    /// key the previous page ended on.
+    pub fn page(&self, owner: &str, request: &PageRequest) -> Result<Page<K>, String> {
+        let owner_key = string_to_storage_key(owner);
+        let after = match &request.cursor {
+            Some(cursor) => Some((owner_key, decode_cursor::<K>(cursor)?)),
+            None => None,
+        };
+
+        let (entries, last) = page_range(
+            &self.map,
+            (owner_key, K::default()),
+            prefix_end(&owner_key, owner_key.len()).map(|end| (end, K::default())),
+            after,
+            request.limit(),
+            request.direction(),
+        );
+
+        Ok(Page {
+            items: entries.into_iter().map(|((_, key), _)| key).collect(),
+            next_cursor: last.map(|(_, key)| encode_cursor(&key)),
+        })
+    }
+
+    pub fn first(&self, owner: &str) -> Option<K> {
+        let owner_key = string_to_storage_key(owner);
+        self.map
+            .range((owner_key, K::default())..)
+            .next()
+            .filter(|((o, _), _)| *o == owner_key)
+            .map(|((_, key), _)| key)
+    }
+
+    pub fn clear(&mut self) {
+        let entries: Vec<(StorageKey, K)> = self.map.iter().map(|(k, _)| k).collect();
+        for entry in entries {
+            self.map.remove(&entry);
+        }
+    }
+
+    /// Replaces the whole index with `entries` and returns how many there were.
+    pub fn rebuild(&mut self, entries: impl IntoIterator<Item = (String, K)>) -> u64 {
+        self.clear();
+        let mut count = 0;
+        for (owner, key) in entries {
+            self.insert(&owner, key);
+            count += 1;
+        }
+        count
+    }
+}
@@ -1,78 +1,304 @@
-use crate::validation::ValidationService;
-use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap};
+use crate::validation::{bank, phone, ValidationService};
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, StableCell, Storable};
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::ops::Bound;
+use sha2::{Digest, Sha256};
 use crate::models::{
     UserProfile,
     user::{StableUserProfile},
-    education::{EducationRecord, StableEducationRecord},
+    education::{EducationEntry, EducationRecord, StableEducationRecord},
     bank::{BankInformation, StableBankInformation},
-    cv::{CV, StableCV, CVAnalysisStatus},
+    cv::{CV, StableCV, CVAnalysisStatus, CVVersion, CVVersionAnalysis},
+    cv_document::CvDocument,
+    experience::{WorkExperience, MAX_WORK_EXPERIENCES},
+    payout::{BankAccount, PayoutMethod, PayoutMethodRecord, StablePayoutMethodRecord, MAX_PAYOUT_METHODS},
     chat::{ChatMessage, StableChatMessage, ChatSession, StableChatSession},
     FixedString, StorageKey
 };
 use crate::models::rate_limit::{UserAPIUsage, StableUserAPIUsage};
-use crate::types::errors::{StorageError, ChatStorageError};
-use crate::models::types::{string_to_storage_key, storage_key_to_string, string_to_content, string_to_fixed};
+use crate::types::errors::{ApiError, StorageError, ChatStorageError};
+use crate::models::types::{string_to_storage_key, string_to_fixed};
 use ic_cdk::api::time;
 use crate::models::RateLimitConfig;
+use crate::models::pagination::{Page, PageRequest, SortDirection};
+use crate::storage::index::SecondaryIndex;
+use crate::storage::pagination::{collect_pages, decode_cursor, encode_cursor, page_range, prefix_end};
+use crate::storage::registry::{self, Memory, MemoryRegion};
 
-const MEMORY_ID_USERS: MemoryId = MemoryId::new(0);
-const MEMORY_ID_EDUCATION: MemoryId = MemoryId::new(1);
-const MEMORY_ID_BANK: MemoryId = MemoryId::new(2);
-const MEMORY_ID_API_USAGE: MemoryId = MemoryId::new(3);
-const MEMORY_ID_CV: MemoryId = MemoryId::new(4);
-const MEMORY_ID_CHAT: MemoryId = MemoryId::new(5);
-const MEMORY_ID_CHAT_SESSION: MemoryId = MemoryId::new(6);
-type CVMemory = VirtualMemory<DefaultMemoryImpl>;
+thread_local! {
+    static USERS: RefCell<StableBTreeMap<StorageKey, StableUserProfile, Memory>> = RefCell::new(
+        registry::init_map(registry::USERS)
+    );
 
+    static BANK_INFO: RefCell<StableBTreeMap<StorageKey, StableBankInformation, Memory>> = RefCell::new(
+        registry::init_map(registry::BANK)
+    );
 
-thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
-        MemoryManager::init(DefaultMemoryImpl::default())
+    static EDUCATION_RECORDS: RefCell<StableBTreeMap<StorageKey, StableEducationRecord, Memory>> = RefCell::new(
+        registry::init_map(registry::EDUCATION)
     );
 
-    static USERS: RefCell<StableBTreeMap<StorageKey, StableUserProfile, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_USERS))
-        )
+    static CV_STORAGE: RefCell<StableBTreeMap<StorageKey, StableCV, Memory>> = RefCell::new(
+        registry::init_map(registry::CV)
     );
 
-    static BANK_INFO: RefCell<StableBTreeMap<StorageKey, StableBankInformation, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_BANK))
-        )
+    static CV_ID_SEQ: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        registry::init_cell(registry::CV_ID_SEQ, 0)
     );
-    static EDUCATION_RECORDS: RefCell<StableBTreeMap<StorageKey, StableEducationRecord, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_EDUCATION))
-        )
+
+    static CV_VERSIONS: RefCell<StableBTreeMap<(StorageKey, u32), CVVersion, Memory>> = RefCell::new(
+        registry::init_map(registry::CV_VERSIONS)
     );
 
-    static CV_STORAGE: RefCell<StableBTreeMap<StorageKey, StableCV, CVMemory>> = RefCell::new(
-        StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CV))
-        )
+    static CV_DOCUMENTS: RefCell<StableBTreeMap<StorageKey, CvDocument, Memory>> = RefCell::new(
+        registry::init_map(registry::CV_DOCUMENTS)
+    );
+
+    static WORK_EXPERIENCE: RefCell<StableBTreeMap<(StorageKey, u32), WorkExperience, Memory>> = RefCell::new(
+        registry::init_map(registry::WORK_EXPERIENCE)
+    );
+
+    /// The last work experience id handed out per user.
+    static WORK_EXPERIENCE_SEQ: RefCell<StableBTreeMap<StorageKey, u32, Memory>> = RefCell::new(
+        registry::init_map(registry::WORK_EXPERIENCE_SEQ)
+    );
+
+    static PAYOUT_METHODS: RefCell<StableBTreeMap<PayoutMethodKey, StablePayoutMethodRecord, Memory>> = RefCell::new(
+        registry::init_map(registry::PAYOUT_METHODS)
+    );
+
+    /// The last payout method id handed out per user.
+    static PAYOUT_METHOD_SEQ: RefCell<StableBTreeMap<StorageKey, u32, Memory>> = RefCell::new(
+        registry::init_map(registry::PAYOUT_METHOD_SEQ)
+    );
+
+    static API_USAGE_STORAGE: RefCell<StableBTreeMap<FixedString, StableUserAPIUsage, Memory>> = RefCell::new(
+        registry::init_map(registry::API_USAGE)
+    );
+
+    /// Messages written before `CHAT_MESSAGES`; only read by the migration.
+    static CHAT_STORAGE: RefCell<StableBTreeMap<FixedString, StableChatMessage, Memory>> = RefCell::new(
+        registry::init_map(registry::CHAT)
     );
 
-    static API_USAGE_STORAGE: RefCell<StableBTreeMap<FixedString, StableUserAPIUsage, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::new(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))))
+    static CHAT_MESSAGES: RefCell<StableBTreeMap<MessageKey, StableChatMessage, Memory>> = RefCell::new(
+        registry::init_map(registry::CHAT_MESSAGES)
     );
 
-    static CHAT_STORAGE: RefCell<StableBTreeMap<FixedString, StableChatMessage, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new(
-        StableBTreeMap::new(MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CHAT)))
+    static CHAT_MESSAGE_SEQ: RefCell<StableBTreeMap<StorageKey, u64, Memory>> = RefCell::new(
+        registry::init_map(registry::CHAT_MESSAGE_SEQ)
     );
 
-    static CHAT_SESSION_STORAGE: RefCell<StableBTreeMap<FixedString, StableChatSession, VirtualMemory<DefaultMemoryImpl>>> = RefCell::new({
-        let memory = MEMORY_MANAGER.with(|m| m.borrow().get(MEMORY_ID_CHAT_SESSION));
-        StableBTreeMap::init(memory)
+    /// Keyed by `session_key` of the session id.
+    static CHAT_SESSION_STORAGE: RefCell<StableBTreeMap<StorageKey, StableChatSession, Memory>> = RefCell::new(
+        registry::init_map(registry::CHAT_SESSION)
+    );
+
+    static EDUCATION_BY_USER: RefCell<SecondaryIndex<StorageKey>> = RefCell::new(
+        SecondaryIndex::init(registry::EDUCATION_BY_USER)
+    );
+
+    static BANK_BY_USER: RefCell<SecondaryIndex<StorageKey>> = RefCell::new(
+        SecondaryIndex::init(registry::BANK_BY_USER)
+    );
+
+    static CV_BY_USER: RefCell<SecondaryIndex<StorageKey>> = RefCell::new(
+        SecondaryIndex::init(registry::CV_BY_USER)
+    );
+
+    static CHAT_SESSION_BY_USER: RefCell<SecondaryIndex<StorageKey>> = RefCell::new(
+        SecondaryIndex::init(registry::CHAT_SESSION_BY_USER)
+    );
+}
+
+/// The next id of one of `user`'s records, from the per-user counter in
+/// `counters`. Ids are never reused, even after the newest record is
+/// deleted, so a client holding an old id cannot reach a new record.
+/// `last_stored` covers records stored before the counter existed.
+fn next_user_seq(counters: &RefCell<StableBTreeMap<StorageKey, u32, Memory>>, user: StorageKey, last_stored: u32) -> u32 {
+    let mut counters = counters.borrow_mut();
+    let next = counters.get(&user).unwrap_or(0).max(last_stored) + 1;
+    counters.insert(user, next);
+    next
+}
+
+/// Writes an education record and its `EDUCATION_BY_USER` entry together.
+fn put_education(key: StorageKey, record: StableEducationRecord) {
+    let owner = record.user_id.clone();
+    let previous = EDUCATION_RECORDS.with(|records| records.borrow_mut().insert(key, record));
+    EDUCATION_BY_USER.with(|index| {
+        index.borrow_mut().on_write(previous.as_ref().map(|p| p.user_id.as_str()), &owner, key)
     });
+}
 
+/// Writes a CV and its `CV_BY_USER` entry together.
+fn put_cv(key: StorageKey, cv: StableCV) {
+    let owner = cv.user_id.clone();
+    let previous = CV_STORAGE.with(|storage| storage.borrow_mut().insert(key, cv));
+    CV_BY_USER.with(|index| {
+        index.borrow_mut().on_write(previous.as_ref().map(|p| p.user_id.as_str()), &owner, key)
+    });
+}
+
+/// Writes a chat session and its `CHAT_SESSION_BY_USER` entry together.
+fn put_chat_session(key: StorageKey, session: StableChatSession) {
+    let owner = session.user_id.clone();
+    let previous = CHAT_SESSION_STORAGE.with(|storage| storage.borrow_mut().insert(key, session));
+    CHAT_SESSION_BY_USER.with(|index| {
+        index.borrow_mut().on_write(previous.as_ref().map(|p| p.user_id.as_str()), &owner, key)
+    });
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct IndexRebuildReport {
+    pub education: u64,
+    pub bank: u64,
+    pub cv: u64,
+    pub chat_sessions: u64,
+}
+
+/// Rebuilds every per-user index from the record maps. Needed once for data
+/// written before the indexes existed, and as a repair tool afterwards.
+pub fn rebuild_user_indexes() -> IndexRebuildReport {
+    IndexRebuildReport {
+        education: EDUCATION_BY_USER.with(|index| {
+            let entries: Vec<(String, StorageKey)> = EDUCATION_RECORDS
+                .with(|records| records.borrow().iter().map(|(k, r)| (r.user_id, k)).collect());
+            index.borrow_mut().rebuild(entries)
+        }),
+        bank: BANK_BY_USER.with(|index| {
+            let entries: Vec<(String, StorageKey)> = BANK_INFO
+                .with(|info| info.borrow().iter().map(|(k, i)| (i.user_id, k)).collect());
+            index.borrow_mut().rebuild(entries)
+        }),
+        cv: CV_BY_USER.with(|index| {
+            let entries: Vec<(String, StorageKey)> = CV_STORAGE
+                .with(|storage| storage.borrow().iter().map(|(k, cv)| (cv.user_id, k)).collect());
+            index.borrow_mut().rebuild(entries)
+        }),
+        chat_sessions: CHAT_SESSION_BY_USER.with(|index| {
+            let entries: Vec<(String, StorageKey)> = CHAT_SESSION_STORAGE
+                .with(|storage| storage.borrow().iter().map(|(k, s)| (s.user_id, k)).collect());
+            index.borrow_mut().rebuild(entries)
+        }),
+    }
+}
+
+/// Removes every entry from `map`. `StableBTreeMap::clear` consumes the map,
+/// so the keys are collected and removed one by one instead.
+fn clear_map<K, V>(map: &RefCell<StableBTreeMap<K, V, Memory>>)
+where
+    K: BoundedStorable + Ord + Clone,
+    V: BoundedStorable,
+{
+    let mut map = map.borrow_mut();
+    let keys: Vec<K> = map.iter().map(|(k, _)| k).collect();
+    for key in keys {
+        map.remove(&key);
+    }
+}
+
+/// Rejects records that do not fit in their map's value slot, which the map
+/// would otherwise trap on.
+fn ensure_fits<V: BoundedStorable>(record: &V) -> Result<(), StorageError> {
+    let size = record.to_bytes().len();
+    if size > V::MAX_SIZE as usize {
+        return Err(StorageError::ValidationError(format!(
+            "Record is too large ({} bytes, max {} bytes)",
+            size,
+            V::MAX_SIZE
+        )));
+    }
+    Ok(())
+}
+
+/// Raw value type used to reopen a map that still holds fixed-width records.
+/// `N` must equal the `MAX_SIZE` the map was created with.
+struct LegacyRecord<const N: u32>(Vec<u8>);
+
+impl<const N: u32> Storable for LegacyRecord<N> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(bytes.into_owned())
+    }
+}
+
+impl<const N: u32> BoundedStorable for LegacyRecord<N> {
+    const MAX_SIZE: u32 = N;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+/// Reads `max_value_size` from the header of the `StableBTreeMap` in `memory`,
+/// if there is one.
+fn stored_max_value_size(memory: &Memory) -> Option<u32> {
+    use ic_stable_structures::Memory;
+
+    if memory.size() == 0 {
+        return None;
+    }
+    let mut header = [0u8; 12];
+    memory.read(0, &mut header);
+    if &header[0..3] != b"BTR" {
+        return None;
+    }
+    Some(u32::from_le_bytes(header[8..12].try_into().unwrap()))
+}
+
+/// Rewrites a map created with the fixed-width layout (`N` bytes per value)
+/// into the versioned encoding, in the same memory.
+///
+/// The map header pins the value size it was created with, so the entries are
+/// read out, the map is recreated with `V::MAX_SIZE` and the entries are
+/// written back; `V::from_bytes` takes care of the legacy decoding.
+fn rewrite_legacy_map<K, V, const N: u32>(region: MemoryRegion) -> u64
+where
+    K: BoundedStorable + Ord + Clone,
+    V: BoundedStorable,
+{
+    let memory = registry::memory(region);
+    if stored_max_value_size(&memory) != Some(N) {
+        return 0;
+    }
+
+    let entries: Vec<(K, LegacyRecord<N>)> =
+        StableBTreeMap::<K, LegacyRecord<N>, _>::load(memory.clone()).iter().collect();
+
+    let mut map = StableBTreeMap::<K, V, _>::new(memory);
+    for (key, legacy) in &entries {
+        map.insert(key.clone(), V::from_bytes(Cow::Owned(legacy.0.clone())));
+    }
+    entries.len() as u64
+}
+
+/// Converts every map still using the fixed-width record layout to the
+/// versioned encoding. Maps that are empty or already converted are left
+/// alone, so this is safe to run on every upgrade.
+///
+/// Must run before any of the maps above is first touched: they are opened
+/// lazily, and opening a legacy map with the new value size traps.
+pub fn migrate_fixed_width_records() -> u64 {
+    rewrite_legacy_map::<StorageKey, StableUserProfile, { StableUserProfile::LEGACY_MAX_SIZE }>(registry::USERS)
+        + rewrite_legacy_map::<StorageKey, StableEducationRecord, { StableEducationRecord::LEGACY_MAX_SIZE }>(registry::EDUCATION)
+        + rewrite_legacy_map::<StorageKey, StableBankInformation, { StableBankInformation::LEGACY_MAX_SIZE }>(registry::BANK)
+        + rewrite_legacy_map::<FixedString, StableUserAPIUsage, { StableUserAPIUsage::LEGACY_MAX_SIZE }>(registry::API_USAGE)
+        + rewrite_legacy_map::<StorageKey, StableCV, { StableCV::LEGACY_MAX_SIZE }>(registry::CV)
+        + rewrite_legacy_map::<FixedString, StableChatMessage, { StableChatMessage::LEGACY_MAX_SIZE }>(registry::CHAT)
+        + rewrite_legacy_map::<FixedString, StableChatSession, { StableChatSession::LEGACY_MAX_SIZE }>(registry::CHAT_SESSION)
 }
 
 pub struct UserStorage;
 
 impl UserStorage {
+    pub fn clear() {
+        USERS.with(clear_map);
+    }
+
     pub fn exists(id: &str) -> bool {
         let key = string_to_storage_key(id);
         USERS.with(|users| users.borrow().contains_key(&key))
@@ -86,22 +312,25 @@ impl UserStorage {
         })
     }
 
-     pub fn save_with_validation(user: UserProfile) -> Result<(), StorageError> {
-        if user.name.trim().is_empty() {
-            return Err(StorageError::ValidationError("Name cannot be empty".to_string()));
-        }
-        if user.email.trim().is_empty() {
-            return Err(StorageError::ValidationError("Email cannot be empty".to_string()));
-        }
-        if user.phone_number.trim().is_empty() {
-            return Err(StorageError::ValidationError("Phone number cannot be empty".to_string()));
-        }
-        if user.city.trim().is_empty() {
-            return Err(StorageError::ValidationError("City cannot be empty".to_string()));
-        }
-        if user.country.trim().is_empty() {
-            return Err(StorageError::ValidationError("Country cannot be empty".to_string()));
-        }
+    pub fn get_all() -> Vec<UserProfile> {
+        USERS.with(|users| {
+            users.borrow().iter()
+                .map(|(_, stable_user)| stable_user.into())
+                .collect()
+        })
+    }
+
+    pub fn delete(id: &str) -> Result<(), StorageError> {
+        let key = string_to_storage_key(id);
+        USERS.with(|users| {
+            users.borrow_mut().remove(&key)
+                .map(|_| ())
+                .ok_or_else(|| StorageError::NotFound("User not found".to_string()))
+        })
+    }
+
+    pub fn save_with_validation(user: UserProfile) -> Result<(), StorageError> {
+        ValidationService::validate_user(&user)?;
 
         let key = string_to_storage_key(&user.id);
         
@@ -111,16 +340,17 @@ impl UserStorage {
         }
         
         let stable_user = StableUserProfile::from(user);
+        ensure_fits(&stable_user)?;
         
-        USERS.with(|users| {
-            users.borrow_mut().insert(key, stable_user);
-            Ok(())
-        })
+        USERS.with(|users| users.borrow_mut().insert(key, stable_user));
+        Ok(())
     }
 
     pub fn update_with_validation(user: UserProfile) -> Result<(), StorageError> {
+        ValidationService::validate_user(&user)?;
         let key = string_to_storage_key(&user.id);
         let stable_user = StableUserProfile::from(user.clone());
+        ensure_fits(&stable_user)?;
         
         USERS.with(|users| {
             if !users.borrow().contains_key(&key) {
@@ -128,8 +358,57 @@ impl UserStorage {
             }
             users.borrow_mut().insert(key, stable_user);
             Ok(())
+        })?;
+        Ok(())
+    }
+
+    pub fn mark_email_verified(id: &str, verified_at: u64) -> Result<UserProfile, StorageError> {
+        let key = string_to_storage_key(id);
+        USERS.with(|users| {
+            let mut users = users.borrow_mut();
+            let mut user = users.get(&key)
+                .ok_or_else(|| StorageError::NotFound("User not found".to_string()))?;
+            user.email_verified_at = Some(verified_at);
+            users.insert(key, user.clone());
+            Ok(user.into())
         })
     }
+
+    /// Rewrites stored phone numbers into E.164. Returns how many were
+    /// rewritten and how many could not be parsed and were left as they are.
+    pub fn canonicalize_phone_numbers() -> (u64, u64) {
+        let (mut rewritten, mut unparsed) = (0, 0);
+        USERS.with(|users| {
+            let mut users = users.borrow_mut();
+            let keys: Vec<StorageKey> = users.iter().map(|(key, _)| key).collect();
+            for key in keys {
+                let Some(mut user) = users.get(&key) else { continue };
+                match phone::to_e164(&user.phone_number, &user.country) {
+                    Ok(canonical) if canonical != user.phone_number => {
+                        user.phone_number = canonical;
+                        users.insert(key, user);
+                        rewritten += 1;
+                    }
+                    Ok(_) => {}
+                    Err(_) => unparsed += 1,
+                }
+            }
+        });
+        (rewritten, unparsed)
+    }
+
+    /// Stores computed completion fields without triggering a refresh.
+    pub fn set_completion(id: &str, profile_completion: u8, status: u8) {
+        let key = string_to_storage_key(id);
+        USERS.with(|users| {
+            let mut users = users.borrow_mut();
+            if let Some(mut user) = users.get(&key) {
+                user.profile_completion = profile_completion;
+                user.status = status;
+                users.insert(key, user);
+            }
+        });
+    }
 }
 
 pub struct EducationStorage;
@@ -137,15 +416,15 @@ pub struct EducationStorage;
 impl EducationStorage {
     pub fn exists(id: &str) -> bool {
         let key = string_to_storage_key(id);
-        BANK_INFO.with(|info| info.borrow().contains_key(&key))
+        EDUCATION_RECORDS.with(|records| records.borrow().contains_key(&key))
     }
 
     pub fn save(record: EducationRecord) -> Result<(), String> {
         let stable_record: StableEducationRecord = record.into();
-        EDUCATION_RECORDS.with(|records| {
-            records.borrow_mut().insert(stable_record.id, stable_record);
-            Ok(())
-        })
+        ensure_fits(&stable_record).map_err(|e| e.to_string())?;
+        let key = string_to_storage_key(&stable_record.id);
+        put_education(key, stable_record);
+        Ok(())
     }
 
     pub fn get(id: &str) -> Option<EducationRecord> {
@@ -156,12 +435,10 @@ impl EducationStorage {
         })
     }
 
-    pub fn get_by_user(user_id: &str) -> Option<EducationRecord> { // Changed return type
-        let user_key = string_to_storage_key(user_id);
+    pub fn get_by_user(user_id: &str) -> Option<EducationRecord> {
+        let key = EDUCATION_BY_USER.with(|index| index.borrow().first(user_id))?;
         EDUCATION_RECORDS.with(|storage| {
-            storage.borrow().iter()
-                .find(|(_, record)| record.user_id == user_key)
-                .map(|(_, record)| record.into())
+            storage.borrow().get(&key).map(|record| record.into())
         })
     }
 
@@ -172,24 +449,76 @@ impl EducationStorage {
         }
     
         let stable_record = StableEducationRecord::from(record);
-        EDUCATION_RECORDS.with(|records| {
-            records.borrow_mut().insert(key, stable_record);
-            Ok(())
-        })
+        ensure_fits(&stable_record)?;
+        put_education(key, stable_record);
+        Ok(())
     }
 
     pub fn update(record: EducationRecord) -> Result<(), String> {
         let stable_record: StableEducationRecord = record.into();
+        ensure_fits(&stable_record).map_err(|e| e.to_string())?;
+        let key = string_to_storage_key(&stable_record.id);
+        if !EDUCATION_RECORDS.with(|records| records.borrow().contains_key(&key)) {
+            return Err("Education record not found".to_string());
+        }
+        put_education(key, stable_record);
+        Ok(())
+    }
+
+    /// Users whose best normalized university grade is at least
+    /// `min_normalized`, for filtering candidates by grade.
+    pub fn user_ids_with_min_grade(min_normalized: f32) -> Vec<String> {
         EDUCATION_RECORDS.with(|records| {
-            if records.borrow().contains_key(&stable_record.id) {
-                records.borrow_mut().insert(stable_record.id, stable_record);
-                Ok(())
-            } else {
-                Err("Education record not found".to_string())
-            }
+            records
+                .borrow()
+                .iter()
+                .map(|(_, record)| EducationRecord::from(record))
+                .filter(|record| record.best_normalized_grade().is_some_and(|grade| grade >= min_normalized))
+                .map(|record| record.user_id)
+                .collect()
         })
     }
 
+    /// Re-encodes every record in the current schema, converting flattened
+    /// records into entries on the way.
+    pub fn rewrite_all() -> u64 {
+        let records: Vec<(StorageKey, StableEducationRecord)> =
+            EDUCATION_RECORDS.with(|records| records.borrow().iter().collect());
+        let count = records.len() as u64;
+        for (key, record) in records {
+            put_education(key, record);
+        }
+        count
+    }
+
+    /// Moves the education of `from_user` to `to_user`. When `to_user` has
+    /// a record already, the entries are appended to it under new ids and
+    /// the record of `from_user` is dropped. Returns how many entries moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> u64 {
+        let Some(mut moved) = Self::get_by_user(from_user) else {
+            return 0;
+        };
+        let count = moved.entries.len() as u64;
+        match Self::get_by_user(to_user) {
+            Some(mut target) => {
+                for entry in moved.entries {
+                    target.next_entry_id += 1;
+                    target.entries.push(EducationEntry { id: target.next_entry_id, details: entry.details });
+                }
+                target.updated_at = time();
+                let moved_key = string_to_storage_key(&moved.id);
+                EDUCATION_RECORDS.with(|records| records.borrow_mut().remove(&moved_key));
+                EDUCATION_BY_USER.with(|index| index.borrow_mut().remove(from_user, &moved_key));
+                put_education(string_to_storage_key(&target.id), target.into());
+            }
+            None => {
+                moved.user_id = to_user.to_string();
+                put_education(string_to_storage_key(&moved.id), moved.into());
+            }
+        }
+        count
+    }
+
     pub fn save_with_validation(record: EducationRecord) -> Result<(), StorageError> {
         if UserStorage::get(&record.user_id).is_none() {
             return Err(StorageError::InvalidReference(
@@ -210,127 +539,392 @@ impl EducationStorage {
 
 }
 
-pub struct BankStorage;
-
+pub struct WorkExperienceStorage;
 
-impl BankStorage {
-    pub fn save(info: BankInformation) -> Result<(), String> {
-        let stable_info: StableBankInformation = info.into();
-        BANK_INFO.with(|bank_info| {
-            bank_info.borrow_mut().insert(stable_info.id, stable_info);
-            Ok(())
+impl WorkExperienceStorage {
+    /// Every role of `user_id`, in id order.
+    pub fn get_by_user(user_id: &str) -> Vec<WorkExperience> {
+        let key = string_to_storage_key(user_id);
+        WORK_EXPERIENCE.with(|experiences| {
+            experiences.borrow().range((key, 0)..=(key, u32::MAX)).map(|(_, e)| e).collect()
         })
     }
 
-    pub fn get(id: &str) -> Option<BankInformation> {
-        let key = string_to_storage_key(id);
-        BANK_INFO.with(|storage| {
-            storage.borrow().get(&key)
-                .map(|stable_info| stable_info.into())
+    pub fn get(user_id: &str, id: u32) -> Result<WorkExperience, StorageError> {
+        WORK_EXPERIENCE.with(|experiences| {
+            experiences
+                .borrow()
+                .get(&(string_to_storage_key(user_id), id))
+                .ok_or_else(|| StorageError::NotFound(format!("Work experience {} not found", id)))
         })
     }
 
+    /// Stores a new role under the next id of its user.
+    pub fn add(mut experience: WorkExperience) -> Result<WorkExperience, StorageError> {
+        let existing = Self::get_by_user(&experience.user_id);
+        if existing.len() >= MAX_WORK_EXPERIENCES {
+            return Err(StorageError::ValidationError(format!(
+                "At most {} work experiences are allowed",
+                MAX_WORK_EXPERIENCES
+            )));
+        }
+        let user_key = string_to_storage_key(&experience.user_id);
+        let last_stored = existing.last().map_or(0, |last| last.id);
+        experience.id = WORK_EXPERIENCE_SEQ.with(|counters| next_user_seq(counters, user_key, last_stored));
+        ensure_fits(&experience)?;
+        let key = (user_key, experience.id);
+        WORK_EXPERIENCE.with(|experiences| experiences.borrow_mut().insert(key, experience.clone()));
+        Ok(experience)
+    }
 
-    pub fn get_by_user(user_id: &str) -> Option<BankInformation> {
-        let user_key = string_to_storage_key(user_id);
-        BANK_INFO.with(|storage| {
-            storage.borrow().iter()
-                .find(|(_, info)| info.user_id == user_key)
-                .map(|(_, info)| info.into())
-        })
+    pub fn update(experience: WorkExperience) -> Result<(), StorageError> {
+        Self::get(&experience.user_id, experience.id)?;
+        ensure_fits(&experience)?;
+        let key = (string_to_storage_key(&experience.user_id), experience.id);
+        WORK_EXPERIENCE.with(|experiences| experiences.borrow_mut().insert(key, experience));
+        Ok(())
     }
 
-    pub fn update(info: BankInformation) -> Result<(), String> {
-        let stable_info: StableBankInformation = info.into();
-        BANK_INFO.with(|bank_info| {
-            if bank_info.borrow().contains_key(&stable_info.id) {
-                bank_info.borrow_mut().insert(stable_info.id, stable_info);
-                Ok(())
-            } else {
-                Err("Bank information not found".to_string())
-            }
-        })
+    pub fn delete(user_id: &str, id: u32) -> Result<(), StorageError> {
+        WORK_EXPERIENCE.with(|experiences| {
+            experiences
+                .borrow_mut()
+                .remove(&(string_to_storage_key(user_id), id))
+                .map(|_| ())
+                .ok_or_else(|| StorageError::NotFound(format!("Work experience {} not found", id)))
+        })?;
+        Ok(())
     }
 
-    pub fn save_with_validation(info: BankInformation) -> Result<(), StorageError> {
-        println!("Starting save_with_validation");  // Debug log
+    /// Moves every role of `from_user` to `to_user` under new ids of
+    /// `to_user`, even past `MAX_WORK_EXPERIENCES`. Returns how many moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> u64 {
+        let moved = Self::get_by_user(from_user);
+        let from_key = string_to_storage_key(from_user);
+        let to_key = string_to_storage_key(to_user);
+        let last_stored = Self::get_by_user(to_user).last().map_or(0, |last| last.id);
+        for mut experience in moved.iter().cloned() {
+            WORK_EXPERIENCE.with(|experiences| experiences.borrow_mut().remove(&(from_key, experience.id)));
+            experience.id = WORK_EXPERIENCE_SEQ.with(|counters| next_user_seq(counters, to_key, last_stored));
+            experience.user_id = to_user.to_string();
+            WORK_EXPERIENCE.with(|experiences| experiences.borrow_mut().insert((to_key, experience.id), experience));
+        }
+        moved.len() as u64
+    }
+}
 
-        // Validate user exists
-        if UserStorage::get(&info.user_id).is_none() {
-            println!("User not found in save_with_validation");  // Debug log
-            return Err(StorageError::InvalidReference(
-                "User does not exist".to_string()
-            ));
+/// Single bank records from before payout methods. Only read, to move
+/// them into `PayoutStorage`, and removed once moved.
+pub struct BankStorage;
+
+impl BankStorage {
+    pub fn clear() {
+        BANK_INFO.with(clear_map);
+        BANK_BY_USER.with(|index| index.borrow_mut().clear());
+    }
+
+    pub fn get_all() -> Vec<(StorageKey, BankInformation)> {
+        BANK_INFO.with(|storage| storage.borrow().iter().map(|(key, info)| (key, info.into())).collect())
+    }
+
+    pub fn remove(key: &StorageKey) {
+        if let Some(removed) = BANK_INFO.with(|storage| storage.borrow_mut().remove(key)) {
+            BANK_BY_USER.with(|index| index.borrow_mut().remove(&removed.user_id, key));
         }
+    }
+}
 
-        // Validate SWIFT code
-        if !Self::is_valid_swift(&info.swift_code) {
-            println!("Invalid SWIFT code");  // Debug log
-            return Err(StorageError::ValidationError(
-                "Invalid SWIFT code format".to_string()
-            ));
+/// Payout methods are keyed by user and the method's id within the user.
+pub type PayoutMethodKey = (StorageKey, u32);
+
+/// What one `PayoutStorage::re_encrypt_batch` call did. `next` is where the
+/// next batch continues, or `None` once every record was visited.
+#[derive(Default)]
+pub struct ReEncryptBatch {
+    pub next: Option<PayoutMethodKey>,
+    pub visited: u64,
+    pub re_encrypted: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+/// Payout methods, with bank account numbers and BICs encrypted under the
+/// owner's data key.
+pub struct PayoutStorage;
+
+impl PayoutStorage {
+    pub fn clear() {
+        PAYOUT_METHODS.with(clear_map);
+        PAYOUT_METHOD_SEQ.with(clear_map);
+    }
+
+    /// Every payout method of `user_id`, in id order.
+    pub fn get_by_user(user_id: &str) -> Result<Vec<PayoutMethodRecord>, StorageError> {
+        Self::stored_by_user(user_id).into_iter().map(open_payout_method).collect()
+    }
+
+    /// Whether `user_id` has any payout method; decrypts nothing.
+    pub fn has_any(user_id: &str) -> bool {
+        !Self::stored_by_user(user_id).is_empty()
+    }
+
+    /// Stores a new method under the next id of its user. The first method
+    /// of a user becomes primary.
+    pub fn add(record: PayoutMethodRecord) -> Result<PayoutMethodRecord, StorageError> {
+        Self::insert_new(record, seal_payout_method)
+    }
+
+    /// Replaces a stored method; `is_primary` is kept as stored.
+    pub fn update(mut record: PayoutMethodRecord) -> Result<(), StorageError> {
+        let stored = Self::stored(&record.user_id, record.id)?;
+        if Self::get_by_user(&record.user_id)?
+            .iter()
+            .any(|m| m.id != record.id && m.method.same_destination(&record.method))
+        {
+            return Err(StorageError::AlreadyExists("This payout method is already saved".to_string()));
         }
+        record.is_primary = stored.is_primary;
+        let key = (string_to_storage_key(&record.user_id), record.id);
+        let record = seal_payout_method(record)?;
+        ensure_fits(&record)?;
+        PAYOUT_METHODS.with(|methods| methods.borrow_mut().insert(key, record));
+        Ok(())
+    }
 
-        // Check for existing bank info
-        if let Some(_) = Self::get_by_user(&info.user_id) {
-            println!("Bank info already exists");  // Debug log
-            return Err(StorageError::AlreadyExists(
-                "Bank information already exists for this user".to_string()
-            ));
+    pub fn set_primary(user_id: &str, id: u32) -> Result<(), StorageError> {
+        Self::stored(user_id, id)?;
+        let user_key = string_to_storage_key(user_id);
+        PAYOUT_METHODS.with(|methods| {
+            let mut methods = methods.borrow_mut();
+            for mut method in Self::stored_by_user(user_id) {
+                if method.is_primary != (method.id == id) {
+                    method.is_primary = method.id == id;
+                    methods.insert((user_key, method.id), method);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Removes a method. When it was primary, the oldest remaining method
+    /// takes its place.
+    pub fn remove(user_id: &str, id: u32) -> Result<(), StorageError> {
+        let user_key = string_to_storage_key(user_id);
+        let removed = PAYOUT_METHODS
+            .with(|methods| methods.borrow_mut().remove(&(user_key, id)))
+            .ok_or_else(|| StorageError::NotFound(format!("Payout method {} not found", id)))?;
+
+        if removed.is_primary {
+            if let Some(next) = Self::stored_by_user(user_id).first() {
+                Self::set_primary(user_id, next.id)?;
+            }
         }
+        Ok(())
+    }
 
-        println!("Converting to stable storage format");  // Debug log
-        let stable_info: StableBankInformation = info.clone().into();
-        let key = string_to_storage_key(&info.id);
-        
-        BANK_INFO.with(|storage| {
-            storage.borrow_mut().insert(key, stable_info);
-            Ok(())
-        })
+    /// Moves the payout methods of `from_user` to `to_user`, re-sealed under
+    /// the key of `to_user`, even past `MAX_PAYOUT_METHODS`. Methods with a
+    /// destination `to_user` already has are dropped, and the primary method
+    /// stays primary only if `to_user` had none. Every method is opened and
+    /// sealed before any is written. Returns how many moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> Result<u64, StorageError> {
+        let existing = Self::get_by_user(to_user)?;
+        let moved = Self::get_by_user(from_user)?;
+        let from_key = string_to_storage_key(from_user);
+        let to_key = string_to_storage_key(to_user);
+        let last_stored = existing.last().map_or(0, |last| last.id);
+
+        let mut sealed = Vec::new();
+        for mut record in moved.iter().cloned() {
+            if existing.iter().any(|m| m.method.same_destination(&record.method)) {
+                continue;
+            }
+            record.id = PAYOUT_METHOD_SEQ.with(|counters| next_user_seq(counters, to_key, last_stored));
+            record.user_id = to_user.to_string();
+            record.is_primary = record.is_primary && existing.is_empty();
+            let stored = seal_payout_method(record)?;
+            ensure_fits(&stored)?;
+            sealed.push(stored);
+        }
+
+        PAYOUT_METHODS.with(|methods| {
+            let mut methods = methods.borrow_mut();
+            for record in &moved {
+                methods.remove(&(from_key, record.id));
+            }
+            for stored in &sealed {
+                methods.insert((to_key, stored.id), stored.clone());
+            }
+        });
+        Ok(sealed.len() as u64)
     }
 
-    pub fn is_valid_swift(code: &str) -> bool {
-        let code = code.trim();
-        if code.len() != 8 && code.len() != 11 {
-            println!("Invalid SWIFT code length: {}", code.len());  // Debug log
-            return false;
+    /// Moves the single bank records kept before payout methods into
+    /// payout methods and drops the ones that moved. Records the payout
+    /// methods refuse (no such user, a destination saved already, too many
+    /// methods) stay where they are. Returns `(moved, left behind)`.
+    ///
+    /// This runs in `post_upgrade`, before the master key can be created,
+    /// so the records are stored in plain text for `seal_plaintext`.
+    pub fn import_bank_information() -> (u64, u64) {
+        let (mut moved, mut left_behind) = (0, 0);
+        for (key, info) in BankStorage::get_all() {
+            let created_at = info.created_at;
+            let updated_at = info.updated_at;
+            let user_id = info.user_id.clone();
+            let mut account = BankAccount::from(info);
+            account.swift_code = bank::normalize(&account.swift_code);
+            account.account_number = bank::normalize(&account.account_number);
+
+            let record = PayoutMethodRecord {
+                id: 0,
+                user_id,
+                label: None,
+                method: PayoutMethod::BankAccount(account),
+                is_primary: false,
+                created_at,
+                updated_at,
+            };
+            match Self::insert_new(record, |r| Ok(StablePayoutMethodRecord::plain(r))) {
+                Ok(_) => {
+                    BankStorage::remove(&key);
+                    moved += 1;
+                }
+                Err(_) => left_behind += 1,
+            }
         }
-        // Basic SWIFT code format validation
-        code.chars().all(|c| c.is_ascii_alphanumeric())
+        (moved, left_behind)
     }
 
-    pub fn update_with_validation(info: BankInformation) -> Result<(), StorageError> {
-        let fixed_id = string_to_fixed(&info.id); 
-        if !BANK_INFO.with(|bank_info| bank_info.borrow().contains_key(&fixed_id)) {
-            return Err(StorageError::NotFound("Bank information not found".to_string()));
+    /// Encrypts every record still holding plain-text bank fields. Needs
+    /// `Encryption::ensure_ready`. Returns how many were sealed.
+    pub fn seal_plaintext() -> Result<u64, StorageError> {
+        let pending: Vec<_> = PAYOUT_METHODS.with(|methods| {
+            methods.borrow().iter().filter(|(_, m)| !m.is_sealed()).collect()
+        });
+        let count = pending.len() as u64;
+        for (key, stored) in pending {
+            let sealed = seal_payout_method(open_payout_method(stored)?)?;
+            PAYOUT_METHODS.with(|methods| methods.borrow_mut().insert(key, sealed));
         }
+        Ok(count)
+    }
 
-        if !Self::is_valid_swift(&info.swift_code) {
-            return Err(StorageError::ValidationError(
-                "Invalid SWIFT code format".to_string()
-            ));
+    pub fn count() -> u64 {
+        PAYOUT_METHODS.with(|methods| methods.borrow().len())
+    }
+
+    /// Re-encrypts, in key order after `after`, every record not sealed
+    /// under master key `version`, until `out_of_budget` says to stop.
+    /// Records that fail to decrypt are counted and skipped.
+    pub fn re_encrypt_batch(
+        after: Option<PayoutMethodKey>,
+        version: u32,
+        out_of_budget: impl Fn() -> bool,
+    ) -> ReEncryptBatch {
+        let mut batch = ReEncryptBatch { next: after, ..Default::default() };
+        loop {
+            // At least one record per batch, so the job always moves on.
+            if batch.visited > 0 && out_of_budget() {
+                return batch;
+            }
+            let start = batch.next.map_or(Bound::Unbounded, Bound::Excluded);
+            let Some((key, stored)) =
+                PAYOUT_METHODS.with(|methods| methods.borrow().range((start, Bound::Unbounded)).next())
+            else {
+                batch.next = None;
+                return batch;
+            };
+
+            batch.visited += 1;
+            batch.next = Some(key);
+            if stored.is_sealed_under(version) {
+                continue;
+            }
+            match open_payout_method(stored).and_then(seal_payout_method) {
+                Ok(sealed) => {
+                    PAYOUT_METHODS.with(|methods| methods.borrow_mut().insert(key, sealed));
+                    batch.re_encrypted += 1;
+                }
+                Err(e) => {
+                    batch.failed += 1;
+                    batch.last_error = Some(e.to_string());
+                }
+            }
         }
+    }
+
+    fn stored_by_user(user_id: &str) -> Vec<StablePayoutMethodRecord> {
+        let key = string_to_storage_key(user_id);
+        PAYOUT_METHODS.with(|methods| {
+            methods.borrow().range((key, 0)..=(key, u32::MAX)).map(|(_, m)| m).collect()
+        })
+    }
 
-        let stable_info: StableBankInformation = info.into();
-        BANK_INFO.with(|bank_info| {
-            bank_info.borrow_mut().insert(stable_info.id, stable_info);
-            Ok(()) 
+    fn stored(user_id: &str, id: u32) -> Result<StablePayoutMethodRecord, StorageError> {
+        PAYOUT_METHODS.with(|methods| {
+            methods
+                .borrow()
+                .get(&(string_to_storage_key(user_id), id))
+                .ok_or_else(|| StorageError::NotFound(format!("Payout method {} not found", id)))
         })
     }
 
+    fn insert_new(
+        mut record: PayoutMethodRecord,
+        store: impl FnOnce(PayoutMethodRecord) -> Result<StablePayoutMethodRecord, StorageError>,
+    ) -> Result<PayoutMethodRecord, StorageError> {
+        if UserStorage::get(&record.user_id).is_none() {
+            return Err(StorageError::InvalidReference("User does not exist".to_string()));
+        }
+        let existing = Self::get_by_user(&record.user_id)?;
+        if existing.len() >= MAX_PAYOUT_METHODS {
+            return Err(StorageError::ValidationError(format!(
+                "At most {} payout methods are allowed",
+                MAX_PAYOUT_METHODS
+            )));
+        }
+        if existing.iter().any(|m| m.method.same_destination(&record.method)) {
+            return Err(StorageError::AlreadyExists("This payout method is already saved".to_string()));
+        }
+
+        let user_key = string_to_storage_key(&record.user_id);
+        let last_stored = existing.last().map_or(0, |last| last.id);
+        record.id = PAYOUT_METHOD_SEQ.with(|counters| next_user_seq(counters, user_key, last_stored));
+        record.is_primary = existing.is_empty();
+        let stored = store(record.clone())?;
+        ensure_fits(&stored)?;
+        let key = (user_key, record.id);
+        PAYOUT_METHODS.with(|methods| methods.borrow_mut().insert(key, stored));
+        Ok(record)
+    }
+}
+
+fn seal_payout_method(record: PayoutMethodRecord) -> Result<StablePayoutMethodRecord, StorageError> {
+    StablePayoutMethodRecord::seal(record).map_err(StorageError::SystemError)
+}
+
+fn open_payout_method(stored: StablePayoutMethodRecord) -> Result<PayoutMethodRecord, StorageError> {
+    let id = stored.id;
+    stored
+        .open()
+        .map_err(|e| StorageError::SystemError(format!("Payout method {}: {}", id, e)))
 }
 
 pub struct CVStorage;
 
 impl CVStorage {
+    pub fn clear() {
+        CV_STORAGE.with(clear_map);
+        CV_BY_USER.with(|index| index.borrow_mut().clear());
+    }
+
     pub fn store_cv(cv: CV) -> Result<(), StorageError> {
         let stable_cv = StableCV::from(cv.clone());
+        ensure_fits(&stable_cv)?;
         let key = string_to_storage_key(&cv.id);
-        
-        CV_STORAGE.with(|storage| {
-            storage.borrow_mut().insert(key, stable_cv);
-            Ok(())
-        })
+        put_cv(key, stable_cv);
+        Ok(())
     }
 
     pub fn get_cv(id: &str) -> Result<CV, StorageError> {
@@ -343,54 +937,73 @@ impl CVStorage {
     }
 
     pub fn get_user_cvs(user_id: &str) -> Result<Vec<CV>, StorageError> {
-        let fixed_user_id = string_to_fixed(user_id);
-        CV_STORAGE.with(|storage| {
-            let cvs: Vec<CV> = storage
-                .borrow()
-                .iter()
-                .filter(|(_, cv)| cv.user_id == fixed_user_id)
-                .map(|(_, cv)| CV::from(cv))
-                .collect();
+        let cvs = collect_pages(SortDirection::Ascending, |request| {
+            Self::get_user_cvs_page(user_id, request)
+        })?;
+
+        if cvs.is_empty() {
+            Err(StorageError::NotFound("No CVs found for user".to_string()))
+        } else {
+            Ok(cvs)
+        }
+    }
+
+    pub fn get_user_cvs_page(user_id: &str, request: &PageRequest) -> Result<Page<CV>, StorageError> {
+        let keys = CV_BY_USER
+            .with(|index| index.borrow().page(user_id, request))
+            .map_err(StorageError::ValidationError)?;
 
-            if cvs.is_empty() {
-                Err(StorageError::NotFound("No CVs found for user".to_string()))
-            } else {
-                Ok(cvs)
+        Ok(CV_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+            Page {
+                items: keys.items.iter().filter_map(|key| storage.get(key)).map(CV::from).collect(),
+                next_cursor: keys.next_cursor,
             }
-        })
+        }))
     }
 
     pub fn update_cv(cv: CV) -> Result<(), StorageError> {
         let stable_cv = StableCV::from(cv);
-        CV_STORAGE.with(|storage| {
-            if !storage.borrow().contains_key(&stable_cv.id) {
-                return Err(StorageError::NotFound("CV not found".to_string()));
-            }
-            storage.borrow_mut().insert(stable_cv.id, stable_cv);
-            Ok(())
-        })
+        ensure_fits(&stable_cv)?;
+        let key = string_to_storage_key(&stable_cv.id);
+        if !CV_STORAGE.with(|storage| storage.borrow().contains_key(&key)) {
+            return Err(StorageError::NotFound("CV not found".to_string()));
+        }
+        put_cv(key, stable_cv);
+        Ok(())
     }
 
     pub fn delete_cv(id: &str) -> Result<(), StorageError> {
         let fixed_id = string_to_fixed(id);
-        CV_STORAGE.with(|storage| {
-            if storage.borrow_mut().remove(&fixed_id).is_none() {
-                return Err(StorageError::NotFound("CV not found".to_string()));
+        let removed = CV_STORAGE
+            .with(|storage| storage.borrow_mut().remove(&fixed_id))
+            .ok_or_else(|| StorageError::NotFound("CV not found".to_string()))?;
+        CV_BY_USER.with(|index| index.borrow_mut().remove(&removed.user_id, &fixed_id));
+        Ok(())
+    }
+
+    /// Moves the CVs of `from_user` to `to_user`. Versions and documents
+    /// are keyed by the CV id and follow without changes. Returns how many
+    /// moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> u64 {
+        let keys = CV_BY_USER.with(|index| index.borrow().keys(from_user));
+        for key in &keys {
+            if let Some(mut cv) = CV_STORAGE.with(|storage| storage.borrow().get(key)) {
+                cv.user_id = to_user.to_string();
+                put_cv(*key, cv);
             }
-            Ok(())
-        })
+        }
+        keys.len() as u64
     }
 
-    pub fn get_latest_version(user_id: &str) -> u32 {
-        let fixed_user_id = string_to_fixed(user_id);
-        CV_STORAGE.with(|storage| {
-            storage
-                .borrow()
-                .iter()
-                .filter(|(_, cv)| cv.user_id == fixed_user_id)
-                .map(|(_, cv)| cv.version)
-                .max()
-                .unwrap_or(0)
+    /// A new CV id. Ids used to be `{user_id}_{n}`, which collided once cut
+    /// down to a `StorageKey`; these fit in one.
+    pub fn next_id() -> String {
+        CV_ID_SEQ.with(|seq| {
+            let mut seq = seq.borrow_mut();
+            let next = seq.get() + 1;
+            seq.set(next).expect("Failed to store CV id sequence");
+            format!("cv_{}", next)
         })
     }
 
@@ -400,57 +1013,173 @@ impl CVStorage {
         feedback: Option<String>
     ) -> Result<(), StorageError> {
         let fixed_id = string_to_fixed(id);
-        CV_STORAGE.with(|storage| {
-            let mut storage = storage.borrow_mut();
-            
-            if let Some(cv) = storage.get(&fixed_id) {
-                let mut regular_cv = CV::from(cv);
-                regular_cv.ai_analysis_status = status;
-                regular_cv.ai_feedback = feedback;
-                
-                let stable_cv = StableCV::from(regular_cv);
-                storage.insert(fixed_id, stable_cv);
-                Ok(())
-            } else {
-                Err(StorageError::NotFound("CV not found".to_string()))
+        let cv = CV_STORAGE
+            .with(|storage| storage.borrow().get(&fixed_id))
+            .ok_or_else(|| StorageError::NotFound("CV not found".to_string()))?;
+
+        let mut regular_cv = CV::from(cv);
+        regular_cv.ai_analysis_status = status;
+        regular_cv.ai_feedback = feedback;
+
+        let stable_cv = StableCV::from(regular_cv);
+        ensure_fits(&stable_cv)?;
+        put_cv(fixed_id, stable_cv);
+        Ok(())
+    }
+}
+
+pub struct CVVersionStorage;
+
+impl CVVersionStorage {
+    /// Fails if `record` would refuse `version`, so callers can check
+    /// before writing anything else.
+    pub fn check(version: &CVVersion) -> Result<(), StorageError> {
+        ensure_fits(version)?;
+        let key = (string_to_storage_key(&version.cv_id), version.version);
+        if CV_VERSIONS.with(|versions| versions.borrow().contains_key(&key)) {
+            return Err(StorageError::AlreadyExists(format!(
+                "Version {} of CV {} already exists",
+                version.version, version.cv_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Stores a new version. Versions are immutable, so writing one that
+    /// already exists is an error.
+    pub fn record(version: CVVersion) -> Result<(), StorageError> {
+        Self::check(&version)?;
+        let key = (string_to_storage_key(&version.cv_id), version.version);
+        CV_VERSIONS.with(|versions| versions.borrow_mut().insert(key, version));
+        Ok(())
+    }
+
+    pub fn get(cv_id: &str, version: u32) -> Result<CVVersion, StorageError> {
+        CV_VERSIONS.with(|versions| {
+            versions
+                .borrow()
+                .get(&(string_to_storage_key(cv_id), version))
+                .ok_or_else(|| StorageError::NotFound(format!("Version {} of CV not found", version)))
+        })
+    }
+
+    /// Versions of a CV in version order. Cursors are the version number the
+    /// previous page ended on.
+    pub fn get_page(cv_id: &str, request: &PageRequest) -> Result<Page<CVVersion>, String> {
+        let key = string_to_storage_key(cv_id);
+        let after = match &request.cursor {
+            Some(cursor) => Some((key, decode_cursor::<u32>(cursor)?)),
+            None => None,
+        };
+
+        let (entries, last) = CV_VERSIONS.with(|versions| {
+            page_range(
+                &versions.borrow(),
+                (key, 0),
+                prefix_end(&key, key.len()).map(|end| (end, 0)),
+                after,
+                request.limit(),
+                request.direction(),
+            )
+        });
+
+        Ok(Page {
+            items: entries.into_iter().map(|(_, version)| version).collect(),
+            next_cursor: last.map(|(_, version)| encode_cursor(&version)),
+        })
+    }
+
+    /// Records the analysis of `version`. Only the first analysis sticks.
+    pub fn attach_analysis(cv_id: &str, version: u32, feedback: String) -> Result<(), StorageError> {
+        let key = (string_to_storage_key(cv_id), version);
+        CV_VERSIONS.with(|versions| {
+            let mut versions = versions.borrow_mut();
+            let mut stored = versions
+                .get(&key)
+                .ok_or_else(|| StorageError::NotFound(format!("Version {} of CV not found", version)))?;
+            if stored.analysis.is_none() {
+                stored.analysis = Some(CVVersionAnalysis {
+                    feedback,
+                    analyzed_at: time(),
+                });
+                versions.insert(key, stored);
             }
+            Ok(())
         })
     }
+}
 
-    pub fn save_with_validation(cv: CV) -> Result<(), StorageError> {
-        if cv.title.len() > 64 {
-            return Err(StorageError::ValidationError(
-                "Title is too long (max 64 bytes)".to_string()
-            ));
-        }
+pub struct CvDocumentStorage;
 
-        if cv.content.len() > 1024 {
-            return Err(StorageError::ValidationError(
-                "Content is too long (max 1024 bytes)".to_string()
-            ));
+impl CvDocumentStorage {
+    pub fn get(cv_id: &str) -> Option<CvDocument> {
+        CV_DOCUMENTS.with(|documents| documents.borrow().get(&string_to_storage_key(cv_id)))
+    }
+
+    pub fn put(cv_id: &str, document: CvDocument) -> Result<(), StorageError> {
+        ensure_fits(&document)?;
+        CV_DOCUMENTS.with(|documents| {
+            documents.borrow_mut().insert(string_to_storage_key(cv_id), document)
+        });
+        Ok(())
+    }
+}
+
+/// Records the current state of every CV as its first history entry.
+pub fn backfill_cv_versions() -> u64 {
+    let cvs: Vec<CV> = CV_STORAGE.with(|storage| storage.borrow().iter().map(|(_, cv)| cv.into()).collect());
+    let mut recorded = 0;
+    for mut cv in cvs {
+        cv.version = cv.version.max(1);
+        let mut version = CVVersion::of(&cv, None);
+        version.analysis = cv.ai_feedback.map(|feedback| CVVersionAnalysis {
+            feedback,
+            analyzed_at: version.created_at,
+        });
+        if CVVersionStorage::record(version).is_ok() {
+            recorded += 1;
         }
+    }
+    recorded
+}
 
-        if let Some(feedback) = &cv.ai_feedback {
-            if feedback.len() > 1024 {
-                return Err(StorageError::ValidationError(
-                    "AI feedback is too long (max 1024 bytes)".to_string()
-                ));
+/// Restores the owner of CVs from the fixed-width layout, whose 32-byte
+/// `user_id` field cut off longer principal texts. An owner is restored
+/// only when exactly one user's id starts with the stored prefix; CVs that
+/// stay unresolved remain inaccessible. Returns `(restored, unresolved)`.
+pub fn restore_truncated_cv_owners() -> (u64, u64) {
+    const LEGACY_FIELD_BYTES: usize = 32;
+
+    let orphaned: Vec<(StorageKey, StableCV)> = CV_STORAGE.with(|storage| {
+        storage
+            .borrow()
+            .iter()
+            .filter(|(_, cv)| cv.user_id.len() == LEGACY_FIELD_BYTES && !UserStorage::exists(&cv.user_id))
+            .collect()
+    });
+    if orphaned.is_empty() {
+        return (0, 0);
+    }
+
+    let user_ids: Vec<String> = UserStorage::get_all().into_iter().map(|user| user.id).collect();
+    let (mut restored, mut unresolved) = (0, 0);
+    for (key, mut cv) in orphaned {
+        let mut owners = user_ids.iter().filter(|id| id.starts_with(&cv.user_id));
+        match (owners.next(), owners.next()) {
+            (Some(owner), None) => {
+                cv.user_id = owner.clone();
+                put_cv(key, cv);
+                restored += 1;
             }
+            _ => unresolved += 1,
         }
-
-        let stable_cv: StableCV = cv.into();
-        CV_STORAGE.with(|storage| {
-            storage.borrow_mut().insert(stable_cv.id, stable_cv);
-            Ok(()) 
-        })
     }
+    (restored, unresolved)
 }
 
 #[cfg(test)]
 pub fn clear_cv_storage() {
-    CV_STORAGE.with(|storage| {
-        storage.borrow_mut().clear();
-    });
+    CVStorage::clear();
 }
 
 pub struct APIUsageStorage;
@@ -460,7 +1189,9 @@ impl APIUsageStorage {
         RateLimitConfig::default()
     }
 
-    pub fn check_and_update_limit(user_id: &str) -> Result<bool, String> {
+    /// Counts one request against `user_id`'s daily limit, or says how long
+    /// until the limit resets when it is used up.
+    pub fn check_and_update_limit(user_id: &str) -> Result<(), ApiError> {
         let config = Self::get_config();
         let mut usage = Self::get_usage(user_id).unwrap_or(UserAPIUsage {
             user_id: user_id.to_string(),
@@ -477,15 +1208,16 @@ impl APIUsageStorage {
         }
 
         if usage.daily_requests >= config.daily_limit {
-            return Ok(false);
+            let reset_at = usage.last_reset + config.reset_interval_nanos;
+            return Err(ApiError::RateLimited {
+                retry_after: reset_at.saturating_sub(current_time).div_ceil(1_000_000_000),
+            });
         }
 
         usage.daily_requests += 1;
         usage.total_requests += 1;
 
-        Self::update_usage(usage)?;
-
-        Ok(true)
+        Self::update_usage(usage).map_err(ApiError::Internal)
     }
 
     pub fn get_usage(user_id: &str) -> Result<UserAPIUsage, String> {
@@ -501,7 +1233,7 @@ impl APIUsageStorage {
 
     pub fn update_usage(usage: UserAPIUsage) -> Result<(), String> {
         let stable_usage: StableUserAPIUsage = usage.into();
-        let fixed_id = stable_usage.user_id;
+        let fixed_id = string_to_fixed(&stable_usage.user_id);
         API_USAGE_STORAGE.with(|storage| {
             storage.borrow_mut().insert(fixed_id, stable_usage);
             Ok(())
@@ -510,77 +1242,113 @@ impl APIUsageStorage {
 
 }
 
+/// Key of a chat message: the session it belongs to and its position in it.
+pub type MessageKey = (StorageKey, u64);
+
+/// Session ids (`chat_{principal}_{cv_id}`) are longer than a `StorageKey`,
+/// so sessions and message keys use their SHA-256 instead of the truncated
+/// text.
+fn session_key(session_id: &str) -> StorageKey {
+    Sha256::digest(session_id.as_bytes()).into()
+}
+
+fn message_id(session_id: &str, seq: u64) -> String {
+    format!("msg_{}_{}", session_id, seq)
+}
+
+/// Sequence numbers start at 1 and are never reused within a session, even
+/// after messages are deleted or a store fails.
+fn next_message_seq(key: StorageKey) -> u64 {
+    CHAT_MESSAGE_SEQ.with(|counters| {
+        let mut counters = counters.borrow_mut();
+        let seq = counters.get(&key).unwrap_or(0) + 1;
+        counters.insert(key, seq);
+        seq
+    })
+}
+
 pub struct ChatStorage;
 
 impl ChatStorage {
     pub fn store_message(session_id: &str, content: String, is_ai: bool) -> Result<ChatMessage, String> {
+        let key = session_key(session_id);
+        let seq = next_message_seq(key);
         let message = ChatMessage {
-            id: format!("msg_{}_{}", session_id, time()),
+            id: message_id(session_id, seq),
             content,
             is_ai,
             timestamp: time(),
         };
-        
+
         let stable_message = StableChatMessage::from(message.clone());
-        CHAT_STORAGE.with(|storage| {
-            storage.borrow_mut().insert(
-                string_to_fixed(&message.id),
-                stable_message
-            );
+        ensure_fits(&stable_message).map_err(|e| e.to_string())?;
+        CHAT_MESSAGES.with(|storage| {
+            storage.borrow_mut().insert((key, seq), stable_message);
         });
         
         Ok(message)
     }
 
-    pub fn get_message(id: &str) -> Result<ChatMessage, String> {
-        let fixed_id = string_to_fixed(id);
-
-        CHAT_STORAGE.with(|storage| {
-            storage
-                .borrow()
-                .get(&fixed_id)
-                .map(|msg| msg.into())
-                .ok_or_else(|| "Chat message not found".to_string())
-        })
+    pub fn get_session_messages(session_id: &str) -> Result<Vec<ChatMessage>, String> {
+        let messages = collect_pages(SortDirection::Ascending, |request| {
+            Self::get_session_messages_page(session_id, request)
+        })?;
+
+        if messages.is_empty() {
+            Err("No messages found for session".to_string())
+        } else {
+            Ok(messages)
+        }
     }
 
-    pub fn delete_message(id: &str) -> Result<(), String> {
-        let fixed_id = string_to_fixed(id);
+    /// Messages of a session in sequence order. Cursors are the sequence
+    /// number the previous page ended on.
+    pub fn get_session_messages_page(session_id: &str, request: &PageRequest) -> Result<Page<ChatMessage>, String> {
+        let after = match &request.cursor {
+            Some(cursor) => Some(decode_cursor::<u64>(cursor)?),
+            None => None,
+        };
+        let (entries, last) = Self::range(session_id, after, request.limit(), request.direction());
 
-        CHAT_STORAGE.with(|storage| {
-            storage
-                .borrow_mut()
-                .remove(&fixed_id)
-                .map(|_| ())
-                .ok_or_else(|| "Chat message not found".to_string())
+        Ok(Page {
+            items: entries.into_iter().map(|(_, message)| message).collect(),
+            next_cursor: last.map(|seq| encode_cursor(&seq)),
         })
     }
 
-    pub fn get_session_messages(session_id: &str) -> Result<Vec<ChatMessage>, String> {
-        let prefix = string_to_fixed(session_id);
-        
-        CHAT_STORAGE.with(|storage| {
-            let messages: Vec<ChatMessage> = storage
-                .borrow()
-                .iter()
-                .filter(|(key, _)| key.starts_with(&prefix[..session_id.len().min(32)]))
-                .map(|(_, value)| value.into())
-                .collect();
+    /// Up to `limit` messages of a session after (or, descending, before)
+    /// sequence number `after`, with the sequence number to continue from if
+    /// more follow.
+    pub fn range(
+        session_id: &str,
+        after: Option<u64>,
+        limit: usize,
+        direction: SortDirection,
+    ) -> (Vec<(u64, ChatMessage)>, Option<u64>) {
+        let key = session_key(session_id);
+        let (entries, last) = CHAT_MESSAGES.with(|storage| {
+            page_range(
+                &storage.borrow(),
+                (key, 0),
+                prefix_end(&key, key.len()).map(|end| (end, 0)),
+                after.map(|seq| (key, seq)),
+                limit,
+                direction,
+            )
+        });
 
-            if messages.is_empty() {
-                Err("No messages found for session".to_string())
-            } else {
-                Ok(messages)
-            }
-        })
+        (
+            entries.into_iter().map(|((_, seq), message)| (seq, message.into())).collect(),
+            last.map(|(_, seq)| seq),
+        )
     }
 
     pub fn cleanup_old_messages(older_than_nanos: u64) -> Result<u32, String> {
         let current_time = ic_cdk::api::time();
         let mut deleted_count = 0;
 
-        CHAT_STORAGE.with(|storage| {
-            let to_delete: Vec<FixedString> = storage
+        CHAT_MESSAGES.with(|storage| {
+            let to_delete: Vec<MessageKey> = storage
                 .borrow()
                 .iter()
                 .filter(|(_, msg)| (current_time - msg.timestamp) > older_than_nanos)
@@ -598,6 +1366,102 @@ impl ChatStorage {
     }
 }
 
+/// The session a message stored under the old `msg_{session_id}_{time}`
+/// (or `..._{time}_ai`, `..._welcome`) ids belongs to. Those ids were cut to
+/// 32 bytes, which leaves only the start of the session id, so the message
+/// is placed only when exactly one session matches what is left.
+fn legacy_message_session<'a>(id: &str, session_ids: &'a [String]) -> Option<&'a String> {
+    let rest = id.strip_prefix("msg_")?;
+    let truncated = id.len() >= LEGACY_MESSAGE_ID_BYTES;
+    let mut matches = session_ids.iter().filter(|session_id| {
+        if truncated {
+            session_id.starts_with(rest) || rest.starts_with(&format!("{}_", session_id))
+        } else {
+            rest.strip_prefix(session_id.as_str()).is_some_and(|tail| tail.starts_with('_'))
+        }
+    });
+    match (matches.next(), matches.next()) {
+        (Some(session_id), None) => Some(session_id),
+        _ => None,
+    }
+}
+
+/// Width of the `id` field in the fixed-width chat message layout.
+const LEGACY_MESSAGE_ID_BYTES: usize = 32;
+
+/// Moves messages out of the old `FixedString`-keyed map into
+/// `CHAT_MESSAGES`, numbering each session's messages by timestamp.
+///
+/// Messages whose truncated id matches no session, or several, stay in the
+/// old map, where nothing reads them. Returns `(moved, left behind)`.
+pub fn migrate_chat_message_keys() -> (u64, u64) {
+    let legacy: Vec<(FixedString, StableChatMessage)> =
+        CHAT_STORAGE.with(|storage| storage.borrow().iter().collect());
+    if legacy.is_empty() {
+        return (0, 0);
+    }
+    let session_ids: Vec<String> = CHAT_SESSION_STORAGE
+        .with(|storage| storage.borrow().iter().map(|(_, session)| full_session_id(&session)).collect());
+
+    let mut by_session: std::collections::BTreeMap<String, Vec<(FixedString, StableChatMessage)>> =
+        std::collections::BTreeMap::new();
+    let mut left_behind = 0;
+    for (key, message) in legacy {
+        match legacy_message_session(&message.id, &session_ids) {
+            Some(session_id) => by_session.entry(session_id.clone()).or_default().push((key, message)),
+            None => left_behind += 1,
+        }
+    }
+
+    let mut moved = 0;
+    for (session_id, mut messages) in by_session {
+        messages.sort_by_key(|(_, message)| message.timestamp);
+        let key = session_key(&session_id);
+        for (legacy_key, mut message) in messages {
+            let seq = next_message_seq(key);
+            message.id = message_id(&session_id, seq);
+            CHAT_MESSAGES.with(|storage| storage.borrow_mut().insert((key, seq), message));
+            CHAT_STORAGE.with(|storage| storage.borrow_mut().remove(&legacy_key));
+            moved += 1;
+        }
+    }
+
+    (moved, left_behind)
+}
+
+/// The id of a session, rebuilt from its user and CV ids if the stored one
+/// was cut by the fixed-width layout.
+fn full_session_id(session: &StableChatSession) -> String {
+    let full_id = format!("chat_{}_{}", session.user_id, session.cv_id);
+    if full_id.starts_with(&session.id) {
+        full_id
+    } else {
+        session.id.clone()
+    }
+}
+
+/// Re-keys chat sessions stored under their id cut to 32 bytes, where every
+/// session of a user collided, by `session_key`. Returns the number of
+/// sessions re-keyed.
+pub fn migrate_chat_session_keys() -> u64 {
+    let sessions: Vec<(StorageKey, StableChatSession)> =
+        CHAT_SESSION_STORAGE.with(|storage| storage.borrow().iter().collect());
+
+    let mut rekeyed = 0;
+    for (old_key, mut session) in sessions {
+        session.id = full_session_id(&session);
+        let key = session_key(&session.id);
+        if key == old_key {
+            continue;
+        }
+        CHAT_SESSION_STORAGE.with(|storage| storage.borrow_mut().remove(&old_key));
+        CHAT_SESSION_BY_USER.with(|index| index.borrow_mut().remove(&session.user_id, &old_key));
+        put_chat_session(key, session);
+        rekeyed += 1;
+    }
+    rekeyed
+}
+
 pub struct ChatSessionStorage;
 
 impl ChatSessionStorage {
@@ -614,22 +1478,17 @@ impl ChatSessionStorage {
         println!("Creating session with ID: {}", session_id);  // Debug
 
         let stable_session: StableChatSession = session.clone().into();
-        let fixed_id = string_to_fixed(&session_id);
-        
-        CHAT_SESSION_STORAGE.with(|storage| {
-            storage.borrow_mut().insert(fixed_id, stable_session);
-            Ok(session)
-        })
+        put_chat_session(session_key(&session_id), stable_session);
+        Ok(session)
     }
 
     pub fn get_session(session_id: &str) -> Result<ChatSession, ChatStorageError> {
         println!("Getting session with ID: {}", session_id);  // Debug
         
-        let fixed_id = string_to_fixed(session_id);
         let result = CHAT_SESSION_STORAGE.with(|storage| {
             storage
                 .borrow()
-                .get(&fixed_id)
+                .get(&session_key(session_id))
                 .map(|session| session.into())
                 .ok_or(ChatStorageError::NotFound)
         });
@@ -640,25 +1499,55 @@ impl ChatSessionStorage {
     
     pub fn update_session(session: ChatSession) -> Result<(), ChatStorageError> {
         let stable_session: StableChatSession = session.into();
-        let fixed_id = stable_session.id;
-    
-        CHAT_SESSION_STORAGE.with(|storage| {
-            storage.borrow_mut().insert(fixed_id, stable_session);
-            Ok(())
-        })
+        put_chat_session(session_key(&stable_session.id), stable_session);
+        Ok(())
     }
 
-    pub fn get_user_sessions(user_id: &str) -> Vec<ChatSession> {
-        let fixed_user_id = string_to_fixed(user_id);
+    pub fn get_user_sessions_page(user_id: &str, request: &PageRequest) -> Result<Page<ChatSession>, String> {
+        let keys = CHAT_SESSION_BY_USER.with(|index| index.borrow().page(user_id, request))?;
 
-        CHAT_SESSION_STORAGE.with(|storage| {
-            storage
-                .borrow()
-                .iter()
-                .filter(|(_, session)| session.user_id == fixed_user_id)
-                .map(|(_, session)| session.into())
-                .collect()
-        })
+        Ok(CHAT_SESSION_STORAGE.with(|storage| {
+            let storage = storage.borrow();
+            Page {
+                items: keys.items.iter().filter_map(|key| storage.get(key)).map(|session| session.into()).collect(),
+                next_cursor: keys.next_cursor,
+            }
+        }))
+    }
+
+    /// Moves the chat sessions of `from_user` to `to_user`. Session ids
+    /// name their user, so each session is re-keyed under the id it has for
+    /// `to_user`, and its messages are renumbered into that session.
+    /// Returns how many sessions moved.
+    pub fn transfer(from_user: &str, to_user: &str) -> u64 {
+        let keys = CHAT_SESSION_BY_USER.with(|index| index.borrow().keys(from_user));
+        for old_key in &keys {
+            let Some(mut session) = CHAT_SESSION_STORAGE.with(|storage| storage.borrow_mut().remove(old_key)) else {
+                continue;
+            };
+            CHAT_SESSION_BY_USER.with(|index| index.borrow_mut().remove(from_user, old_key));
+            session.user_id = to_user.to_string();
+            session.id = format!("chat_{}_{}", to_user, session.cv_id);
+            let key = session_key(&session.id);
+
+            let messages: Vec<(MessageKey, StableChatMessage)> = CHAT_MESSAGES
+                .with(|storage| storage.borrow().range((*old_key, 0)..=(*old_key, u64::MAX)).collect());
+            for (old_message_key, mut message) in messages {
+                let seq = next_message_seq(key);
+                message.id = message_id(&session.id, seq);
+                CHAT_MESSAGES.with(|storage| {
+                    let mut storage = storage.borrow_mut();
+                    storage.remove(&old_message_key);
+                    storage.insert((key, seq), message);
+                });
+            }
+            CHAT_MESSAGE_SEQ.with(|counters| counters.borrow_mut().remove(old_key));
+
+            if !CHAT_SESSION_STORAGE.with(|storage| storage.borrow().contains_key(&key)) {
+                put_chat_session(key, session);
+            }
+        }
+        keys.len() as u64
     }
 
     pub fn delete_old_sessions(older_than_nanos: u64) -> u32 {
@@ -666,7 +1555,7 @@ impl ChatSessionStorage {
         let mut deleted_count = 0;
 
         CHAT_SESSION_STORAGE.with(|storage| {
-            let to_delete: Vec<FixedString> = storage
+            let to_delete: Vec<StorageKey> = storage
                 .borrow()
                 .iter()
                 .filter(|(_, session)| (current_time - session.last_interaction) > older_than_nanos)
@@ -674,7 +1563,8 @@ impl ChatSessionStorage {
                 .collect();
 
             for key in to_delete {
-                if storage.borrow_mut().remove(&key).is_some() {
+                if let Some(session) = storage.borrow_mut().remove(&key) {
+                    CHAT_SESSION_BY_USER.with(|index| index.borrow_mut().remove(&session.user_id, &key));
                     deleted_count += 1;
                 }
             }
@@ -1,2 +1,6 @@
+pub mod blob;
+pub mod index;
 pub mod memory;
+pub mod pagination;
+pub mod registry;
 pub use memory::*;
\ No newline at end of file
@@ -0,0 +1,129 @@
+//! Cursor pagination over `StableBTreeMap` key ranges.
+//!
+//! A cursor is the hex encoding of the last key of the previous page, so a
+//! page costs one range scan no matter how deep into the map it starts.
+//! `StableBTreeMap` cannot iterate backwards; descending pages step with
+//! `iter_upper_bound`, one lookup per entry.
+
+use ic_stable_structures::{BoundedStorable, StableBTreeMap, Storable};
+use std::borrow::Cow;
+use std::ops::Bound;
+
+use crate::models::pagination::{Page, PageRequest, SortDirection};
+use crate::storage::registry::Memory;
+
+pub fn encode_cursor<K: Storable>(key: &K) -> String {
+    key.to_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses a cursor made by `encode_cursor`, rejecting anything that would not
+/// decode into a `K` instead of trapping on it.
+pub fn decode_cursor<K: BoundedStorable>(cursor: &str) -> Result<K, String> {
+    let invalid = || "Invalid cursor".to_string();
+
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+
+    let size_ok = if K::IS_FIXED_SIZE {
+        bytes.len() == K::MAX_SIZE as usize
+    } else {
+        bytes.len() <= K::MAX_SIZE as usize
+    };
+    if !size_ok {
+        return Err(invalid());
+    }
+    Ok(K::from_bytes(Cow::Owned(bytes)))
+}
+
+/// Smallest key greater than every key that shares the first `len` bytes of
+/// `prefix`, or `None` if there is none (those bytes are all `0xFF`).
+pub fn prefix_end<const N: usize>(prefix: &[u8; N], len: usize) -> Option<[u8; N]> {
+    let mut end = [0u8; N];
+    end[..len].copy_from_slice(&prefix[..len]);
+    for i in (0..len).rev() {
+        if end[i] < u8::MAX {
+            end[i] += 1;
+            return Some(end);
+        }
+        end[i] = 0;
+    }
+    None
+}
+
+/// Reads up to `limit` entries with keys in `[start, end)` (`end: None` runs
+/// to the end of the map), continuing after `after`. Returns the entries and,
+/// if more follow, the key to continue after.
+pub fn page_range<K, V>(
+    map: &StableBTreeMap<K, V, Memory>,
+    start: K,
+    end: Option<K>,
+    after: Option<K>,
+    limit: usize,
+    direction: SortDirection,
+) -> (Vec<(K, V)>, Option<K>)
+where
+    K: BoundedStorable + Ord + Clone,
+    V: BoundedStorable,
+{
+    let mut entries: Vec<(K, V)> = Vec::with_capacity(limit + 1);
+
+    match direction {
+        SortDirection::Ascending => {
+            let lower = match after {
+                Some(after) if after >= start => Bound::Excluded(after),
+                _ => Bound::Included(start),
+            };
+            let upper = end.map_or(Bound::Unbounded, Bound::Excluded);
+            entries.extend(map.range((lower, upper)).take(limit + 1));
+        }
+        SortDirection::Descending => {
+            let bound = match (after, end) {
+                (Some(after), Some(end)) => Some(after.min(end)),
+                (after, end) => after.or(end),
+            };
+            let mut next = match bound {
+                Some(bound) => map.iter_upper_bound(&bound).next(),
+                None => map.last_key_value(),
+            };
+            while let Some((key, value)) = next {
+                if key < start || entries.len() > limit {
+                    break;
+                }
+                next = map.iter_upper_bound(&key).next();
+                entries.push((key, value));
+            }
+        }
+    }
+
+    if entries.len() > limit {
+        entries.truncate(limit);
+        let last = entries.last().map(|(key, _)| key.clone());
+        (entries, last)
+    } else {
+        (entries, None)
+    }
+}
+
+/// Walks every page of `fetch`. Backs the older endpoints that return whole
+/// lists.
+pub fn collect_pages<T, E>(
+    direction: SortDirection,
+    mut fetch: impl FnMut(&PageRequest) -> Result<Page<T>, E>,
+) -> Result<Vec<T>, E> {
+    let mut request = PageRequest::first(direction);
+    let mut items = Vec::new();
+    loop {
+        let page = fetch(&request)?;
+        items.extend(page.items);
+        match page.next_cursor {
+            Some(cursor) => request.cursor = Some(cursor),
+            None => return Ok(items),
+        }
+    }
+}
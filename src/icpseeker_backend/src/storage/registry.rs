@@ -0,0 +1,202 @@
+//! The canister's only `MemoryManager`, and the table of every stable-memory
+//! region carved out of it.
+//!
+//! Ids are grouped into reserved ranges so new subsystems have an obvious
+//! place to go:
+//!
+//! | ids       | purpose                                          |
+//! |-----------|--------------------------------------------------|
+//! | 0–6       | core records (fixed since the first release)     |
+//! | 7–15      | upgrade and migration state                      |
+//! | 16–31     | identity and authentication                      |
+//! | 32–63     | secondary indexes                                |
+//! | 64–127    | feature data (CV history, uploads, profile data) |
+//! | 128–159   | security (encryption keys, admin lists)          |
+//! | 160–254   | unassigned                                       |
+//!
+//! Every region must be listed in `REGIONS`. `check_layout` runs in `init`
+//! and `post_upgrade` and traps if two regions share an id or an id falls
+//! outside the reserved ranges; `memory` additionally traps if an id is
+//! opened under two different names.
+
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{BoundedStorable, DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub id: u8,
+    pub name: &'static str,
+}
+
+impl MemoryRegion {
+    const fn new(id: u8, name: &'static str) -> Self {
+        Self { id, name }
+    }
+
+    pub fn memory_id(&self) -> MemoryId {
+        MemoryId::new(self.id)
+    }
+}
+
+pub const USERS: MemoryRegion = MemoryRegion::new(0, "users");
+pub const EDUCATION: MemoryRegion = MemoryRegion::new(1, "education");
+pub const BANK: MemoryRegion = MemoryRegion::new(2, "bank");
+pub const API_USAGE: MemoryRegion = MemoryRegion::new(3, "api_usage");
+pub const CV: MemoryRegion = MemoryRegion::new(4, "cv");
+pub const CHAT: MemoryRegion = MemoryRegion::new(5, "chat");
+pub const CHAT_SESSION: MemoryRegion = MemoryRegion::new(6, "chat_session");
+
+pub const STATE_VERSION: MemoryRegion = MemoryRegion::new(7, "state_version");
+pub const UPGRADE_STATE: MemoryRegion = MemoryRegion::new(8, "upgrade_state");
+
+pub const PRINCIPAL_LINKS: MemoryRegion = MemoryRegion::new(16, "principal_links");
+pub const USER_PRINCIPALS: MemoryRegion = MemoryRegion::new(17, "user_principals");
+pub const LINK_CODES: MemoryRegion = MemoryRegion::new(18, "link_codes");
+pub const EMAIL_VERIFICATIONS: MemoryRegion = MemoryRegion::new(19, "email_verifications");
+pub const MAIL_CONFIG: MemoryRegion = MemoryRegion::new(20, "mail_config");
+
+pub const EDUCATION_BY_USER: MemoryRegion = MemoryRegion::new(32, "education_by_user");
+pub const BANK_BY_USER: MemoryRegion = MemoryRegion::new(33, "bank_by_user");
+pub const CV_BY_USER: MemoryRegion = MemoryRegion::new(34, "cv_by_user");
+pub const CHAT_SESSION_BY_USER: MemoryRegion = MemoryRegion::new(35, "chat_session_by_user");
+pub const BLOB_BY_USER: MemoryRegion = MemoryRegion::new(36, "blob_by_user");
+
+pub const CHAT_MESSAGES: MemoryRegion = MemoryRegion::new(64, "chat_messages");
+pub const CHAT_MESSAGE_SEQ: MemoryRegion = MemoryRegion::new(65, "chat_message_seq");
+pub const CV_ID_SEQ: MemoryRegion = MemoryRegion::new(66, "cv_id_seq");
+pub const CV_VERSIONS: MemoryRegion = MemoryRegion::new(67, "cv_versions");
+pub const UPLOADS: MemoryRegion = MemoryRegion::new(68, "uploads");
+pub const BLOBS: MemoryRegion = MemoryRegion::new(69, "blobs");
+pub const BLOB_CHUNKS: MemoryRegion = MemoryRegion::new(70, "blob_chunks");
+pub const BLOB_ID_SEQ: MemoryRegion = MemoryRegion::new(71, "blob_id_seq");
+pub const BLOB_TEXT: MemoryRegion = MemoryRegion::new(72, "blob_text");
+pub const CV_DOCUMENTS: MemoryRegion = MemoryRegion::new(73, "cv_documents");
+pub const WORK_EXPERIENCE: MemoryRegion = MemoryRegion::new(74, "work_experience");
+pub const PAYOUT_METHODS: MemoryRegion = MemoryRegion::new(75, "payout_methods");
+pub const PAYOUT_METHOD_SEQ: MemoryRegion = MemoryRegion::new(76, "payout_method_seq");
+pub const WORK_EXPERIENCE_SEQ: MemoryRegion = MemoryRegion::new(77, "work_experience_seq");
+
+pub const MASTER_KEY: MemoryRegion = MemoryRegion::new(128, "master_key");
+pub const KEY_ROTATION: MemoryRegion = MemoryRegion::new(129, "key_rotation");
+pub const BUSINESS_MEMBERS: MemoryRegion = MemoryRegion::new(130, "business_members");
+pub const ADMINS: MemoryRegion = MemoryRegion::new(131, "admins");
+
+/// Every region in use. Add new regions here, inside their reserved range.
+pub const REGIONS: &[MemoryRegion] = &[
+    USERS,
+    EDUCATION,
+    BANK,
+    API_USAGE,
+    CV,
+    CHAT,
+    CHAT_SESSION,
+    STATE_VERSION,
+    UPGRADE_STATE,
+    PRINCIPAL_LINKS,
+    USER_PRINCIPALS,
+    LINK_CODES,
+    EMAIL_VERIFICATIONS,
+    MAIL_CONFIG,
+    EDUCATION_BY_USER,
+    BANK_BY_USER,
+    CV_BY_USER,
+    CHAT_SESSION_BY_USER,
+    BLOB_BY_USER,
+    CHAT_MESSAGES,
+    CHAT_MESSAGE_SEQ,
+    CV_ID_SEQ,
+    CV_VERSIONS,
+    UPLOADS,
+    BLOBS,
+    BLOB_CHUNKS,
+    BLOB_ID_SEQ,
+    BLOB_TEXT,
+    CV_DOCUMENTS,
+    WORK_EXPERIENCE,
+    PAYOUT_METHODS,
+    PAYOUT_METHOD_SEQ,
+    WORK_EXPERIENCE_SEQ,
+    MASTER_KEY,
+    KEY_ROTATION,
+    BUSINESS_MEMBERS,
+    ADMINS,
+];
+
+/// Inclusive id ranges regions may be placed in, with what they are for.
+const RESERVED_RANGES: &[(u8, u8, &str)] = &[
+    (0, 6, "core records"),
+    (7, 15, "upgrade state"),
+    (16, 31, "identity"),
+    (32, 63, "indexes"),
+    (64, 127, "feature data"),
+    (128, 159, "security"),
+];
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
+        MemoryManager::init(DefaultMemoryImpl::default())
+    );
+
+    static CLAIMED: RefCell<BTreeMap<u8, &'static str>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Traps if `REGIONS` assigns an id twice or places one outside the reserved
+/// ranges. Called at the start of `init` and `post_upgrade`.
+pub fn check_layout() {
+    let mut seen: BTreeMap<u8, &'static str> = BTreeMap::new();
+    for region in REGIONS {
+        if let Some(other) = seen.insert(region.id, region.name) {
+            ic_cdk::trap(&format!(
+                "Memory id {} is claimed by both '{}' and '{}'",
+                region.id, other, region.name
+            ));
+        }
+        if !RESERVED_RANGES
+            .iter()
+            .any(|(start, end, _)| (*start..=*end).contains(&region.id))
+        {
+            ic_cdk::trap(&format!(
+                "Memory id {} ('{}') is outside the reserved ranges",
+                region.id, region.name
+            ));
+        }
+    }
+}
+
+/// Returns the virtual memory for `region`, recording the claim. Opening the
+/// same region again is fine; opening its id under another name traps.
+pub fn memory(region: MemoryRegion) -> Memory {
+    CLAIMED.with(|claimed| {
+        let mut claimed = claimed.borrow_mut();
+        match claimed.get(&region.id) {
+            Some(name) if *name != region.name => ic_cdk::trap(&format!(
+                "Memory id {} is already claimed by '{}', cannot open it as '{}'",
+                region.id, name, region.name
+            )),
+            Some(_) => {}
+            None => {
+                claimed.insert(region.id, region.name);
+            }
+        }
+    });
+    MEMORY_MANAGER.with(|m| m.borrow().get(region.memory_id()))
+}
+
+/// Opens (or creates) the map stored in `region`.
+pub fn init_map<K, V>(region: MemoryRegion) -> StableBTreeMap<K, V, Memory>
+where
+    K: BoundedStorable + Ord + Clone,
+    V: BoundedStorable,
+{
+    StableBTreeMap::init(memory(region))
+}
+
+/// Opens the cell stored in `region`, writing `default` if it is empty.
+pub fn init_cell<T: Storable>(region: MemoryRegion, default: T) -> StableCell<T, Memory> {
+    StableCell::init(memory(region), default)
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("Failed to open '{}': {:?}", region.name, e)))
+}
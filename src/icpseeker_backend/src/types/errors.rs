@@ -20,14 +20,74 @@ pub enum StorageError {
     SystemError(String),
 }
 
-#[derive(CandidType, Serialize, Deserialize, Debug)]
-pub enum ValidationError {
-    InvalidEmail(String),
-    InvalidPhone(String),
-    InvalidDate(String),
-    MissingRequiredField(String),
-    InvalidFormat(String),
-    RelationshipError(String),
+/// One invalid input field. `field` is the payload path, e.g. `email` or
+/// `university[0].gpa`; `code` is stable for the frontend to match on and
+/// `message` is meant for display.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+/// The one error type of the v2 API. Clients match on the variant, never
+/// on the text, so variants are only ever added.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ApiError {
+    NotFound(String),
+    /// The caller may not do this, or may not see the record.
+    Unauthorized(String),
+    Validation(Vec<FieldError>),
+    /// Seconds until the caller's limit resets.
+    RateLimited { retry_after: u64 },
+    /// A call to another canister or an HTTPS outcall failed.
+    Upstream(String),
+    Internal(String),
+}
+
+impl ApiError {
+    /// A validation error on a single field; `field` is empty when the
+    /// problem is with the request as a whole.
+    pub fn invalid(field: &str, code: &str, message: impl Into<String>) -> Self {
+        ApiError::Validation(vec![FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        }])
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::NotFound(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::Upstream(msg)
+            | ApiError::Internal(msg) => write!(f, "{}", msg),
+            ApiError::Validation(errors) => write!(f, "{}", crate::validation::describe(errors)),
+            ApiError::RateLimited { retry_after } => {
+                write!(f, "Rate limit exceeded; try again in {} seconds", retry_after)
+            }
+        }
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(error: StorageError) -> Self {
+        match error {
+            StorageError::NotFound(msg) => ApiError::NotFound(msg),
+            StorageError::AlreadyExists(msg) => ApiError::invalid("", "already_exists", msg),
+            StorageError::InvalidReference(msg) => ApiError::invalid("", "invalid_reference", msg),
+            StorageError::ValidationError(msg) => ApiError::invalid("", "invalid", msg),
+            StorageError::OrphanedRecord(msg) | StorageError::SystemError(msg) => ApiError::Internal(msg),
+        }
+    }
+}
+
+impl From<Vec<FieldError>> for ApiError {
+    fn from(errors: Vec<FieldError>) -> Self {
+        ApiError::Validation(errors)
+    }
 }
 
 pub enum ChatError {
@@ -0,0 +1,315 @@
+//! Bank identifiers: BIC (SWIFT) structure, IBAN check digits, per-country
+//! account-number rules, and a directory of banks known by BIC.
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::country;
+
+type Failure = (&'static str, String);
+
+/// The BIC and account number as stored: upper case, without the spaces
+/// and dashes people group them with.
+pub fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// The ISO code of a free-text `bank_country`. Two-letter codes outside
+/// the country table are taken as they are.
+fn country_code(bank_country: &str) -> Option<String> {
+    if let Some(found) = country::find(bank_country) {
+        return Some(found.iso.to_string());
+    }
+    let trimmed = bank_country.trim();
+    (trimmed.len() == 2 && trimmed.chars().all(|c| c.is_ascii_alphabetic())).then(|| trimmed.to_ascii_uppercase())
+}
+
+/// Checks a normalized BIC: a 4-letter bank code, a 2-letter country code
+/// that has to be `bank_country`'s, a 2-character location code and an
+/// optional 3-character branch code.
+pub fn check_bic(bic: &str, bank_country: &str) -> Result<(), Failure> {
+    let bytes = bic.as_bytes();
+    let well_formed = matches!(bytes.len(), 8 | 11)
+        && bytes[..6].iter().all(u8::is_ascii_uppercase)
+        && bytes[6..].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+    if !well_formed {
+        return Err((
+            "invalid_bic",
+            "Must be a 4-letter bank code, 2-letter country code, 2-character location and optional 3-character branch"
+                .to_string(),
+        ));
+    }
+
+    match country_code(bank_country) {
+        Some(iso) if iso != bic[4..6] => Err((
+            "country_mismatch",
+            format!("Is a BIC from {}, not {}", country::name(&bic[4..6]), bank_country.trim()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// IBAN length by country, for the countries whose domestic accounts are
+/// identified by IBAN.
+const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AE", 23), ("AT", 20), ("BE", 16), ("CH", 21), ("CZ", 24), ("DE", 22),
+    ("DK", 18), ("ES", 24), ("FI", 18), ("FR", 27), ("GB", 22), ("GR", 27),
+    ("IE", 22), ("IT", 27), ("LU", 20), ("NL", 18), ("NO", 15), ("PL", 28),
+    ("PT", 25), ("SA", 24), ("SE", 24), ("TR", 26),
+];
+
+fn iban_length(iso: &str) -> Option<usize> {
+    IBAN_LENGTHS.iter().find(|(code, _)| *code == iso).map(|(_, len)| *len)
+}
+
+/// ISO 13616 check: move the first four characters to the end, read
+/// letters as 10–35, and the number has to leave 1 modulo 97.
+fn iban_checksum_ok(iban: &str) -> bool {
+    let (Some(head), Some(tail)) = (iban.get(..4), iban.get(4..)) else {
+        return false;
+    };
+    let rearranged = tail.chars().chain(head.chars());
+    let mut remainder: u32 = 0;
+    for c in rearranged {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+/// Account-number format of one country's banks, for the checks beyond the
+/// generic length and character rules. Add a country by implementing this
+/// and listing it in `LOCAL_RULES`.
+pub trait AccountNumberRules: Sync {
+    /// `account_number` is normalized; `bank` is the directory entry of the
+    /// account's BIC, when known.
+    fn check(&self, iso: &str, account_number: &str, bank: Option<&DirectoryEntry>) -> Result<(), Failure>;
+}
+
+struct Iban;
+
+impl AccountNumberRules for Iban {
+    fn check(&self, iso: &str, account_number: &str, _bank: Option<&DirectoryEntry>) -> Result<(), Failure> {
+        let expected = iban_length(iso).unwrap_or_default();
+        if !account_number.starts_with(iso) {
+            return Err((
+                "invalid_iban",
+                format!("Must be an IBAN starting with {} for {}", iso, country::name(iso)),
+            ));
+        }
+        if !account_number.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(("invalid_iban", "An IBAN only contains letters and digits".to_string()));
+        }
+        if account_number.len() != expected {
+            return Err((
+                "invalid_iban",
+                format!("An IBAN from {} has {} characters", country::name(iso), expected),
+            ));
+        }
+        let check_digits = account_number.get(2..4).unwrap_or_default();
+        if !check_digits.bytes().all(|b| b.is_ascii_digit()) || !iban_checksum_ok(account_number) {
+            return Err(("invalid_iban", "The IBAN check digits do not match; check for typos".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Digit counts of Indonesian account numbers, keyed by the bank's
+/// three-digit domestic transfer code.
+const INDONESIAN_ACCOUNT_LENGTHS: &[(&str, &[usize])] = &[
+    ("002", &[15]),     // BRI
+    ("008", &[13]),     // Mandiri
+    ("009", &[10]),     // BNI
+    ("011", &[10]),     // Danamon
+    ("013", &[10]),     // Permata
+    ("014", &[10]),     // BCA
+    ("016", &[10]),     // Maybank Indonesia
+    ("019", &[10]),     // Panin
+    ("022", &[13, 14]), // CIMB Niaga
+    ("028", &[12]),     // OCBC NISP
+    ("200", &[16]),     // BTN
+    ("451", &[10]),     // BSI
+];
+/// Bounds for Indonesian banks without a known length.
+const INDONESIAN_MIN_DIGITS: usize = 7;
+const INDONESIAN_MAX_DIGITS: usize = 16;
+
+struct Indonesia;
+
+impl AccountNumberRules for Indonesia {
+    fn check(&self, _iso: &str, account_number: &str, bank: Option<&DirectoryEntry>) -> Result<(), Failure> {
+        if !account_number.chars().all(|c| c.is_ascii_digit()) {
+            return Err(("invalid_account_number", "Indonesian account numbers only contain digits".to_string()));
+        }
+
+        let known = bank.and_then(|bank| {
+            let code = bank.local_code?;
+            let (_, lengths) = INDONESIAN_ACCOUNT_LENGTHS.iter().find(|(c, _)| *c == code)?;
+            Some((bank.name, *lengths))
+        });
+        match known {
+            Some((_, lengths)) if lengths.contains(&account_number.len()) => Ok(()),
+            Some((name, lengths)) => Err((
+                "invalid_length",
+                format!(
+                    "{} account numbers have {} digits",
+                    name,
+                    lengths.iter().map(usize::to_string).collect::<Vec<_>>().join(" or ")
+                ),
+            )),
+            None if (INDONESIAN_MIN_DIGITS..=INDONESIAN_MAX_DIGITS).contains(&account_number.len()) => Ok(()),
+            None => Err((
+                "invalid_length",
+                format!(
+                    "Indonesian account numbers have {} to {} digits",
+                    INDONESIAN_MIN_DIGITS, INDONESIAN_MAX_DIGITS
+                ),
+            )),
+        }
+    }
+}
+
+const LOCAL_RULES: &[(&str, &dyn AccountNumberRules)] = &[("ID", &Indonesia)];
+
+/// The rules for accounts held in `iso`: IBAN for IBAN countries, then any
+/// local rules.
+pub fn account_number_rules(iso: &str) -> Option<&'static dyn AccountNumberRules> {
+    if iban_length(iso).is_some() {
+        return Some(&Iban);
+    }
+    LOCAL_RULES.iter().find(|(code, _)| *code == iso).map(|(_, rules)| *rules)
+}
+
+/// Checks a normalized account number against the rules of the country
+/// named by `bank_country`. Countries without rules pass.
+pub fn check_account_number(account_number: &str, bic: &str, bank_country: &str) -> Result<(), Failure> {
+    let Some(iso) = country_code(bank_country) else {
+        return Ok(());
+    };
+    match account_number_rules(&iso) {
+        Some(rules) => rules.check(&iso, account_number, lookup_bic(bic)),
+        None => Ok(()),
+    }
+}
+
+pub struct DirectoryEntry {
+    /// The 8-character BIC of the head office; branch codes are ignored.
+    pub bic: &'static str,
+    pub name: &'static str,
+    /// Domestic bank code, where the country has one.
+    pub local_code: Option<&'static str>,
+}
+
+const fn bank(bic: &'static str, name: &'static str, local_code: Option<&'static str>) -> DirectoryEntry {
+    DirectoryEntry { bic, name, local_code }
+}
+
+pub const DIRECTORY: &[DirectoryEntry] = &[
+    bank("BRINIDJA", "Bank Rakyat Indonesia (BRI)", Some("002")),
+    bank("BMRIIDJA", "Bank Mandiri", Some("008")),
+    bank("BNINIDJA", "Bank Negara Indonesia (BNI)", Some("009")),
+    bank("BDINIDJA", "Bank Danamon", Some("011")),
+    bank("BBBAIDJA", "Bank Permata", Some("013")),
+    bank("CENAIDJA", "Bank Central Asia (BCA)", Some("014")),
+    bank("IBBKIDJA", "Maybank Indonesia", Some("016")),
+    bank("PINBIDJA", "Panin Bank", Some("019")),
+    bank("BNIAIDJA", "CIMB Niaga", Some("022")),
+    bank("NISPIDJA", "OCBC NISP", Some("028")),
+    bank("BTANIDJA", "Bank Tabungan Negara (BTN)", Some("200")),
+    bank("BSMDIDJA", "Bank Syariah Indonesia (BSI)", Some("451")),
+    bank("DBSSSGSG", "DBS Bank", None),
+    bank("OCBCSGSG", "OCBC Bank", None),
+    bank("MBBEMYKL", "Maybank", None),
+    bank("CIBBMYKL", "CIMB Bank", None),
+    bank("CHASUS33", "JPMorgan Chase Bank", None),
+    bank("BOFAUS3N", "Bank of America", None),
+    bank("DEUTDEFF", "Deutsche Bank", None),
+    bank("INGBNL2A", "ING Bank", None),
+    bank("HBUKGB4B", "HSBC UK Bank", None),
+];
+
+/// The directory entry of a normalized BIC, matched on its first eight
+/// characters.
+pub fn lookup_bic(bic: &str) -> Option<&'static DirectoryEntry> {
+    let head = bic.get(..8)?;
+    DIRECTORY.iter().find(|entry| entry.bic == head)
+}
+
+/// What the directory knows about a BIC, for suggesting `bank_name`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct BankSuggestion {
+    pub bic: String,
+    pub bank_name: String,
+    pub bank_country: String,
+    pub bank_code: Option<String>,
+}
+
+impl From<&DirectoryEntry> for BankSuggestion {
+    fn from(entry: &DirectoryEntry) -> Self {
+        Self {
+            bic: entry.bic.to_string(),
+            bank_name: entry.name.to_string(),
+            bank_country: country::name(&entry.bic[4..6]).to_string(),
+            bank_code: entry.local_code.map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_ibans() {
+        for (iban, country) in [
+            ("DE89370400440532013000", "Germany"),
+            ("GB82WEST12345698765432", "GB"),
+            ("NL91ABNA0417164300", "Netherlands"),
+            ("FR1420041010050500013M02606", "FR"),
+            ("BE68539007547034", "Belgium"),
+        ] {
+            assert!(iban_checksum_ok(iban), "{}", iban);
+            assert_eq!(check_account_number(iban, "", country), Ok(()), "{}", iban);
+        }
+    }
+
+    #[test]
+    fn rejects_typos_in_ibans() {
+        for iban in [
+            "DE89370400440532013001", // one digit changed
+            "DE89370400440523013000", // two digits swapped
+            "DE98370400440532013000", // check digits swapped
+            "GB82WEST12345698765433",
+        ] {
+            assert!(!iban_checksum_ok(iban), "{}", iban);
+            assert_eq!(check_account_number(iban, "", "DE").map_err(|(code, _)| code), Err("invalid_iban"), "{}", iban);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_ibans_without_panicking() {
+        for iban in ["DE€93704004405320130", "DEX9370400440532013000", "DE", "NL91ABNA041716430", "DE89370400440532013000X"] {
+            assert_eq!(check_account_number(iban, "", "DE").map_err(|(code, _)| code), Err("invalid_iban"), "{}", iban);
+        }
+        assert!(!iban_checksum_ok("DE€9"));
+        assert!(!iban_checksum_ok(""));
+    }
+
+    #[test]
+    fn checks_indonesian_account_lengths() {
+        assert_eq!(check_account_number("1234567890", "CENAIDJA", "Indonesia"), Ok(()));
+        assert!(check_account_number("123456789", "CENAIDJA", "Indonesia").is_err());
+        assert!(check_account_number("12345A7890", "CENAIDJA", "ID").is_err());
+        assert_eq!(check_account_number("1234567", "XXXXIDJA", "ID"), Ok(()));
+    }
+}
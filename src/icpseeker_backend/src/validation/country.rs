@@ -0,0 +1,63 @@
+//! Countries the validators know by name, for matching the free-text
+//! `country` and `bank_country` fields against ISO 3166 codes.
+
+pub struct Country {
+    pub iso: &'static str,
+    /// The first name is the one shown to users.
+    pub names: &'static [&'static str],
+}
+
+const fn country(iso: &'static str, names: &'static [&'static str]) -> Country {
+    Country { iso, names }
+}
+
+pub const COUNTRIES: &[Country] = &[
+    country("ID", &["Indonesia"]),
+    country("MY", &["Malaysia"]),
+    country("SG", &["Singapore"]),
+    country("PH", &["Philippines"]),
+    country("TH", &["Thailand"]),
+    country("VN", &["Vietnam", "Viet Nam"]),
+    country("IN", &["India"]),
+    country("CN", &["China"]),
+    country("JP", &["Japan"]),
+    country("KR", &["South Korea", "Korea"]),
+    country("AU", &["Australia"]),
+    country("US", &["United States", "United States of America", "USA"]),
+    country("CA", &["Canada"]),
+    country("GB", &["United Kingdom", "UK", "Great Britain"]),
+    country("IE", &["Ireland"]),
+    country("NL", &["Netherlands", "The Netherlands"]),
+    country("BE", &["Belgium"]),
+    country("LU", &["Luxembourg"]),
+    country("DE", &["Germany"]),
+    country("AT", &["Austria"]),
+    country("CH", &["Switzerland"]),
+    country("FR", &["France"]),
+    country("ES", &["Spain"]),
+    country("PT", &["Portugal"]),
+    country("IT", &["Italy"]),
+    country("GR", &["Greece"]),
+    country("DK", &["Denmark"]),
+    country("SE", &["Sweden"]),
+    country("NO", &["Norway"]),
+    country("FI", &["Finland"]),
+    country("PL", &["Poland"]),
+    country("CZ", &["Czech Republic", "Czechia"]),
+    country("TR", &["Turkey", "Türkiye"]),
+    country("SA", &["Saudi Arabia"]),
+    country("AE", &["United Arab Emirates", "UAE"]),
+];
+
+/// The entry for `country`, given as a name or ISO code, ignoring case.
+pub fn find(country: &str) -> Option<&'static Country> {
+    let country = country.trim();
+    COUNTRIES.iter().find(|c| {
+        c.iso.eq_ignore_ascii_case(country) || c.names.iter().any(|name| name.eq_ignore_ascii_case(country))
+    })
+}
+
+/// The display name of `iso`, or the code itself for a country not listed.
+pub fn name(iso: &str) -> &str {
+    COUNTRIES.iter().find(|c| c.iso == iso).map_or(iso, |c| c.names[0])
+}
@@ -0,0 +1,151 @@
+//! ICP ledger account formats: the ICRC-1 textual account encoding and the
+//! legacy 32-byte account identifier, each with its CRC32 checksum.
+
+use candid::Principal;
+use data_encoding::BASE32_NOPAD;
+
+use crate::models::payout::SUBACCOUNT_LENGTH;
+
+type Failure = (&'static str, String);
+
+const ACCOUNT_IDENTIFIER_LENGTH: usize = 32;
+
+/// Parses an ICRC-1 account in its textual form: the owner principal
+/// alone for the default subaccount, or
+/// `<owner>-<checksum>.<subaccount hex without leading zeros>`, where the
+/// checksum is the base32 CRC32 of the owner and subaccount bytes.
+pub fn parse_icrc1_account(text: &str) -> Result<(Principal, Option<Vec<u8>>), Failure> {
+    let text = text.trim().to_ascii_lowercase();
+    let Some((head, subaccount_hex)) = text.split_once('.') else {
+        return Ok((owner(&text)?, None));
+    };
+    let Some((owner_text, checksum)) = head.rsplit_once('-') else {
+        return Err(("invalid_account", "The checksum before the subaccount is missing".to_string()));
+    };
+    let owner = owner(owner_text)?;
+
+    if subaccount_hex.is_empty()
+        || subaccount_hex.starts_with('0')
+        || subaccount_hex.len() > SUBACCOUNT_LENGTH * 2
+        || !subaccount_hex.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        return Err((
+            "invalid_subaccount",
+            "The subaccount must be up to 64 hex digits without leading zeros".to_string(),
+        ));
+    }
+    let subaccount = hex::decode(format!("{:0>64}", subaccount_hex))
+        .map_err(|e| ("invalid_subaccount", format!("The subaccount is not valid hex: {}", e)))?;
+
+    if icrc1_checksum(&owner, &subaccount) != checksum {
+        return Err(("checksum_mismatch", "The account checksum does not match; check for typos".to_string()));
+    }
+    Ok((owner, Some(subaccount)))
+}
+
+/// Parses a hex account identifier: a CRC32 of the remaining 28 bytes,
+/// followed by those bytes. Returns it in lower case.
+pub fn parse_account_identifier(text: &str) -> Result<String, Failure> {
+    let text = text.trim().to_ascii_lowercase();
+    let bytes = hex::decode(&text)
+        .ok()
+        .filter(|bytes| bytes.len() == ACCOUNT_IDENTIFIER_LENGTH)
+        .ok_or(("invalid_account_identifier", "Must be 64 hex digits".to_string()))?;
+
+    let expected = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if crc32fast::hash(&bytes[4..]) != expected {
+        return Err((
+            "checksum_mismatch",
+            "The account identifier checksum does not match; check for typos".to_string(),
+        ));
+    }
+    Ok(text)
+}
+
+fn owner(text: &str) -> Result<Principal, Failure> {
+    let owner = Principal::from_text(text)
+        .map_err(|e| ("invalid_principal", format!("The owner is not a valid principal: {}", e)))?;
+    if owner == Principal::anonymous() {
+        return Err(("invalid_principal", "The anonymous principal cannot own an account".to_string()));
+    }
+    Ok(owner)
+}
+
+fn icrc1_checksum(owner: &Principal, subaccount: &[u8]) -> String {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(owner.as_slice());
+    hasher.update(subaccount);
+    BASE32_NOPAD.encode(&hasher.finalize().to_be_bytes()).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+    fn code<T>(result: Result<T, Failure>) -> Result<T, &'static str> {
+        result.map_err(|(code, _)| code)
+    }
+
+    #[test]
+    fn parses_icrc1_accounts() {
+        let owner = Principal::from_text(OWNER).unwrap();
+        assert_eq!(parse_icrc1_account(OWNER), Ok((owner, None)));
+
+        let mut one = vec![0; SUBACCOUNT_LENGTH];
+        one[SUBACCOUNT_LENGTH - 1] = 1;
+        assert_eq!(parse_icrc1_account(&format!("{}-6cc627i.1", OWNER)), Ok((owner, Some(one))));
+
+        let counting: Vec<u8> = (1..=32).collect();
+        let text = format!("{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20", OWNER);
+        assert_eq!(parse_icrc1_account(&text), Ok((owner, Some(counting.clone()))));
+        assert_eq!(parse_icrc1_account(&text.to_ascii_uppercase()), Ok((owner, Some(counting))));
+    }
+
+    #[test]
+    fn rejects_icrc1_typos() {
+        for text in [
+            format!("{}-6cc627j.1", OWNER), // checksum
+            format!("{}-6cc627i.2", OWNER), // subaccount
+            format!("{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f21", OWNER),
+        ] {
+            assert_eq!(code(parse_icrc1_account(&text)), Err("checksum_mismatch"), "{}", text);
+        }
+        // One character of the owner changes its built-in checksum.
+        let owner_typo = OWNER.replacen("k2t6j", "k2t6k", 1);
+        assert_eq!(code(parse_icrc1_account(&owner_typo)), Err("invalid_principal"));
+    }
+
+    #[test]
+    fn rejects_malformed_icrc1_accounts() {
+        assert_eq!(code(parse_icrc1_account(&format!("{}.1", OWNER))), Err("invalid_principal"));
+        assert_eq!(code(parse_icrc1_account(&format!("{}-6cc627i.01", OWNER))), Err("invalid_subaccount"));
+        assert_eq!(code(parse_icrc1_account(&format!("{}-6cc627i.", OWNER))), Err("invalid_subaccount"));
+        assert_eq!(code(parse_icrc1_account(&format!("{}-6cc627i.xyz", OWNER))), Err("invalid_subaccount"));
+        assert_eq!(code(parse_icrc1_account(&format!("{}-6cc627i.1{}", OWNER, "0".repeat(64)))), Err("invalid_subaccount"));
+        assert_eq!(code(parse_icrc1_account("2vxsx-fae")), Err("invalid_principal"));
+    }
+
+    #[test]
+    fn parses_account_identifiers() {
+        // The default account of the anonymous principal.
+        let id = "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79";
+        assert_eq!(parse_account_identifier(id), Ok(id.to_string()));
+        assert_eq!(parse_account_identifier(&format!(" {} ", id.to_ascii_uppercase())), Ok(id.to_string()));
+    }
+
+    #[test]
+    fn rejects_account_identifier_typos() {
+        for id in [
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc78", // last digit
+            "0c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79", // checksum
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cacd79", // two digits swapped
+        ] {
+            assert_eq!(code(parse_account_identifier(id)), Err("checksum_mismatch"), "{}", id);
+        }
+        for id in ["1c7a48ba", "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc7g", ""] {
+            assert_eq!(code(parse_account_identifier(id)), Err("invalid_account_identifier"), "{}", id);
+        }
+    }
+}
@@ -1,37 +1,40 @@
-use crate::models::user::{StableUserProfile, fixed_to_string};
-use crate::storage::memory::{UserStorage, EducationStorage, BankStorage};
-use crate::types::errors::StorageError;
+pub mod bank;
+pub mod country;
+pub mod ledger;
+mod payloads;
+pub mod phone;
+pub mod rules;
+
+use crate::models::UserProfile;
+use crate::storage::memory::EducationStorage;
+use crate::types::errors::{FieldError, StorageError};
+
+/// Input that can check itself field by field. Implemented for every
+/// payload that creates or changes a record, and for the records storage
+/// checks before writing them.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Vec<FieldError>>;
+}
+
+/// The errors as one line, for responses that only carry a message.
+pub fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
 
 pub struct ValidationService;
 
 impl ValidationService {
-    pub fn validate_user(user: &StableUserProfile) -> Result<(), StorageError> {
-        if !fixed_to_string(&user.email).contains('@') {
-            return Err(StorageError::ValidationError("Invalid email address".to_string()));
-        }
-
-        if user.phone_number.len() < 10 {
-            return Err(StorageError::ValidationError("Invalid phone number".to_string()));
-        }
-
-        if user.name.is_empty() {
-            return Err(StorageError::ValidationError("Name is required".to_string()));
-        }
-
-        if user.city.is_empty() || user.country.is_empty() {
-            return Err(StorageError::ValidationError("Location fields are required".to_string()));
-        }
-
-        Ok(())
+    /// The profile's own fields and the records it points to.
+    pub fn validate_user(user: &UserProfile) -> Result<(), StorageError> {
+        user.validate().map_err(|errors| StorageError::ValidationError(describe(&errors)))?;
+        Self::validate_relationships(user)
     }
 
-    pub fn validate_relationships(user_id: &str) -> Result<(), StorageError> {
-
-        let user = match UserStorage::get(user_id) {
-            Some(user) => user,
-            None => return Err(StorageError::NotFound("User not found".to_string())),
-        };
-
+    pub fn validate_relationships(user: &UserProfile) -> Result<(), StorageError> {
         if let Some(edu_id) = &user.education_id {
             if EducationStorage::get(edu_id).is_none() {
                 return Err(StorageError::InvalidReference(
@@ -40,23 +43,6 @@ impl ValidationService {
             }
         }
 
-        if let Some(bank_id) = &user.bank_info_id {
-            if BankStorage::get(bank_id).is_none() {
-                return Err(StorageError::InvalidReference(
-                    "Referenced bank information not found".to_string(),
-                ));
-            }
-        }
-
-        Ok(())
-    }
-
-    pub fn validate_swift_code(code: &str) -> Result<(), StorageError> {
-        if code.len() != 8 && code.len() != 11 {
-            return Err(StorageError::ValidationError(
-                "SWIFT code must be 8 or 11 characters".to_string(),
-            ));
-        }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,296 @@
+use crate::models::education::{EducationDetails, GradeScale, HighSchoolEducation, UniversityEducation};
+use crate::models::UserProfile;
+use crate::types::errors::FieldError;
+use crate::{
+    BankInfoPayload, CreateCVPayload, CreateUserPayload, EducationPayload, HighSchoolPayload,
+    PayoutMethodInput, PayoutMethodPayload, UniversityPayload, UpdateCVPayload, UpdateUserPayload,
+};
+
+use super::{bank, ledger};
+use super::rules::{Rule, Validator};
+use super::Validate;
+
+const MAX_TEXT_CHARS: usize = 100;
+const MAX_CV_TITLE_CHARS: usize = 200;
+const MAX_CV_CONTENT_CHARS: usize = 30_000;
+
+const NAME: &[Rule] = &[Rule::Required, Rule::Length { min: 2, max: MAX_TEXT_CHARS }];
+const REQUIRED_TEXT: &[Rule] = &[Rule::Required, Rule::Length { min: 1, max: MAX_TEXT_CHARS }];
+const OPTIONAL_TEXT: &[Rule] = &[Rule::Length { min: 1, max: MAX_TEXT_CHARS }];
+const EMAIL: &[Rule] = &[Rule::Required, Rule::Email];
+const PHONE: &[Rule] = &[Rule::Required, Rule::Phone];
+const ACCOUNT_NUMBER: &[Rule] = &[Rule::Required, Rule::Length { min: 5, max: 34 }];
+const HIGH_SCHOOL_TRACKS: &[&str] = &["Science", "Social", "Language", "Religious", "Vocational", "General", "Other"];
+const TRACK: &[Rule] = &[Rule::OneOf(HIGH_SCHOOL_TRACKS)];
+const CV_TITLE: &[Rule] = &[Rule::Required, Rule::Length { min: 1, max: MAX_CV_TITLE_CHARS }];
+const CV_CONTENT: &[Rule] = &[Rule::Length { min: 0, max: MAX_CV_CONTENT_CHARS }];
+
+/// Fields every user profile needs, whether from a payload or in storage.
+/// The phone number has to fit `country`.
+fn user_fields(v: &mut Validator, name: &str, email: &str, phone: &str, city: &str, country: &str) {
+    v.text("name", name, NAME)
+        .text("email", email, EMAIL)
+        .phone("phone_number", phone, country)
+        .text("city", city, REQUIRED_TEXT)
+        .text("country", country, REQUIRED_TEXT);
+}
+
+/// Fields of a bank account. The BIC and account number are checked in
+/// their normalized form, which is what gets stored.
+fn bank_fields(
+    v: &mut Validator,
+    account_holder_name: &str,
+    bank_name: &str,
+    swift_code: &str,
+    account_number: &str,
+    bank_country: &str,
+    bank_branch: Option<&str>,
+) {
+    let swift_code = bank::normalize(swift_code);
+    let account_number = bank::normalize(account_number);
+    let alphanumeric = account_number.chars().all(|c| c.is_ascii_alphanumeric());
+
+    v.text("account_holder_name", account_holder_name, NAME)
+        .text("bank_name", bank_name, REQUIRED_TEXT)
+        .text("swift_code", &swift_code, &[Rule::Required])
+        .format("swift_code", bank::check_bic(&swift_code, bank_country))
+        .text("account_number", &account_number, ACCOUNT_NUMBER)
+        .format(
+            "account_number",
+            alphanumeric
+                .then_some(())
+                .ok_or(("invalid_account_number", "Must contain only letters and digits".to_string())),
+        )
+        .format(
+            "account_number",
+            if alphanumeric {
+                bank::check_account_number(&account_number, &swift_code, bank_country)
+            } else {
+                Ok(())
+            },
+        )
+        .text("bank_country", bank_country, REQUIRED_TEXT)
+        .optional_text("bank_branch", bank_branch, OPTIONAL_TEXT);
+}
+
+fn high_school_fields(
+    v: &mut Validator,
+    school_name: &str,
+    track: &str,
+    city: &str,
+    country: &str,
+    start_year: u32,
+    end_year: Option<u32>,
+) {
+    v.text("school_name", school_name, REQUIRED_TEXT)
+        .text("track", track, TRACK)
+        .text("city", city, OPTIONAL_TEXT)
+        .text("country", country, OPTIONAL_TEXT)
+        .year("start_year", start_year)
+        .end_year("end_year", end_year, start_year);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn university_fields(
+    v: &mut Validator,
+    university_name: &str,
+    major: &str,
+    city: &str,
+    country: &str,
+    start_year: u32,
+    end_year: Option<u32>,
+    gpa: Option<f32>,
+    gpa_scale: Option<GradeScale>,
+) {
+    v.text("university_name", university_name, REQUIRED_TEXT)
+        .text("major", major, OPTIONAL_TEXT)
+        .text("city", city, OPTIONAL_TEXT)
+        .text("country", country, OPTIONAL_TEXT)
+        .year("start_year", start_year)
+        .end_year("end_year", end_year, start_year);
+    if let Some(gpa) = gpa {
+        match gpa_scale {
+            None => {
+                v.check("gpa_scale", false, "required", "A grade needs its grading scale");
+            }
+            Some(scale) => {
+                if let Err(message) = scale.validate(gpa) {
+                    v.check("gpa", false, "invalid_grade", message);
+                }
+            }
+        }
+    }
+}
+
+impl Validate for CreateUserPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        user_fields(&mut v, &self.name, &self.email, &self.phone_number, &self.city, &self.country);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateUserPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.optional_text("name", self.name.as_deref(), NAME)
+            .optional_text("email", self.email.as_deref(), EMAIL)
+            .optional_text("phone_number", self.phone_number.as_deref(), PHONE)
+            .optional_text("city", self.city.as_deref(), REQUIRED_TEXT)
+            .optional_text("country", self.country.as_deref(), REQUIRED_TEXT);
+        v.finish()
+    }
+}
+
+impl Validate for UserProfile {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        user_fields(&mut v, &self.name, &self.email, &self.phone_number, &self.city, &self.country);
+        v.finish()
+    }
+}
+
+impl Validate for HighSchoolPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        high_school_fields(
+            &mut v,
+            &self.school_name,
+            &self.track,
+            &self.city,
+            &self.country,
+            self.start_year,
+            self.end_year,
+        );
+        v.finish()
+    }
+}
+
+impl Validate for UniversityPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        university_fields(
+            &mut v,
+            &self.university_name,
+            &self.major,
+            &self.city,
+            &self.country,
+            self.start_year,
+            self.end_year,
+            self.gpa,
+            self.gpa_scale,
+        );
+        v.finish()
+    }
+}
+
+impl Validate for EducationPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        if let Some(Err(errors)) = self.high_school.as_ref().map(Validate::validate) {
+            v.nested("high_school.", errors);
+        }
+        for (index, university) in self.university.iter().flatten().enumerate() {
+            if let Err(errors) = university.validate() {
+                v.nested(&format!("university[{}].", index), errors);
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for EducationDetails {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        match self {
+            EducationDetails::HighSchool(HighSchoolEducation {
+                school_name,
+                track,
+                city,
+                country,
+                start_year,
+                end_year,
+                ..
+            }) => high_school_fields(&mut v, school_name, track, city, country, *start_year, *end_year),
+            EducationDetails::University(UniversityEducation {
+                university_name,
+                major,
+                city,
+                country,
+                start_year,
+                end_year,
+                gpa,
+                gpa_scale,
+                ..
+            }) => university_fields(
+                &mut v,
+                university_name,
+                major,
+                city,
+                country,
+                *start_year,
+                *end_year,
+                *gpa,
+                *gpa_scale,
+            ),
+        }
+        v.finish()
+    }
+}
+
+impl Validate for BankInfoPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        bank_fields(
+            &mut v,
+            &self.account_holder_name,
+            &self.bank_name,
+            &self.swift_code,
+            &self.account_number,
+            &self.bank_country,
+            self.bank_branch.as_deref(),
+        );
+        v.finish()
+    }
+}
+
+impl Validate for PayoutMethodPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.optional_text("label", self.label.as_deref(), OPTIONAL_TEXT);
+        match &self.method {
+            PayoutMethodInput::BankAccount(bank) => {
+                if let Err(errors) = bank.validate() {
+                    v.nested("method.", errors);
+                }
+            }
+            PayoutMethodInput::Icrc1Account(text) => {
+                v.text("method.account", text, &[Rule::Required])
+                    .format("method.account", ledger::parse_icrc1_account(text).map(|_| ()));
+            }
+            PayoutMethodInput::IcpAccountIdentifier(text) => {
+                v.text("method.account_identifier", text, &[Rule::Required])
+                    .format("method.account_identifier", ledger::parse_account_identifier(text).map(|_| ()));
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CreateCVPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.text("title", &self.title, CV_TITLE).text("content", &self.content, CV_CONTENT);
+        v.finish()
+    }
+}
+
+impl Validate for UpdateCVPayload {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut v = Validator::new();
+        v.text("id", &self.id, &[Rule::Required])
+            .text("title", &self.title, CV_TITLE)
+            .text("content", &self.content, CV_CONTENT);
+        v.finish()
+    }
+}
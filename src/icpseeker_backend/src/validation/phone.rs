@@ -0,0 +1,248 @@
+//! Phone numbers in E.164 form (`+` country code, then the national
+//! number), parsed against a small table of the countries our users come
+//! from. Indonesian numbers get the local conventions: a leading trunk `0`,
+//! and `62` typed without the `+`.
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use super::country;
+
+const MIN_E164_DIGITS: usize = 8;
+const MAX_E164_DIGITS: usize = 15;
+
+const INDONESIA_CALLING_CODE: &str = "62";
+
+#[derive(Debug)]
+pub struct CallingCountry {
+    pub iso: &'static str,
+    pub calling_code: &'static str,
+    /// Dialled before the national number inside the country.
+    pub trunk_prefix: Option<char>,
+    /// Inclusive bounds on the digits after the country code.
+    pub min_digits: usize,
+    pub max_digits: usize,
+}
+
+const fn calling(
+    iso: &'static str,
+    calling_code: &'static str,
+    trunk_prefix: Option<char>,
+    min_digits: usize,
+    max_digits: usize,
+) -> CallingCountry {
+    CallingCountry { iso, calling_code, trunk_prefix, min_digits, max_digits }
+}
+
+pub const CALLING_COUNTRIES: &[CallingCountry] = &[
+    calling("ID", INDONESIA_CALLING_CODE, Some('0'), 8, 12),
+    calling("MY", "60", Some('0'), 8, 10),
+    calling("SG", "65", None, 8, 8),
+    calling("PH", "63", Some('0'), 8, 10),
+    calling("TH", "66", Some('0'), 8, 9),
+    calling("VN", "84", Some('0'), 9, 10),
+    calling("IN", "91", Some('0'), 10, 10),
+    calling("CN", "86", Some('0'), 10, 11),
+    calling("JP", "81", Some('0'), 9, 10),
+    calling("KR", "82", Some('0'), 8, 10),
+    calling("AU", "61", Some('0'), 9, 9),
+    calling("NL", "31", Some('0'), 9, 9),
+    calling("DE", "49", Some('0'), 7, 11),
+    calling("GB", "44", Some('0'), 9, 10),
+    calling("US", "1", None, 10, 10),
+    calling("CA", "1", None, 10, 10),
+];
+
+/// One row of `CALLING_COUNTRIES`, as clients see it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PhoneCountry {
+    pub iso: String,
+    pub name: String,
+    pub calling_code: String,
+}
+
+pub fn phone_countries() -> Vec<PhoneCountry> {
+    CALLING_COUNTRIES
+        .iter()
+        .map(|c| PhoneCountry {
+            iso: c.iso.to_string(),
+            name: country::name(c.iso).to_string(),
+            calling_code: format!("+{}", c.calling_code),
+        })
+        .collect()
+}
+
+/// The table entry for a profile's free-text `country`, by name or ISO code.
+pub fn calling_country(name: &str) -> Option<&'static CallingCountry> {
+    let iso = country::find(name)?.iso;
+    CALLING_COUNTRIES.iter().find(|c| c.iso == iso)
+}
+
+/// Parses `input` into E.164 for a user in `country`, or returns an error
+/// code and message.
+///
+/// Numbers with a `+` or `00` prefix carry their own country code, which
+/// must then match `country` when it is in the table. Numbers without one
+/// are read as national numbers of `country`, with or without its trunk
+/// prefix.
+pub fn to_e164(input: &str, country: &str) -> Result<String, (&'static str, String)> {
+    let input = input.trim();
+    let (international, rest) = match input.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, input),
+    };
+    if !rest.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')')) {
+        return Err(("invalid_phone", "May only contain digits, spaces, dashes, dots and parentheses after an optional +".to_string()));
+    }
+    let digits: String = rest.chars().filter(char::is_ascii_digit).collect();
+    let home = calling_country(country);
+
+    let international_digits = if international {
+        Some(digits.as_str())
+    } else if let Some(stripped) = digits.strip_prefix("00") {
+        Some(stripped)
+    } else if home.is_some_and(|c| c.calling_code == INDONESIA_CALLING_CODE)
+        && digits.starts_with("628")
+    {
+        // Indonesian mobile numbers are often written as 628… without the
+        // `+`; no national number starts with 628 after the trunk 0.
+        Some(digits.as_str())
+    } else {
+        None
+    };
+
+    match international_digits {
+        Some(digits) => parse_international(digits, home, country),
+        None => {
+            let home = home.ok_or_else(|| {
+                (
+                    "missing_country_code",
+                    "Include the country code, e.g. +62 812 3456 7890".to_string(),
+                )
+            })?;
+            national_number(&digits, home)
+        }
+    }
+}
+
+fn parse_international(
+    digits: &str,
+    home: Option<&'static CallingCountry>,
+    country: &str,
+) -> Result<String, (&'static str, String)> {
+    if digits.starts_with('0') {
+        return Err(("invalid_phone", "A country code never starts with 0".to_string()));
+    }
+
+    // Calling codes are prefix-free, so at most one distinct code matches.
+    let Some(matched) = CALLING_COUNTRIES.iter().find(|c| digits.starts_with(c.calling_code)) else {
+        if let Some(home) = home {
+            return Err(country_mismatch(home, country));
+        }
+        // A country outside the table: only the general E.164 shape is known.
+        if !(MIN_E164_DIGITS..=MAX_E164_DIGITS).contains(&digits.len()) {
+            return Err((
+                "invalid_phone",
+                format!("Must have {} to {} digits including the country code", MIN_E164_DIGITS, MAX_E164_DIGITS),
+            ));
+        }
+        return Ok(format!("+{}", digits));
+    };
+
+    if let Some(home) = home {
+        if home.calling_code != matched.calling_code {
+            return Err(country_mismatch(home, country));
+        }
+    }
+    let home = home.unwrap_or(matched);
+    national_number(&digits[home.calling_code.len()..], home)
+}
+
+/// Checks the digits after the country code against `home` and returns
+/// the E.164 form. A trunk prefix is dropped, including the common
+/// `+62 0812…` slip.
+fn national_number(digits: &str, home: &CallingCountry) -> Result<String, (&'static str, String)> {
+    let national = match home.trunk_prefix {
+        Some(trunk) => digits.strip_prefix(trunk).unwrap_or(digits),
+        None => digits,
+    };
+
+    if national.starts_with('0') {
+        return Err(("invalid_phone", "Too many leading zeros".to_string()));
+    }
+    if !(home.min_digits..=home.max_digits).contains(&national.len()) {
+        let expected = if home.min_digits == home.max_digits {
+            home.min_digits.to_string()
+        } else {
+            format!("{} to {}", home.min_digits, home.max_digits)
+        };
+        return Err((
+            "invalid_length",
+            format!("Numbers in {} have {} digits after +{}", country::name(home.iso), expected, home.calling_code),
+        ));
+    }
+    Ok(format!("+{}{}", home.calling_code, national))
+}
+
+fn country_mismatch(home: &CallingCountry, country: &str) -> (&'static str, String) {
+    (
+        "country_mismatch",
+        format!("Does not match the country code of {} (+{})", country.trim(), home.calling_code),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(result: Result<String, (&'static str, String)>) -> Result<String, &'static str> {
+        result.map_err(|(code, _)| code)
+    }
+
+    #[test]
+    fn drops_the_indonesian_trunk_zero() {
+        for input in ["0812-3456-7890", "0812 3456 7890", "(0812) 3456.7890", "+62 0812 3456 7890"] {
+            assert_eq!(to_e164(input, "Indonesia"), Ok("+6281234567890".to_string()), "{}", input);
+        }
+        assert_eq!(to_e164("812 3456 7890", "ID"), Ok("+6281234567890".to_string()));
+    }
+
+    #[test]
+    fn reads_bare_628_as_indonesian_international() {
+        assert_eq!(to_e164("6281234567890", "Indonesia"), Ok("+6281234567890".to_string()));
+        assert_eq!(to_e164("62 812 3456 7890", "ID"), Ok("+6281234567890".to_string()));
+        // Only for Indonesian users; elsewhere it is a national number.
+        assert_eq!(code(to_e164("6281234567890", "Malaysia")), Err("invalid_length"));
+    }
+
+    #[test]
+    fn accepts_00_as_international_prefix() {
+        assert_eq!(to_e164("0062 812 3456 7890", "Indonesia"), Ok("+6281234567890".to_string()));
+        assert_eq!(to_e164("0065 6123 4567", "Singapore"), Ok("+6561234567".to_string()));
+        assert_eq!(to_e164("0044 20 7946 0018", "Atlantis"), Ok("+442079460018".to_string()));
+        assert_eq!(code(to_e164("000 1234 5678", "Indonesia")), Err("invalid_phone"));
+    }
+
+    #[test]
+    fn rejects_a_country_code_of_another_country() {
+        assert_eq!(code(to_e164("+65 6123 4567", "Indonesia")), Err("country_mismatch"));
+        assert_eq!(code(to_e164("0060 12 345 6789", "ID")), Err("country_mismatch"));
+        assert_eq!(code(to_e164("+7 912 345 6789", "Germany")), Err("country_mismatch"));
+    }
+
+    #[test]
+    fn shares_plus_1_between_the_us_and_canada() {
+        assert_eq!(to_e164("+1 (212) 555-0100", "United States"), Ok("+12125550100".to_string()));
+        assert_eq!(to_e164("+1 416 555 0100", "Canada"), Ok("+14165550100".to_string()));
+        assert_eq!(to_e164("416 555 0100", "CA"), Ok("+14165550100".to_string()));
+        assert_eq!(code(to_e164("+1 416 555 010", "Canada")), Err("invalid_length"));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert_eq!(code(to_e164("0812-3456-789a", "Indonesia")), Err("invalid_phone"));
+        assert_eq!(code(to_e164("812345678", "Atlantis")), Err("missing_country_code"));
+        assert_eq!(code(to_e164("+62 00812 3456 7890", "Indonesia")), Err("invalid_phone"));
+        assert_eq!(code(to_e164("0812 34", "Indonesia")), Err("invalid_length"));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::types::errors::FieldError;
+
+use super::phone;
+
+pub const MIN_YEAR: u32 = 1900;
+pub const MAX_YEAR: u32 = 2100;
+
+const MAX_EMAIL_CHARS: usize = 254;
+const MIN_PHONE_DIGITS: usize = 8;
+const MAX_PHONE_DIGITS: usize = 15;
+
+/// A check on one text field. Values are trimmed first, and every rule but
+/// `Required` passes on an empty value, so optional fields only need to
+/// leave `Required` out.
+#[derive(Clone, Copy, Debug)]
+pub enum Rule {
+    Required,
+    /// Length in characters, inclusive on both ends.
+    Length { min: usize, max: usize },
+    Email,
+    /// 8–15 digits, optionally with a leading `+` and spaces, dashes,
+    /// dots or parentheses between them. Only the shape; `Validator::phone`
+    /// also checks the number against a country.
+    Phone,
+    /// One of the listed values, ignoring case.
+    OneOf(&'static [&'static str]),
+}
+
+impl Rule {
+    /// The error code and message for `value`, if it breaks the rule.
+    fn check(self, value: &str) -> Option<(&'static str, String)> {
+        if value.is_empty() {
+            return match self {
+                Rule::Required => Some(("required", "This field is required".to_string())),
+                _ => None,
+            };
+        }
+        match self {
+            Rule::Required => None,
+            Rule::Length { min, max } => {
+                let chars = value.chars().count();
+                if chars < min {
+                    Some(("too_short", format!("Must be at least {} characters", min)))
+                } else if chars > max {
+                    Some(("too_long", format!("Must be at most {} characters", max)))
+                } else {
+                    None
+                }
+            }
+            Rule::Email => (!is_email(value))
+                .then(|| ("invalid_email", "Must be a valid email address".to_string())),
+            Rule::Phone => (!is_phone(value)).then(|| {
+                (
+                    "invalid_phone",
+                    format!("Must be a phone number of {} to {} digits", MIN_PHONE_DIGITS, MAX_PHONE_DIGITS),
+                )
+            }),
+            Rule::OneOf(allowed) => (!allowed.iter().any(|a| a.eq_ignore_ascii_case(value)))
+                .then(|| ("not_allowed", format!("Must be one of: {}", allowed.join(", ")))),
+        }
+    }
+}
+
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    value.chars().count() <= MAX_EMAIL_CHARS
+        && !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.split('.').count() >= 2
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+fn is_phone(value: &str) -> bool {
+    let rest = value.strip_prefix('+').unwrap_or(value);
+    if !rest.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')')) {
+        return false;
+    }
+    let digits = rest.chars().filter(char::is_ascii_digit).count();
+    (MIN_PHONE_DIGITS..=MAX_PHONE_DIGITS).contains(&digits)
+}
+
+/// Collects every failing field of a payload, so the caller can show all
+/// of them at once instead of one per submit.
+#[derive(Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks `value` against `rules` in order and records the first that
+    /// fails; later rules are skipped so each field gets one error.
+    pub fn text(&mut self, field: &str, value: &str, rules: &[Rule]) -> &mut Self {
+        let value = value.trim();
+        if let Some((code, message)) = rules.iter().find_map(|rule| rule.check(value)) {
+            self.error(field, code, message);
+        }
+        self
+    }
+
+    /// Like `text`, but a missing value passes. A value that is present is
+    /// checked in full, so `Some("")` fails `Required`.
+    pub fn optional_text(&mut self, field: &str, value: Option<&str>, rules: &[Rule]) -> &mut Self {
+        if let Some(value) = value {
+            self.text(field, value, rules);
+        }
+        self
+    }
+
+    /// A required phone number that parses for `country`; see
+    /// `phone::to_e164`.
+    pub fn phone(&mut self, field: &str, value: &str, country: &str) -> &mut Self {
+        let value = value.trim();
+        let failure = Rule::Required.check(value).or_else(|| phone::to_e164(value, country).err());
+        if let Some((code, message)) = failure {
+            self.error(field, code, message);
+        }
+        self
+    }
+
+    /// Records the failure of a format check such as `bank::check_bic`,
+    /// unless `field` already has an error.
+    pub fn format(&mut self, field: &str, result: Result<(), (&'static str, String)>) -> &mut Self {
+        if let Err((code, message)) = result {
+            if !self.errors.iter().any(|error| error.field == field) {
+                self.error(field, code, message);
+            }
+        }
+        self
+    }
+
+    pub fn year(&mut self, field: &str, value: u32) -> &mut Self {
+        if !(MIN_YEAR..=MAX_YEAR).contains(&value) {
+            self.error(field, "out_of_range", format!("Must be a year between {} and {}", MIN_YEAR, MAX_YEAR));
+        }
+        self
+    }
+
+    /// An end year, which also has to be no earlier than `start`.
+    pub fn end_year(&mut self, field: &str, value: Option<u32>, start: u32) -> &mut Self {
+        match value {
+            Some(end) if end > MAX_YEAR => {
+                self.year(field, end);
+            }
+            Some(end) if end < start => {
+                self.error(field, "before_start", "Must not be before the start year".to_string());
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Records `code` on `field` unless `ok`, for checks that span fields
+    /// or are not about text.
+    pub fn check(&mut self, field: &str, ok: bool, code: &str, message: impl Into<String>) -> &mut Self {
+        if !ok {
+            self.error(field, code, message.into());
+        }
+        self
+    }
+
+    /// Adds the errors of a nested payload with `prefix` before each field,
+    /// e.g. `university[1].` + `gpa`.
+    pub fn nested(&mut self, prefix: &str, errors: Vec<FieldError>) -> &mut Self {
+        self.errors.extend(errors.into_iter().map(|error| FieldError {
+            field: format!("{}{}", prefix, error.field),
+            ..error
+        }));
+        self
+    }
+
+    pub fn finish(self) -> Result<(), Vec<FieldError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn error(&mut self, field: &str, code: &str, message: String) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            code: code.to_string(),
+            message,
+        });
+    }
+}
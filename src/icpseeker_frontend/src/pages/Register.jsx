@@ -20,6 +20,7 @@ const Register = () => {
   const navigate = useNavigate();
   const [step, setStep] = useState(0);
   const [error, setError] = useState("");
+  const [fieldErrors, setFieldErrors] = useState({});
   const [loading, setLoading] = useState(false);
   const [authManager, setAuthManager] = useState(null);
 
@@ -75,6 +76,7 @@ const Register = () => {
     e.preventDefault();
     setLoading(true);
     setError("");
+    setFieldErrors({});
 
     try {
       if (!authManager) {
@@ -115,6 +117,10 @@ const Register = () => {
 
       if ("Success" in response) {
         setStep(1);
+      } else if ("Invalid" in response) {
+        setFieldErrors(
+          Object.fromEntries(response.Invalid.map((e) => [e.field, e.message]))
+        );
       } else {
         setError(response.Error || "Failed to create/update user");
       }
@@ -126,6 +132,11 @@ const Register = () => {
     }
   };
 
+  const renderFieldError = (field) =>
+    fieldErrors[field] && (
+      <p className="mt-1 text-sm text-red-600">{fieldErrors[field]}</p>
+    );
+
   const renderUserProfileForm = () => (
     <form onSubmit={handleUserProfileSubmit} className="space-y-4">
       <div>
@@ -139,6 +150,7 @@ const Register = () => {
           className="mt-1 block w-full rounded-md border-gray-300 shadow-sm focus:border-blue-500 focus:ring-blue-500"
           required
         />
+        {renderFieldError("name")}
       </div>
       <div>
         <label className="block text-sm font-medium text-gray-700">Email</label>
@@ -151,6 +163,7 @@ const Register = () => {
           className="mt-1 block w-full rounded-md border-gray-300 shadow-sm focus:border-blue-500 focus:ring-blue-500"
           required
         />
+        {renderFieldError("email")}
       </div>
       <div>
         <label className="block text-sm font-medium text-gray-700">
@@ -165,6 +178,7 @@ const Register = () => {
           className="mt-1 block w-full rounded-md border-gray-300 shadow-sm focus:border-blue-500 focus:ring-blue-500"
           required
         />
+        {renderFieldError("phone_number")}
       </div>
       <div>
         <label className="block text-sm font-medium text-gray-700">City</label>
@@ -177,6 +191,7 @@ const Register = () => {
           className="mt-1 block w-full rounded-md border-gray-300 shadow-sm focus:border-blue-500 focus:ring-blue-500"
           required
         />
+        {renderFieldError("city")}
       </div>
       <div>
         <label className="block text-sm font-medium text-gray-700">
@@ -191,6 +206,7 @@ const Register = () => {
           className="mt-1 block w-full rounded-md border-gray-300 shadow-sm focus:border-blue-500 focus:ring-blue-500"
           required
         />
+        {renderFieldError("country")}
       </div>
       <button
         type="submit"